@@ -0,0 +1,351 @@
+use std::path::Path;
+use std::time::Duration;
+
+use log::warn;
+use sdl2::audio::{
+    AudioCVT, AudioCallback, AudioDevice, AudioFormat, AudioSpecDesired, AudioSpecWAV,
+};
+use sdl2::Sdl;
+
+/// Sample rate requested for the beep, in samples per second
+const SAMPLE_RATE: i32 = 44_100;
+
+/// Frequency of the beep, in Hz
+const BEEP_FREQUENCY: f32 = 440.0;
+
+/// Volume of the beep, between 0 and 1
+const BEEP_VOLUME: f32 = 0.25;
+
+/// Rate at which the sound timer ticks, in Hz
+const TIMER_RATE: i32 = 60;
+
+/// Sound played while a beep lasts
+enum Tone {
+    /// A square wave, at the given phase (between 0 and 1) and phase
+    /// increment per sample
+    Square { phase: f32, phase_inc: f32 },
+    /// A recorded sample, looped if the beep lasts longer than it, at the
+    /// given position
+    Sample { samples: Vec<f32>, pos: usize },
+}
+
+/// A tone that plays for a given amount of samples, and is silent afterwards
+struct Beep {
+    tone: Tone,
+    volume: f32,
+    remaining: usize,
+    /// Samples that still play even if the beep is stopped, so that it
+    /// lasts at least the minimum length
+    minimum: usize,
+}
+
+impl Beep {
+    /// Plays the tone for the given amount of samples, replacing any beep
+    /// still playing. A beep that starts plays for at least `minimum`
+    /// samples, even if it's stopped (with a length of 0) earlier.
+    ///
+    /// # Arguments
+    ///
+    /// * `length` - Length of the beep, in samples
+    /// * `minimum` - Minimum length of a beep, in samples
+    fn start(&mut self, length: usize, minimum: usize) {
+        // A sample starts over unless the beep is just extended
+        let silent = self.remaining == 0;
+        if let Tone::Sample { pos, .. } = &mut self.tone {
+            if silent {
+                *pos = 0;
+            }
+        }
+        if silent && length > 0 {
+            self.minimum = minimum;
+        }
+        self.remaining = length.max(self.minimum);
+    }
+
+    /// Fills a buffer with the samples that will play next, without
+    /// playing them
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - Buffer to fill
+    fn peek(&self, out: &mut [f32]) {
+        for (offset, sample) in out.iter_mut().enumerate() {
+            *sample = if offset < self.remaining {
+                self.tone.value_at(offset, self.volume)
+            } else {
+                0.0
+            };
+        }
+    }
+}
+
+impl Tone {
+    /// Returns the value of the tone the given amount of samples from now
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - Amount of samples from the current one
+    /// * `volume` - Volume of the square wave, between 0 and 1
+    fn value_at(&self, offset: usize, volume: f32) -> f32 {
+        match self {
+            Tone::Square { phase, phase_inc } => {
+                if (phase + phase_inc * offset as f32) % 1.0 < 0.5 {
+                    volume
+                } else {
+                    -volume
+                }
+            }
+            Tone::Sample { samples, pos } => samples[(pos + offset) % samples.len()],
+        }
+    }
+
+    /// Moves on to the next sample of the tone
+    fn advance(&mut self) {
+        match self {
+            Tone::Square { phase, phase_inc } => *phase = (*phase + *phase_inc) % 1.0,
+            Tone::Sample { samples, pos } => *pos = (*pos + 1) % samples.len(),
+        }
+    }
+}
+
+impl AudioCallback for Beep {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            if self.remaining == 0 {
+                *sample = 0.0;
+                continue;
+            }
+
+            *sample = self.tone.value_at(0, self.volume);
+            self.tone.advance();
+            self.remaining -= 1;
+            self.minimum = self.minimum.saturating_sub(1);
+        }
+    }
+}
+
+/// Plays the beep of the sound timer. Beeps are timed in audio samples
+/// rather than frames, so even a single timer tick is audible for exactly
+/// 1/60th of a second.
+pub struct Beeper {
+    device: AudioDevice<Beep>,
+    samples_per_tick: usize,
+    min_samples: usize,
+}
+
+impl Beeper {
+    /// Opens the default audio device to play beeps on
+    ///
+    /// # Arguments
+    ///
+    /// * `sdl_context` - SDL2 context to open the audio device with
+    /// * `wav` - WAV file to play as the beep instead of a square wave, if any.
+    ///   If it can't be loaded, the square wave is used.
+    /// * `min_beep` - Shortest time a beep plays for, however short the game
+    ///   sets it
+    pub fn new(sdl_context: &Sdl, wav: Option<&Path>, min_beep: Duration) -> Result<Self, String> {
+        let audio_subsystem = sdl_context.audio()?;
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: None,
+        };
+
+        let device = audio_subsystem.open_playback(None, &desired, |spec| {
+            let square = Tone::Square {
+                phase: 0.0,
+                phase_inc: BEEP_FREQUENCY / spec.freq as f32,
+            };
+            let tone = match wav.map(|path| (path, load_sample(path, spec.freq))) {
+                Some((_, Ok(samples))) => Tone::Sample { samples, pos: 0 },
+                Some((path, Err(err))) => {
+                    warn!(
+                        "Couldn't load {}, using a square wave: {}",
+                        path.display(),
+                        err
+                    );
+                    square
+                }
+                None => square,
+            };
+
+            Beep {
+                tone,
+                volume: BEEP_VOLUME,
+                remaining: 0,
+                minimum: 0,
+            }
+        })?;
+        let freq = device.spec().freq;
+        let samples_per_tick = (freq / TIMER_RATE) as usize;
+        let min_samples = (min_beep.as_secs_f64() * freq as f64) as usize;
+        device.resume();
+
+        Ok(Self {
+            device,
+            samples_per_tick,
+            min_samples,
+        })
+    }
+
+    /// Starts a beep lasting the given amount of sound timer ticks, replacing
+    /// any beep still playing. A length of 0 stops the beep, once it played
+    /// for the minimum length.
+    ///
+    /// # Arguments
+    ///
+    /// * `ticks` - Length of the beep, in 60 Hz timer ticks
+    pub fn beep(&mut self, ticks: u8) {
+        let length = ticks as usize * self.samples_per_tick;
+        self.device.lock().start(length, self.min_samples);
+    }
+
+    /// Fills a buffer with the samples that are about to play (silence
+    /// once the beep ends), e.g. to display the waveform
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - Buffer to fill
+    pub fn waveform(&mut self, out: &mut [f32]) {
+        self.device.lock().peek(out);
+    }
+}
+
+/// Loads a WAV file as mono samples at the given sample rate
+///
+/// # Arguments
+///
+/// * `path` - Path of the WAV file
+/// * `freq` - Sample rate to convert the file to, in samples per second
+fn load_sample(path: &Path, freq: i32) -> Result<Vec<f32>, String> {
+    let wav = AudioSpecWAV::load_wav(path)?;
+    let cvt = AudioCVT::new(
+        wav.format,
+        wav.channels,
+        wav.freq,
+        AudioFormat::f32_sys(),
+        1,
+        freq,
+    )?;
+
+    let samples = to_f32_samples(&cvt.convert(wav.buffer().to_vec()));
+    if samples.is_empty() {
+        return Err("The file has no samples".to_string());
+    }
+    Ok(samples)
+}
+
+/// Reads raw audio data in the native 32-bit float format as samples
+///
+/// # Arguments
+///
+/// * `bytes` - The raw audio data
+fn to_f32_samples(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::audio::AudioCallback;
+
+    use super::{to_f32_samples, Beep, Tone};
+
+    #[test]
+    fn square_wave_plays_exact_amount_of_samples() {
+        let mut beep = Beep {
+            tone: Tone::Square {
+                phase: 0.0,
+                phase_inc: 0.25,
+            },
+            volume: 0.5,
+            remaining: 6,
+            minimum: 0,
+        };
+        let mut out = [1.0; 10];
+
+        beep.callback(&mut out);
+        assert_eq!(out, [0.5, 0.5, -0.5, -0.5, 0.5, 0.5, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(beep.remaining, 0);
+    }
+
+    #[test]
+    fn sample_loops_while_beep_lasts() {
+        let mut beep = Beep {
+            tone: Tone::Sample {
+                samples: vec![0.1, 0.2, 0.3],
+                pos: 0,
+            },
+            volume: 0.5,
+            remaining: 5,
+            minimum: 0,
+        };
+        let mut out = [1.0; 7];
+
+        beep.callback(&mut out);
+        assert_eq!(out, [0.1, 0.2, 0.3, 0.1, 0.2, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn short_beeps_last_minimum_length() {
+        let mut beep = Beep {
+            tone: Tone::Square {
+                phase: 0.0,
+                phase_inc: 0.25,
+            },
+            volume: 0.5,
+            remaining: 0,
+            minimum: 0,
+        };
+        let mut out = [1.0; 2];
+
+        beep.start(1, 4);
+        assert_eq!(beep.remaining, 4);
+
+        // Stopping the beep early only cuts it once the minimum has played
+        beep.callback(&mut out);
+        beep.start(0, 4);
+        assert_eq!(beep.remaining, 2);
+        beep.callback(&mut out);
+        assert_eq!(beep.remaining, 0);
+
+        // Without a minimum, stopping is immediate
+        beep.start(3, 0);
+        beep.start(0, 0);
+        assert_eq!(beep.remaining, 0);
+    }
+
+    #[test]
+    fn peek_matches_played_samples() {
+        let mut beep = Beep {
+            tone: Tone::Sample {
+                samples: vec![0.1, 0.2, 0.3],
+                pos: 2,
+            },
+            volume: 0.5,
+            remaining: 4,
+            minimum: 0,
+        };
+        let mut peeked = [1.0; 6];
+        let mut played = [1.0; 6];
+
+        beep.peek(&mut peeked);
+        beep.callback(&mut played);
+        assert_eq!(peeked, played);
+        assert_eq!(peeked, [0.3, 0.1, 0.2, 0.3, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn to_f32_samples_reads_native_floats() {
+        let bytes: Vec<u8> = [0.5f32, -1.0]
+            .iter()
+            .flat_map(|sample| sample.to_ne_bytes())
+            .collect();
+
+        assert_eq!(to_f32_samples(&bytes), vec![0.5, -1.0]);
+    }
+}
@@ -1,4 +1,14 @@
-use rand::random;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::disasm::{self, DecodedOp};
+use crate::error::Error;
+pub use crate::error::{Chip8Error, LoadError};
+use crate::palette::{Palette, Rgb};
+use crate::romdb;
 
 /// Width of the screen in pixels (before any scaling is applied)
 pub const SCREEN_WIDTH: usize = 64;
@@ -9,24 +19,70 @@ pub const SCREEN_HEIGHT: usize = 32;
 /// Total amount of pixels used in the screen
 const SCREEN_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
 
+/// Width of the screen in pixels in the SCHIP high resolution mode
+pub const HIRES_WIDTH: usize = 128;
+
+/// Height of the screen in pixels in the SCHIP high resolution mode
+pub const HIRES_HEIGHT: usize = 64;
+
 /// Total amount of bytes used in the RAM
-const RAM_SIZE: usize = 4096;
+pub const RAM_SIZE: usize = 4096;
 
 /// Total amount of registers (V0 to VF)
-const NUM_REGS: usize = 16;
+pub const NUM_REGS: usize = 16;
 
-/// Total amount of stack levels
-const STACK_SIZE: usize = 16;
+/// Default amount of stack levels (how deeply subroutine calls can be nested)
+pub const DEFAULT_STACK_DEPTH: usize = 16;
+
+/// Default amount of recently executed instructions kept in the trace
+pub const DEFAULT_TRACE_DEPTH: usize = 32;
 
 /// Starting address of the program
-const START_ADDR: u16 = 0x200;
+pub const START_ADDR: u16 = 0x200;
 
 /// Total amount of keys in the keypad
-const NUM_KEYS: usize = 16;
+pub const NUM_KEYS: usize = 16;
+
+/// Maximum amount of rows a single sprite can have
+const MAX_SPRITE_ROWS: u16 = 16;
+
+/// Maximum amount of columns a single sprite can have
+const MAX_SPRITE_WIDTH: u16 = 16;
+
+/// Size (both width and height) of the large sprites drawn by `DXY0` (SCHIP)
+const LARGE_SPRITE_SIZE: u16 = 16;
+
+/// Default maximum amount of pixels a single sprite draw can flip
+/// (a 16x16 sprite is the largest legitimate one)
+pub const DEFAULT_SPRITE_PIXEL_LIMIT: usize = 16 * 16;
+
+/// Time between two timer ticks (timers tick at 60 Hz)
+const TIMER_PERIOD: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Amount of pixels the SCHIP scroll opcodes move the screen by in lores mode,
+/// following the convention of scrolling by half of the hires amount
+const LORES_SCROLL_HALF: usize = 2;
+
+/// Amount of pixels the SCHIP scroll opcodes move the screen by in lores mode,
+/// when forced to scroll full pixels
+const LORES_SCROLL_FULL: usize = 4;
+
+/// Amount of pixels the SCHIP horizontal scroll opcodes move the screen by in
+/// hires mode
+const HIRES_SCROLL: usize = 4;
 
 /// Amount of bytes used for the fontset
 const FONTSET_SIZE: usize = 80;
 
+/// Amount of glyphs in a fontset, one per hexadecimal digit
+const NUM_GLYPHS: usize = 16;
+
+/// Default address of the fontset in the RAM
+pub const DEFAULT_FONT_BASE: u16 = 0x000;
+
+/// Default size of each glyph of the fontset, in bytes
+pub const DEFAULT_FONT_GLYPH_SIZE: u16 = 5;
+
 /// Chip-8 fontset
 const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -47,155 +103,1304 @@ const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+/// Every opcode pattern supported by the virtual machine, along with a
+/// short description of what it does
+pub const OPCODES: &[(&str, &str)] = &[
+    ("0000", "No operation"),
+    ("00E0", "Clear the display"),
+    ("00EE", "Return from a subroutine"),
+    ("00FB", "Scroll the display right (SCHIP)"),
+    ("00FC", "Scroll the display left (SCHIP)"),
+    ("00FD", "Exit the interpreter, halting the machine (SCHIP)"),
+    ("00FE", "Switch to the low resolution mode (SCHIP)"),
+    ("00FF", "Switch to the high resolution mode (SCHIP)"),
+    ("1NNN", "Jump to address NNN"),
+    ("2NNN", "Call the subroutine at address NNN"),
+    ("3XNN", "Skip the next instruction if VX == NN"),
+    ("4XNN", "Skip the next instruction if VX != NN"),
+    ("5XY0", "Skip the next instruction if VX == VY"),
+    ("6XNN", "Set VX to NN"),
+    ("7XNN", "Add NN to VX (without carry)"),
+    ("8XY0", "Set VX to VY"),
+    ("8XY1", "Set VX to VX OR VY"),
+    ("8XY2", "Set VX to VX AND VY"),
+    ("8XY3", "Set VX to VX XOR VY"),
+    ("8XY4", "Add VY to VX, setting VF on carry"),
+    ("8XY5", "Subtract VY from VX, clearing VF on borrow"),
+    (
+        "8XY6",
+        "Shift VX right by one, storing the dropped bit in VF",
+    ),
+    ("8XY7", "Set VX to VY - VX, clearing VF on borrow"),
+    (
+        "8XYE",
+        "Shift VX left by one, storing the dropped bit in VF",
+    ),
+    ("9XY0", "Skip the next instruction if VX != VY"),
+    ("ANNN", "Set I to NNN"),
+    ("BNNN", "Jump to address NNN + V0"),
+    ("CXNN", "Set VX to a random byte AND NN"),
+    (
+        "DXYN",
+        "Draw an N-row sprite at (VX, VY), setting VF on collision",
+    ),
+    (
+        "DXY0",
+        "Draw a 16x16 sprite at (VX, VY) in hires mode (SCHIP)",
+    ),
+    (
+        "EX9E",
+        "Skip the next instruction if the key in VX is pressed",
+    ),
+    (
+        "EXA1",
+        "Skip the next instruction if the key in VX is not pressed",
+    ),
+    ("FX07", "Set VX to the delay timer"),
+    ("FX0A", "Wait for a keypress and store it in VX"),
+    ("FX15", "Set the delay timer to VX"),
+    ("FX18", "Set the sound timer to VX"),
+    ("FX1E", "Add VX to I"),
+    ("FX29", "Set I to the font sprite of the digit in VX"),
+    (
+        "FX33",
+        "Store the BCD representation of VX at I, I+1 and I+2",
+    ),
+    ("FX55", "Store V0 to VX in RAM starting at I"),
+    ("FX65", "Load V0 to VX from RAM starting at I"),
+];
+
+/// Amount of distinct opcode patterns supported by the virtual machine
+pub const OPCODE_COUNT: usize = OPCODES.len();
+
+/// Returns whether an opcode matches a pattern from [`OPCODES`], where hex
+/// digits must match exactly and letters match any digit
+///
+/// # Arguments
+///
+/// * `pattern` - The opcode pattern, e.g. `"8XY4"`
+/// * `op` - The operation code to match
+fn matches_pattern(pattern: &str, op: u16) -> bool {
+    pattern.chars().enumerate().all(|(i, c)| {
+        let digit = (op >> (12 - 4 * i)) & 0xF;
+        c.to_digit(16)
+            .is_none_or(|expected| digit as u32 == expected)
+    })
+}
+
+/// Returns the pattern and description of a supported opcode, or `None` if
+/// the virtual machine doesn't support it. When several patterns match, the
+/// most specific one (with the fewest wildcards) is returned.
+///
+/// # Arguments
+///
+/// * `op` - The operation code to describe
+pub fn describe_opcode(op: u16) -> Option<(&'static str, &'static str)> {
+    OPCODES
+        .iter()
+        .filter(|(pattern, _)| matches_pattern(pattern, op))
+        .min_by_key(|(pattern, _)| pattern.chars().filter(|c| !c.is_ascii_hexdigit()).count())
+        .copied()
+}
+
+/// Unpacks a display packed by `Chip8::display_packed` into one boolean per
+/// pixel, in the same order as `Chip8::get_display`
+///
+/// # Arguments
+///
+/// * `packed` - The packed display, 8 pixels per byte
+pub fn unpack_display(packed: &[u8]) -> Vec<bool> {
+    packed
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |bit| byte & (1 << bit) != 0))
+        .collect()
+}
+
+/// How sprites are combined with the pixels already on the screen
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DrawMode {
+    /// Sprite pixels are XORed onto the screen, as per the Chip-8 spec
+    #[default]
+    Xor,
+    /// Sprite pixels are always turned on (non-standard, meant for debugging
+    /// sprite shapes without XOR interference)
+    Set,
+}
+
+/// What the machine does when the program runs a disabled opcode
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DisabledOpcodeAction {
+    /// The opcode does nothing
+    #[default]
+    Skip,
+    /// The opcode stops the program with an error
+    Fail,
+}
+
+/// What the machine does when `FX33` / `FX55` / `FX65` store or load bytes
+/// past the end of the RAM (e.g. when `I + X` is past the last address)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RamOverflowAction {
+    /// The opcode stops the program with an error
+    #[default]
+    Fail,
+    /// Addresses past the end wrap around to the start of the RAM
+    Wrap,
+    /// Addresses past the end are clamped to the last address of the RAM
+    Clamp,
+}
+
+/// When the machine reports a beep to the frontend (see `Chip8::take_beep`)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BeepTrigger {
+    /// When `FX18` sets the sound timer, with a beep lasting as many timer
+    /// ticks as the sound timer was set to
+    #[default]
+    Set,
+    /// When the sound timer runs out, i.e. exactly as it goes from 1 to 0,
+    /// with a beep lasting a single timer tick
+    RunOut,
+}
+
+/// Compatibility settings for behaviors that differ between Chip-8 interpreters
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    /// Whether the `00FB` / `00FC` scroll opcodes move the screen by 4 pixels
+    /// in lores mode, instead of 2 (half of the hires amount)
+    pub lores_scroll_full: bool,
+    /// Whether `FX1E` sets VF when `I + VX` overflows past `0x0FFF`
+    /// (the "Amiga" behavior, needed by games like Spacefight 2091)
+    pub fx1e_sets_vf: bool,
+    /// Whether the program counter wraps around to the start of the RAM when
+    /// it runs off the end, instead of stopping with an error
+    pub wrap_pc: bool,
+    /// Whether `8XY6` / `8XYE` shift VY and store the result in VX, instead of
+    /// shifting VX in place (the original COSMAC VIP behavior)
+    pub shift_vy: bool,
+    /// Whether `FX55` / `FX65` leave I pointing past the last register stored
+    /// or loaded, instead of leaving it untouched
+    pub load_store_increments_i: bool,
+    /// Whether `BNNN` jumps to `NNN + VX` (reading X from the opcode), instead
+    /// of `NNN + V0` (the SCHIP behavior)
+    pub jump_vx: bool,
+    /// Whether `8XY1`, `8XY2` and `8XY3` reset VF to 0
+    pub vf_reset: bool,
+    /// Whether sprites are clipped at the edges of the screen, instead of
+    /// wrapping around to the opposite edge. Only the sprite's starting
+    /// position wraps (e.g. X = 66 draws from X = 2), as legacy interpreters
+    /// that drew sprites a byte at a time did.
+    pub clip_sprites: bool,
+    /// Whether sprite pixels clipped by the `clip_sprites` quirk still set VF
+    /// when the pixel they would have wrapped onto is lit, as some SCHIP
+    /// interpreters do. Clipped pixels are never drawn either way.
+    pub clip_counts_collision: bool,
+    /// Whether drawing a sprite waits for the next frame (the display's
+    /// vertical blank) before executing any more instructions
+    pub display_wait: bool,
+    /// Whether arithmetic opcodes that set VF store their result after the
+    /// flag, so that the result wins when VF is the destination
+    pub vf_result_last: bool,
+    /// Whether pixels lit at the end of the previous frame stay lit on the
+    /// display (as returned by `get_display`) for the current frame, hiding
+    /// the "off" phase of sprites that are erased and redrawn. Collisions are
+    /// still computed from the actual pixels.
+    pub anti_flicker: bool,
+    /// Whether the `00FE` / `00FF` opcodes clear the display when they switch
+    /// to a different resolution mode, as modern interpreters like Octo do,
+    /// instead of leaving it untouched like some SCHIP versions
+    pub res_switch_clears: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl Quirks {
+    /// Default compatibility settings, usable in constants (e.g. tables of
+    /// known ROMs)
+    pub const DEFAULT: Quirks = Quirks {
+        lores_scroll_full: false,
+        fx1e_sets_vf: false,
+        wrap_pc: false,
+        shift_vy: false,
+        load_store_increments_i: false,
+        jump_vx: false,
+        vf_reset: false,
+        clip_sprites: false,
+        clip_counts_collision: false,
+        display_wait: false,
+        vf_result_last: false,
+        anti_flicker: false,
+        res_switch_clears: true,
+    };
+
+    /// Returns the name and current value of every quirk
+    pub fn flags(&self) -> Vec<(&'static str, bool)> {
+        vec![
+            ("lores_scroll_full", self.lores_scroll_full),
+            ("fx1e_sets_vf", self.fx1e_sets_vf),
+            ("wrap_pc", self.wrap_pc),
+            ("shift_vy", self.shift_vy),
+            ("load_store_increments_i", self.load_store_increments_i),
+            ("jump_vx", self.jump_vx),
+            ("vf_reset", self.vf_reset),
+            ("clip_sprites", self.clip_sprites),
+            ("clip_counts_collision", self.clip_counts_collision),
+            ("display_wait", self.display_wait),
+            ("vf_result_last", self.vf_result_last),
+            ("anti_flicker", self.anti_flicker),
+            ("res_switch_clears", self.res_switch_clears),
+        ]
+    }
+
+    /// Sets the value of a quirk by its name, as returned by `flags`.
+    /// Returns `false` if there's no quirk with that name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the quirk
+    /// * `value` - Whether the quirk is enabled
+    pub fn set(&mut self, name: &str, value: bool) -> bool {
+        let flag = match name {
+            "lores_scroll_full" => &mut self.lores_scroll_full,
+            "fx1e_sets_vf" => &mut self.fx1e_sets_vf,
+            "wrap_pc" => &mut self.wrap_pc,
+            "shift_vy" => &mut self.shift_vy,
+            "load_store_increments_i" => &mut self.load_store_increments_i,
+            "jump_vx" => &mut self.jump_vx,
+            "vf_reset" => &mut self.vf_reset,
+            "clip_sprites" => &mut self.clip_sprites,
+            "clip_counts_collision" => &mut self.clip_counts_collision,
+            "display_wait" => &mut self.display_wait,
+            "vf_result_last" => &mut self.vf_result_last,
+            "anti_flicker" => &mut self.anti_flicker,
+            "res_switch_clears" => &mut self.res_switch_clears,
+            _ => return false,
+        };
+        *flag = value;
+        true
+    }
+}
+
+/// What happened during a single frame run by `Chip8::advance_frame`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameReport {
+    /// Amount of instructions executed during the frame
+    pub cycles: usize,
+    /// Whether the display changed during the frame, i.e. whether any pixel
+    /// was toggled by a sprite draw or a screen clear
+    pub drew: bool,
+    /// Length of the beep that should start, in 60 Hz timer ticks, if one
+    /// was reported during the frame (see `Chip8::take_beep`)
+    pub beep: Option<u8>,
+    /// Error that stopped the machine during the frame, if any
+    pub error: Option<Chip8Error>,
+}
+
+/// A copy of the whole state of a Chip-8 virtual machine at a given point in time
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chip8Snapshot {
+    /// Program counter
+    pub pc: u16,
+    /// Contents of the RAM
+    pub ram: [u8; RAM_SIZE],
+    /// Screen buffer, at the size given by `hires`
+    pub screen: Vec<bool>,
+    /// Whether the machine is in the SCHIP high resolution mode
+    pub hires: bool,
+    /// V0 to VF registers
+    pub v_reg: [u8; NUM_REGS],
+    /// I register
+    pub i_reg: u16,
+    /// Stack pointer
+    pub sp: u16,
+    /// Contents of the stack
+    pub stack: Vec<u16>,
+    /// Pressed state of each key in the keypad
+    pub keys: [bool; NUM_KEYS],
+    /// Delay timer
+    pub dt: u8,
+    /// Sound timer
+    pub st: u8,
+}
+
 /// A Chip8 virtual machine implementation
+#[derive(Clone)]
 pub struct Chip8 {
     pc: u16,
     ram: [u8; RAM_SIZE],
-    screen: [bool; SCREEN_SIZE],
+    screen: Vec<bool>,
+    shown: Vec<bool>,
+    last_frame: Vec<bool>,
     v_reg: [u8; NUM_REGS],
     i_reg: u16,
     sp: u16,
-    stack: [u16; STACK_SIZE],
+    stack: Vec<u16>,
     keys: [bool; NUM_KEYS],
+    latched: [bool; NUM_KEYS],
+    pressed_at: [u64; NUM_KEYS],
+    presses: u64,
     dt: u8,
     st: u8,
+    timer_acc: Duration,
+    waiting_for_vblank: bool,
+    halted: bool,
+    hires: bool,
+    beep: Option<u8>,
+    sprite_pixel_limit: usize,
+    draw_mode: DrawMode,
+    pixels_toggled: u64,
+    last_collisions: Vec<usize>,
+    collision_counts: Vec<u32>,
+    trace: VecDeque<(u16, u16)>,
+    trace_depth: usize,
+    quirks: Quirks,
+    protect_font: bool,
+    font_base: u16,
+    font_glyph_size: u16,
+    rng: StdRng,
+    disabled_opcodes: HashSet<&'static str>,
+    disabled_opcode_action: DisabledOpcodeAction,
+    ram_overflow_action: RamOverflowAction,
+    beep_trigger: BeepTrigger,
+    rom_hash: u64,
+    opcode_counts: Option<HashMap<&'static str, u64>>,
 }
 
-impl Chip8 {
-    /// Returns a new instance of the Chip-8 virtual machine with sensible
-    /// default values
-    pub fn new() -> Self {
-        let mut chip8 = Self {
+/// Builds a Chip-8 virtual machine with custom settings
+#[derive(Clone, Debug)]
+pub struct Chip8Builder {
+    quirks: Quirks,
+    stack_depth: usize,
+    trace_depth: usize,
+    protect_font: bool,
+    font_base: u16,
+    font_glyph_size: u16,
+    seed: Option<u64>,
+    disabled_opcodes: HashSet<&'static str>,
+    disabled_opcode_action: DisabledOpcodeAction,
+    ram_overflow_action: RamOverflowAction,
+    beep_trigger: BeepTrigger,
+}
+
+impl Default for Chip8Builder {
+    fn default() -> Self {
+        Self {
+            quirks: Quirks::default(),
+            stack_depth: DEFAULT_STACK_DEPTH,
+            trace_depth: DEFAULT_TRACE_DEPTH,
+            protect_font: false,
+            font_base: DEFAULT_FONT_BASE,
+            font_glyph_size: DEFAULT_FONT_GLYPH_SIZE,
+            seed: None,
+            disabled_opcodes: HashSet::new(),
+            disabled_opcode_action: DisabledOpcodeAction::default(),
+            ram_overflow_action: RamOverflowAction::default(),
+            beep_trigger: BeepTrigger::default(),
+        }
+    }
+}
+
+impl Chip8Builder {
+    /// Sets the compatibility settings to use
+    ///
+    /// # Arguments
+    ///
+    /// * `quirks` - Compatibility settings to use
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Sets the amount of stack levels, i.e. how deeply subroutine calls
+    /// can be nested before the machine reports a stack overflow
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - Amount of stack levels
+    pub fn stack_depth(mut self, depth: usize) -> Self {
+        self.stack_depth = depth;
+        self
+    }
+
+    /// Sets the amount of recently executed instructions kept in the trace
+    /// (see `Chip8::recent_instructions`). A depth of 0 disables the trace,
+    /// so that it doesn't cost anything.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - Amount of instructions to keep
+    pub fn trace_depth(mut self, depth: usize) -> Self {
+        self.trace_depth = depth;
+        self
+    }
+
+    /// Sets whether the program is stopped with an error when it writes to
+    /// the font region of the RAM, instead of overwriting the fontset
+    ///
+    /// # Arguments
+    ///
+    /// * `protect` - Whether writes to the font region are errors
+    pub fn protect_font(mut self, protect: bool) -> Self {
+        self.protect_font = protect;
+        self
+    }
+
+    /// Sets the address the fontset is stored at, which `FX29` points `I`
+    /// into. The built-in fontset is copied there, so it must fit below
+    /// `START_ADDR`.
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - Address of the first glyph
+    pub fn font_base(mut self, base: u16) -> Self {
+        self.font_base = base;
+        self
+    }
+
+    /// Sets the size of each glyph of the fontset, for custom fonts loaded
+    /// over the built-in one (which uses 5 bytes per glyph)
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - Size of each glyph, in bytes
+    pub fn font_glyph_size(mut self, size: u16) -> Self {
+        self.font_glyph_size = size;
+        self
+    }
+
+    /// Seeds the random number generator used by `CXNN`, so that every
+    /// run of a program gets the same random numbers. By default, it's
+    /// seeded differently on every run.
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - Seed of the random number generator
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Disables an opcode, e.g. to isolate a program's behavior or to
+    /// restrict what an untrusted program can do. Running it then does
+    /// nothing, or stops the program with an error (see
+    /// `disabled_opcode_action`).
+    ///
+    /// Panics if the pattern isn't one of the supported opcodes, as listed
+    /// by [`OPCODES`] (e.g. `CXNN` or `FX55`).
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - Pattern of the opcode to disable
+    pub fn disable_opcode(mut self, pattern: &str) -> Self {
+        let Some((pattern, _)) = OPCODES.iter().find(|(known, _)| *known == pattern) else {
+            panic!("Unknown opcode pattern: {}", pattern);
+        };
+        self.disabled_opcodes.insert(pattern);
+        self
+    }
+
+    /// Sets what the machine does when the program runs a disabled opcode
+    ///
+    /// # Arguments
+    ///
+    /// * `action` - What to do on disabled opcodes
+    pub fn disabled_opcode_action(mut self, action: DisabledOpcodeAction) -> Self {
+        self.disabled_opcode_action = action;
+        self
+    }
+
+    /// Sets what the machine does when the program stores or loads registers
+    /// past the end of the RAM
+    ///
+    /// # Arguments
+    ///
+    /// * `action` - What to do on accesses past the end of the RAM
+    pub fn ram_overflow_action(mut self, action: RamOverflowAction) -> Self {
+        self.ram_overflow_action = action;
+        self
+    }
+
+    /// Sets when the machine reports a beep to the frontend
+    ///
+    /// # Arguments
+    ///
+    /// * `trigger` - What starts a beep
+    pub fn beep_trigger(mut self, trigger: BeepTrigger) -> Self {
+        self.beep_trigger = trigger;
+        self
+    }
+
+    /// Returns a new instance of the Chip-8 virtual machine with the
+    /// configured settings.
+    ///
+    /// Panics if the fontset doesn't fit below `START_ADDR` at its base.
+    pub fn build(self) -> Chip8 {
+        let font_base = self.font_base as usize;
+        assert!(
+            font_base + FONTSET_SIZE <= START_ADDR as usize,
+            "Fontset at {:#05X} overlaps the program area",
+            font_base
+        );
+
+        let mut chip8 = Chip8 {
             pc: START_ADDR,
             ram: [0; RAM_SIZE],
-            screen: [false; SCREEN_SIZE],
+            screen: vec![false; SCREEN_SIZE],
+            shown: vec![false; SCREEN_SIZE],
+            last_frame: vec![false; SCREEN_SIZE],
             v_reg: [0; NUM_REGS],
             i_reg: 0,
             sp: 0,
-            stack: [0; STACK_SIZE],
+            stack: vec![0; self.stack_depth],
             keys: [false; NUM_KEYS],
+            latched: [false; NUM_KEYS],
+            pressed_at: [0; NUM_KEYS],
+            presses: 0,
             dt: 0,
             st: 0,
+            timer_acc: Duration::ZERO,
+            waiting_for_vblank: false,
+            halted: false,
+            hires: false,
+            beep: None,
+            sprite_pixel_limit: DEFAULT_SPRITE_PIXEL_LIMIT,
+            draw_mode: DrawMode::default(),
+            pixels_toggled: 0,
+            last_collisions: Vec::new(),
+            collision_counts: vec![0; SCREEN_SIZE],
+            trace: VecDeque::with_capacity(self.trace_depth),
+            trace_depth: self.trace_depth,
+            quirks: self.quirks,
+            protect_font: self.protect_font,
+            font_base: self.font_base,
+            font_glyph_size: self.font_glyph_size,
+            rng: match self.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+            disabled_opcodes: self.disabled_opcodes,
+            disabled_opcode_action: self.disabled_opcode_action,
+            ram_overflow_action: self.ram_overflow_action,
+            beep_trigger: self.beep_trigger,
+            rom_hash: romdb::rom_hash(&[]),
+            opcode_counts: None,
         };
 
-        chip8.ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+        chip8.ram[font_base..font_base + FONTSET_SIZE].copy_from_slice(&FONTSET);
 
         chip8
     }
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chip8 {
+    /// Returns a new instance of the Chip-8 virtual machine with sensible
+    /// default values
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    /// Returns a builder to create a Chip-8 virtual machine with custom settings
+    pub fn builder() -> Chip8Builder {
+        Chip8Builder::default()
+    }
+
+    /// Returns a new instance of the Chip-8 virtual machine using the given
+    /// compatibility settings
+    ///
+    /// # Arguments
+    ///
+    /// * `quirks` - Compatibility settings to use
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        Self::builder().quirks(quirks).build()
+    }
+
+    /// Returns a read-only copy of the machine's current state
+    pub fn snapshot(&self) -> Chip8Snapshot {
+        Chip8Snapshot {
+            pc: self.pc,
+            ram: self.ram,
+            screen: self.screen.clone(),
+            hires: self.hires,
+            v_reg: self.v_reg,
+            i_reg: self.i_reg,
+            sp: self.sp,
+            stack: self.stack.clone(),
+            keys: self.keys,
+            dt: self.dt,
+            st: self.st,
+        }
+    }
 
     /// Performs one CPU tick on the Chip-8 virtual machine.
     /// Multiple CPU ticks can happen on a single frame.
-    pub fn tick(&mut self) {
-        // Fetch
-        let op = self.fetch();
+    ///
+    /// Ticks do nothing while the machine is halted, either by the `00FD`
+    /// opcode or by an error (which halts the machine and is returned).
+    /// With the `display_wait` quirk, ticks after a sprite draw also do nothing
+    /// until the timers are ticked for the next frame.
+    pub fn tick(&mut self) -> Result<(), Chip8Error> {
+        if self.halted || self.waiting_for_vblank {
+            return Ok(());
+        }
+
+        // Fetch, decode and execute
+        let result = self.fetch().and_then(|op| self.execute(op));
+        if result.is_err() {
+            self.halted = true;
+        }
+        self.update_shown();
+        result
+    }
+
+    /// Runs a whole frame: up to the given amount of instructions, followed
+    /// by one timer tick, and returns what happened during it. The frame ends
+    /// early when the machine halts (including on an error), or when it waits
+    /// for the next frame after a sprite draw (with the `display_wait` quirk).
+    ///
+    /// This is what frontends do on every frame, so that embedders can drive
+    /// the machine without a frontend. The beep is taken as in `take_beep`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ticks_per_frame` - Maximum amount of instructions to run
+    pub fn advance_frame(&mut self, ticks_per_frame: usize) -> FrameReport {
+        let toggled_before = self.pixels_toggled;
+        let mut report = FrameReport::default();
+
+        for _ in 0..ticks_per_frame {
+            if self.halted || self.waiting_for_vblank {
+                break;
+            }
+            if let Err(err) = self.tick() {
+                report.error = Some(err);
+                break;
+            }
+            report.cycles += 1;
+        }
+        self.tick_timers();
+
+        report.drew = self.pixels_toggled != toggled_before;
+        report.beep = self.take_beep();
+        report
+    }
+
+    /// Returns whether the machine is waiting for the next frame after a
+    /// sprite draw (with the `display_wait` quirk). Frontends should end the
+    /// current frame early when it is, as further ticks do nothing.
+    pub fn is_waiting_for_vblank(&self) -> bool {
+        self.waiting_for_vblank
+    }
+
+    /// Returns whether the machine is in the SCHIP high resolution mode.
+    /// Drawing in high resolution isn't supported yet, so `00FF` stops the
+    /// program with an error instead of switching to it, and the machine
+    /// always stays in the low resolution mode.
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Returns whether the machine is halted, i.e. whether it stopped
+    /// executing instructions after the `00FD` opcode or an error
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
 
-        // Decode and execute
-        self.execute(op);
+    /// Resumes executing instructions after a halt, from the instruction
+    /// following the one that halted the machine
+    pub fn resume(&mut self) {
+        self.halted = false;
     }
 
     /// Performs one timer tick on the Chip-8 virtual machine.
     /// This should happen once per frame.
     pub fn tick_timers(&mut self) {
+        self.tick_timers_dt(TIMER_PERIOD);
+    }
+
+    /// Advances the timers of the Chip-8 virtual machine by the given amount
+    /// of time, performing as many 60 Hz timer ticks as fit in it. Any leftover
+    /// time is accumulated for the next call. This should happen once per
+    /// frame, as it also marks the start of a new frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `elapsed` - Time elapsed since the last call
+    pub fn tick_timers_dt(&mut self, elapsed: Duration) {
+        self.waiting_for_vblank = false;
+        if self.quirks.anti_flicker {
+            self.update_shown();
+            self.last_frame.clone_from(&self.screen);
+        }
+        self.timer_acc += elapsed;
+
+        while self.timer_acc >= TIMER_PERIOD {
+            self.timer_acc -= TIMER_PERIOD;
+            self.decrement_timers();
+        }
+    }
+
+    /// Decrements the delay and sound timers by one, if they're active.
+    ///
+    /// Both timers tick together and independently of each other: when both
+    /// are at 1, both reach 0 on the same tick, whatever their order. With
+    /// `BeepTrigger::RunOut`, the beep fires exactly as the sound timer goes
+    /// from 1 to 0; with `BeepTrigger::Set`, it was already started when the
+    /// sound timer was set, and ends on that same tick.
+    fn decrement_timers(&mut self) {
         if self.dt > 0 {
             self.dt -= 1;
         }
 
         if self.st > 0 {
-            if self.st == 1 {
-                // TODO: implement BEEP
-            }
             self.st -= 1;
+            if self.st == 0 && self.beep_trigger == BeepTrigger::RunOut {
+                self.beep = Some(1);
+            }
         }
     }
 
-    /// Returns a slice of the screen buffer
-    pub fn get_display(&self) -> &[bool] {
-        &self.screen
+    /// Returns the length of the beep reported since the last call, in 60 Hz
+    /// timer ticks, if any. By default (`BeepTrigger::Set`), that's the value
+    /// the sound timer was last set to by `FX18`, so that the beep lasts
+    /// exactly as long as the timer regardless of frame boundaries. With
+    /// `BeepTrigger::RunOut`, it's a single tick once the sound timer runs out.
+    pub fn take_beep(&mut self) -> Option<u8> {
+        self.beep.take()
+    }
+
+    /// Returns the current value of the delay timer
+    pub fn delay_timer(&self) -> u8 {
+        self.dt
+    }
+
+    /// Returns the current value of the sound timer, i.e. how many 60 Hz
+    /// timer ticks the beep still lasts
+    pub fn sound_timer(&self) -> u8 {
+        self.st
+    }
+
+    /// Returns the address of the next instruction to execute
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// Returns the return addresses currently on the call stack, from the
+    /// outermost call to the innermost one
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp as usize]
+    }
+
+    /// Returns the stack pointer, i.e. how many subroutine calls are active
+    pub fn sp(&self) -> u16 {
+        self.sp
     }
 
-    /// Registers a keypress in the keypad
+    /// Returns the value stored in one of the V0 to VF registers
     ///
     /// # Arguments
     ///
-    /// * `idx` - Index of the key in the keypad
-    /// * `pressed` - Whether the key was pressed or released
-    pub fn keypress(&mut self, idx: usize, pressed: bool) {
-        self.keys[idx] = pressed;
+    /// * `idx` - Index of the register
+    pub fn register(&self, idx: usize) -> u8 {
+        self.v_reg[idx]
     }
 
-    /// Loads a program into the Chip-8 virtual machine
+    /// Returns the byte stored at the given RAM address
     ///
     /// # Arguments
     ///
-    /// * `data` - The program to load into the virtual machine
-    pub fn load(&mut self, data: &[u8]) {
-        let start = START_ADDR as usize;
-        let end = start + data.len();
-        self.ram[start..end].copy_from_slice(data);
+    /// * `addr` - Address of the byte to read
+    pub fn peek_ram(&self, addr: usize) -> u8 {
+        self.ram[addr]
     }
 
-    /// Returns the operation code of the next instruction to execute
-    /// according to the program counter.
-    /// Note that each instruction is 2 bytes long, stored in the RAM
-    /// as part of the loaded program.
-    fn fetch(&mut self) -> u16 {
-        let higher_byte = self.ram[self.pc as usize] as u16;
-        let lower_byte = self.ram[(self.pc + 1) as usize] as u16;
-        let op = (higher_byte << 8) | lower_byte;
-        self.pc += 2;
-        op
+    /// Decodes the instruction stored at the given RAM address, without
+    /// executing it. Addresses past the end of the RAM wrap around.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - Address of the instruction to decode
+    pub fn decode_at(&self, addr: u16) -> DecodedOp {
+        let addr = addr as usize;
+        let higher_byte = self.ram[addr % RAM_SIZE] as u16;
+        let lower_byte = self.ram[(addr + 1) % RAM_SIZE] as u16;
+        DecodedOp::new((higher_byte << 8) | lower_byte)
     }
 
-    /// Executes an operation on the Chip-8 virtual machine and updates the
-    /// machine's state accordingly.
+    /// Returns the total amount of pixels toggled on or off by sprite draws,
+    /// screen clears and scrolls since the machine was created
+    pub fn pixels_toggled(&self) -> u64 {
+        self.pixels_toggled
+    }
+
+    /// Returns the indices (in the screen buffer) of the pixels where the most
+    /// recent sprite draw collided with pixels already on the screen
+    pub fn last_collisions(&self) -> &[usize] {
+        &self.last_collisions
+    }
+
+    /// Returns how many times each pixel of the screen buffer collided with
+    /// a sprite draw since the ROM was loaded
+    pub fn collision_counts(&self) -> &[u32] {
+        &self.collision_counts
+    }
+
+    /// Returns the address and opcode of the most recently fetched
+    /// instructions (as many as the trace depth given to the builder),
+    /// oldest first. After an error, the last one is the instruction that
+    /// failed, unless the error happened while fetching it.
+    pub fn recent_instructions(&self) -> impl DoubleEndedIterator<Item = (u16, u16)> + '_ {
+        self.trace.iter().copied()
+    }
+
+    /// Forgets the collisions of the most recent sprite draw, e.g. once
+    /// a frontend has displayed them
+    pub fn clear_last_collisions(&mut self) {
+        self.last_collisions.clear();
+    }
+
+    /// Returns a slice of the screen buffer. With the `anti_flicker` quirk,
+    /// pixels lit at the end of the previous frame are lit too.
+    pub fn get_display(&self) -> &[bool] {
+        if self.quirks.anti_flicker {
+            &self.shown
+        } else {
+            &self.screen
+        }
+    }
+
+    /// Returns the display packed into 8 pixels per byte (256 bytes at
+    /// 64x32, 1024 bytes at 128x64), in the same order as `get_display` and with the leftmost
+    /// pixel in the most significant bit, to send it somewhere compactly
+    pub fn display_packed(&self) -> Vec<u8> {
+        self.get_display()
+            .chunks(8)
+            .map(|pixels| {
+                pixels
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, lit)| **lit)
+                    .fold(0, |byte, (bit, _)| byte | (0x80 >> bit))
+            })
+            .collect()
+    }
+
+    /// Renders the display into a buffer of RGBA pixels (4 bytes each, fully
+    /// opaque), row by row, with each display pixel scaled up to a
+    /// `scale` x `scale` square. The buffer is `width * scale` pixels wide,
+    /// with the width given by `display_dimensions`.
     ///
     /// # Arguments
     ///
-    /// * `op` - The operation code to execute
-    fn execute(&mut self, op: u16) {
-        let digit1 = (op & 0xF000) >> 12;
-        let digit2 = (op & 0x0F00) >> 8;
-        let digit3 = (op & 0x00F0) >> 4;
-        let digit4 = op & 0x000F;
+    /// * `scale` - Scaling factor to apply (at least 1)
+    /// * `palette` - Colors to render the display with
+    pub fn render_to_rgba(&self, scale: u32, palette: &Palette) -> Vec<u8> {
+        let scale = scale.max(1) as usize;
+        let (width, height) = self.display_dimensions();
+        let display = self.get_display();
+        let rgba = |Rgb(r, g, b): Rgb| [r, g, b, 0xFF];
+        let (on, off) = (rgba(palette.foreground), rgba(palette.background));
 
-        match (digit1, digit2, digit3, digit4) {
-            (0, 0, 0, 0) => return,
-            (0, 0, 0xE, 0) => self.screen = [false; SCREEN_SIZE],
-            (0, 0, 0xE, 0xE) => {
-                let ret_addr = self.pop();
-                self.pc = ret_addr;
+        let mut pixels = Vec::with_capacity(width * height * scale * scale * 4);
+        for row in display.chunks(width) {
+            let line: Vec<u8> = row
+                .iter()
+                .flat_map(|lit| std::iter::repeat_n(if *lit { on } else { off }, scale))
+                .flatten()
+                .collect();
+            for _ in 0..scale {
+                pixels.extend_from_slice(&line);
             }
-            (1, _, _, _) => {
-                let nnn = op & 0x0FFF;
-                self.pc = nnn;
-            }
-            (2, _, _, _) => {
-                let nnn = op & 0x0FFF;
-                self.push(self.pc);
-                self.pc = nnn;
-            }
-            (3, _, _, _) => {
-                let x = digit2 as usize;
-                let vx = self.v_reg[x];
-                let nn = (op & 0x00FF) as u8;
-                if vx == nn {
-                    self.pc += 2;
-                };
-            }
-            (4, _, _, _) => {
-                let x = digit2 as usize;
-                let vx = self.v_reg[x];
-                let nn = (op & 0x00FF) as u8;
-                if vx != nn {
-                    self.pc += 2;
-                };
-            }
-            (5, _, _, 0) => {
-                let x = digit2 as usize;
-                let vx = self.v_reg[x];
+        }
+        pixels
+    }
 
-                let y = digit3 as usize;
-                let vy = self.v_reg[y];
+    /// Updates the pixels shown with the `anti_flicker` quirk, i.e. those lit
+    /// now or at the end of the previous frame
+    fn update_shown(&mut self) {
+        if !self.quirks.anti_flicker {
+            return;
+        }
+
+        for (shown, (now, before)) in self
+            .shown
+            .iter_mut()
+            .zip(self.screen.iter().zip(self.last_frame.iter()))
+        {
+            *shown = *now || *before;
+        }
+    }
+
+    /// Returns the width and height (in pixels) of the screen buffer
+    /// returned by `get_display`: `SCREEN_WIDTH` x `SCREEN_HEIGHT` in the
+    /// low resolution mode, and `HIRES_WIDTH` x `HIRES_HEIGHT` in the high
+    /// resolution one. Renderers should use these instead of the constants,
+    /// as programs can switch modes at any time.
+    pub fn display_dimensions(&self) -> (usize, usize) {
+        if self.hires {
+            (HIRES_WIDTH, HIRES_HEIGHT)
+        } else {
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        }
+    }
+
+    /// Turns every pixel of the screen off, as the `00E0` opcode does, without
+    /// changing any other part of the machine's state
+    pub fn clear_display(&mut self) {
+        let lit = self.screen.iter().filter(|pixel| **pixel).count();
+        self.pixels_toggled += lit as u64;
+        self.screen.fill(false);
+        self.update_shown();
+    }
+
+    /// Turns a pixel of the screen on or off directly, without running any
+    /// opcode. Coordinates outside the screen are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Column of the pixel, from the left
+    /// * `y` - Row of the pixel, from the top
+    /// * `on` - Whether the pixel should be lit
+    #[cfg(feature = "debug-screen")]
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        let (width, height) = self.display_dimensions();
+        if x < width && y < height {
+            self.screen[y * width + x] = on;
+        }
+        self.update_shown();
+    }
+
+    /// Registers a keypress in the keypad. Indices past the last key
+    /// (`NUM_KEYS` and above) are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `idx` - Index of the key in the keypad
+    /// * `pressed` - Whether the key was pressed or released
+    pub fn keypress(&mut self, idx: usize, pressed: bool) {
+        if idx >= NUM_KEYS {
+            return;
+        }
+        self.set_key(idx, pressed);
+        self.latched[idx] = false;
+    }
+
+    /// Toggles a key of the keypad, for players who can't hold keys down.
+    /// A key toggled on stays pressed until it's toggled again, or until
+    /// the `FX0A` opcode accepts it. Returns whether the key is now pressed.
+    /// Indices past the last key (`NUM_KEYS` and above) are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `idx` - Index of the key in the keypad
+    pub fn toggle_key(&mut self, idx: usize) -> bool {
+        if idx >= NUM_KEYS {
+            return false;
+        }
+        let pressed = !self.keys[idx];
+        self.set_key(idx, pressed);
+        self.latched[idx] = pressed;
+        pressed
+    }
+
+    /// Sets the state of a key, numbering each new press so that the order
+    /// in which held keys were pressed is known
+    ///
+    /// # Arguments
+    ///
+    /// * `idx` - Index of the key in the keypad
+    /// * `pressed` - Whether the key is pressed
+    fn set_key(&mut self, idx: usize, pressed: bool) {
+        if pressed && !self.keys[idx] {
+            self.presses += 1;
+            self.pressed_at[idx] = self.presses;
+        }
+        self.keys[idx] = pressed;
+    }
+
+    /// Presses the key labeled with the given hexadecimal character
+    /// (`0` to `9` and `A` to `F`, in either case) in the keypad. Returns
+    /// whether the character names a key.
+    ///
+    /// # Arguments
+    ///
+    /// * `c` - Label of the key to press
+    pub fn press_key_char(&mut self, c: char) -> bool {
+        self.key_char(c, true)
+    }
+
+    /// Releases the key labeled with the given hexadecimal character
+    /// (`0` to `9` and `A` to `F`, in either case) in the keypad. Returns
+    /// whether the character names a key.
+    ///
+    /// # Arguments
+    ///
+    /// * `c` - Label of the key to release
+    pub fn release_key_char(&mut self, c: char) -> bool {
+        self.key_char(c, false)
+    }
+
+    /// Registers a keypress for the key labeled with the given hexadecimal
+    /// character, returning whether the character names a key
+    ///
+    /// # Arguments
+    ///
+    /// * `c` - Label of the key
+    /// * `pressed` - Whether the key was pressed or released
+    fn key_char(&mut self, c: char, pressed: bool) -> bool {
+        match c.to_digit(16) {
+            Some(idx) => {
+                self.keypress(idx as usize, pressed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sets the maximum amount of pixels a single sprite draw can flip.
+    /// Any pixels past the limit are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Maximum amount of pixels to draw per sprite
+    pub fn set_sprite_pixel_limit(&mut self, limit: usize) {
+        self.sprite_pixel_limit = limit;
+    }
+
+    /// Sets how sprites are combined with the pixels already on the screen.
+    /// Collisions are still reported in VF as if sprites were XORed.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - Draw mode to use
+    pub fn set_draw_mode(&mut self, mode: DrawMode) {
+        self.draw_mode = mode;
+    }
+
+    /// Sets whether the machine counts how many times each opcode pattern
+    /// runs. Counting is off by default, and turning it off discards the
+    /// counts.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to count executed opcodes
+    pub fn set_opcode_profiling(&mut self, enabled: bool) {
+        self.opcode_counts = enabled.then(HashMap::new);
+    }
+
+    /// Returns how many times each opcode pattern ran while profiling was
+    /// enabled, from the most to the least executed one
+    pub fn opcode_histogram(&self) -> Vec<(&'static str, u64)> {
+        let mut histogram: Vec<_> = self
+            .opcode_counts
+            .iter()
+            .flatten()
+            .map(|(pattern, count)| (*pattern, *count))
+            .collect();
+        histogram.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        histogram
+    }
+
+    /// Loads a program into the Chip-8 virtual machine
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The program to load into the virtual machine
+    pub fn load(&mut self, data: &[u8]) -> Result<(), LoadError> {
+        check_program(data)?;
+
+        self.load_at(START_ADDR as usize, data)?;
+        self.rom_hash = romdb::rom_hash(data);
+        self.collision_counts.fill(0);
+        self.trace.clear();
+        Ok(())
+    }
+
+    /// Replaces the running program with another one, for live patching.
+    ///
+    /// Unlike `load`, this is meant for a machine that's already running:
+    /// the program area of the RAM (from `START_ADDR` on) is cleared before
+    /// the new program is copied in, and execution restarts from its first
+    /// instruction. The call stack is emptied, as its return addresses point
+    /// into the old program, and a halted machine resumes. Everything else
+    /// is kept as is: the display, the registers (including `I`), the
+    /// timers, the pressed keys and the fontset. If the program can't be
+    /// loaded, the machine is left untouched.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The program to swap in
+    pub fn hot_swap(&mut self, data: &[u8]) -> Result<(), LoadError> {
+        check_program(data)?;
+
+        self.ram[START_ADDR as usize..].fill(0);
+        self.load(data)?;
+        self.pc = START_ADDR;
+        self.sp = 0;
+        self.halted = false;
+        Ok(())
+    }
+
+    /// Returns the hash of the program loaded with `load` (as computed by
+    /// [`romdb::rom_hash`]), which identifies the exact ROM file
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    /// Copies data into the RAM of the Chip-8 virtual machine, starting at
+    /// an arbitrary address
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - Address to copy the data to
+    /// * `data` - The data to copy
+    pub fn load_at(&mut self, addr: usize, data: &[u8]) -> Result<(), LoadError> {
+        let end = addr
+            .checked_add(data.len())
+            .filter(|end| *end <= RAM_SIZE)
+            .ok_or(LoadError::OutOfBounds {
+                addr,
+                len: data.len(),
+            })?;
+
+        self.ram[addr..end].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Writes a single byte into the RAM, e.g. to patch a loaded program.
+    /// Fails if the address is past the end of the RAM, or in the font
+    /// region while it's protected.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - Address to write to
+    /// * `value` - The byte to write
+    pub fn poke_ram(&mut self, addr: usize, value: u8) -> Result<(), Error> {
+        if addr >= RAM_SIZE {
+            return Err(LoadError::OutOfBounds { addr, len: 1 }.into());
+        }
+        self.write_ram(addr, value)?;
+        Ok(())
+    }
+
+    /// Returns the operation code of the next instruction to execute
+    /// according to the program counter.
+    /// Note that each instruction is 2 bytes long, stored in the RAM
+    /// as part of the loaded program.
+    ///
+    /// Running off the end of the RAM is an error, unless the `wrap_pc` quirk
+    /// is set, in which case the RAM is treated as circular.
+    fn fetch(&mut self) -> Result<u16, Chip8Error> {
+        let pc = self.pc as usize;
+        if !self.quirks.wrap_pc && pc + 1 >= RAM_SIZE {
+            return Err(Chip8Error::PcOutOfBounds(self.pc));
+        }
+
+        let higher_byte = self.ram[pc % RAM_SIZE] as u16;
+        let lower_byte = self.ram[(pc + 1) % RAM_SIZE] as u16;
+        let op = (higher_byte << 8) | lower_byte;
+        if self.trace_depth > 0 {
+            if self.trace.len() == self.trace_depth {
+                self.trace.pop_front();
+            }
+            self.trace.push_back((self.pc, op));
+        }
+        self.pc += 2;
+        if self.quirks.wrap_pc {
+            self.pc %= RAM_SIZE as u16;
+        }
+        Ok(op)
+    }
+
+    /// Executes an operation on the Chip-8 virtual machine and updates the
+    /// machine's state accordingly.
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - The operation code to execute
+    fn execute(&mut self, op: u16) -> Result<(), Chip8Error> {
+        let (digit1, digit2, digit3, digit4) = disasm::digits(op);
+
+        if let Some(counts) = self.opcode_counts.as_mut() {
+            if let Some((pattern, _)) = describe_opcode(op) {
+                *counts.entry(pattern).or_insert(0) += 1;
+            }
+        }
+
+        if !self.disabled_opcodes.is_empty() {
+            let disabled = describe_opcode(op)
+                .is_some_and(|(pattern, _)| self.disabled_opcodes.contains(pattern));
+            if disabled {
+                return match self.disabled_opcode_action {
+                    DisabledOpcodeAction::Skip => Ok(()),
+                    DisabledOpcodeAction::Fail => Err(Chip8Error::DisabledOpcode(op)),
+                };
+            }
+        }
+
+        match (digit1, digit2, digit3, digit4) {
+            (0, 0, 0, 0) => (),
+            (0, 0, 0xE, 0) => self.clear_display(),
+            (0, 0, 0xE, 0xE) => {
+                let ret_addr = self.pop()?;
+                self.pc = ret_addr;
+            }
+            (0, 0, 0xF, 0xB) => {
+                let amount = self.scroll_amount();
+                self.scroll_right(amount);
+            }
+            (0, 0, 0xF, 0xC) => {
+                let amount = self.scroll_amount();
+                self.scroll_left(amount);
+            }
+            (0, 0, 0xF, 0xD) => {
+                self.halted = true;
+            }
+            (0, 0, 0xF, 0xE) => {
+                self.set_hires(false);
+            }
+            (0, 0, 0xF, 0xF) => {
+                self.set_hires(true);
+            }
+            (1, _, _, _) => {
+                let nnn = op & 0x0FFF;
+                self.pc = nnn;
+            }
+            (2, _, _, _) => {
+                let nnn = op & 0x0FFF;
+                self.push(self.pc)?;
+                self.pc = nnn;
+            }
+            (3, _, _, _) => {
+                let x = digit2 as usize;
+                let vx = self.v_reg[x];
+                let nn = (op & 0x00FF) as u8;
+                if vx == nn {
+                    self.pc += 2;
+                };
+            }
+            (4, _, _, _) => {
+                let x = digit2 as usize;
+                let vx = self.v_reg[x];
+                let nn = (op & 0x00FF) as u8;
+                if vx != nn {
+                    self.pc += 2;
+                };
+            }
+            (5, _, _, 0) => {
+                let x = digit2 as usize;
+                let vx = self.v_reg[x];
+
+                let y = digit3 as usize;
+                let vy = self.v_reg[y];
 
                 if vx == vy {
                     self.pc += 2;
@@ -229,6 +1434,9 @@ impl Chip8 {
                 let vy = self.v_reg[y];
 
                 self.v_reg[x] = vx | vy;
+                if self.quirks.vf_reset {
+                    self.v_reg[0xF] = 0;
+                }
             }
             (8, _, _, 2) => {
                 let x = digit2 as usize;
@@ -238,6 +1446,9 @@ impl Chip8 {
                 let vy = self.v_reg[y];
 
                 self.v_reg[x] = vx & vy;
+                if self.quirks.vf_reset {
+                    self.v_reg[0xF] = 0;
+                }
             }
             (8, _, _, 3) => {
                 let x = digit2 as usize;
@@ -247,6 +1458,9 @@ impl Chip8 {
                 let vy = self.v_reg[y];
 
                 self.v_reg[x] = vx ^ vy;
+                if self.quirks.vf_reset {
+                    self.v_reg[0xF] = 0;
+                }
             }
             (8, _, _, 4) => {
                 let x = digit2 as usize;
@@ -258,8 +1472,7 @@ impl Chip8 {
                 let (new_vx, carry) = vx.overflowing_add(vy);
                 let new_vf = if carry { 1 } else { 0 };
 
-                self.v_reg[x] = new_vx;
-                self.v_reg[0xF] = new_vf;
+                self.set_with_flag(x, new_vx, new_vf);
             }
             (8, _, _, 5) => {
                 let x = digit2 as usize;
@@ -271,16 +1484,15 @@ impl Chip8 {
                 let (new_vx, borrow) = vx.overflowing_sub(vy);
                 let new_vf = if borrow { 0 } else { 1 };
 
-                self.v_reg[x] = new_vx;
-                self.v_reg[0xF] = new_vf;
+                self.set_with_flag(x, new_vx, new_vf);
             }
             (8, _, _, 6) => {
                 let x = digit2 as usize;
-                let vx = self.v_reg[x];
+                let y = digit3 as usize;
+                let val = self.shift_source(x, y);
 
-                let lsb = vx & 1;
-                self.v_reg[x] >>= 1;
-                self.v_reg[0xF] = lsb;
+                let lsb = val & 1;
+                self.set_with_flag(x, val >> 1, lsb);
             }
             (8, _, _, 7) => {
                 let x = digit2 as usize;
@@ -292,16 +1504,15 @@ impl Chip8 {
                 let (new_vx, borrow) = vy.overflowing_sub(vx);
                 let new_vf = if borrow { 0 } else { 1 };
 
-                self.v_reg[x] = new_vx;
-                self.v_reg[0xF] = new_vf;
+                self.set_with_flag(x, new_vx, new_vf);
             }
             (8, _, _, 0xE) => {
                 let x = digit2 as usize;
-                let vx = self.v_reg[x];
+                let y = digit3 as usize;
+                let val = self.shift_source(x, y);
 
-                let msb = (vx >> 7) & 1;
-                self.v_reg[x] <<= 1;
-                self.v_reg[0xF] = msb;
+                let msb = (val >> 7) & 1;
+                self.set_with_flag(x, val << 1, msb);
             }
             (9, _, _, 0) => {
                 let x = digit2 as usize;
@@ -320,12 +1531,17 @@ impl Chip8 {
             }
             (0xB, _, _, _) => {
                 let nnn = op & 0x0FFF;
-                self.pc = (self.v_reg[0] as u16) + nnn;
+                let reg = if self.quirks.jump_vx {
+                    digit2 as usize
+                } else {
+                    0
+                };
+                self.pc = (self.v_reg[reg] as u16) + nnn;
             }
             (0xC, _, _, _) => {
                 let x = digit2 as usize;
                 let nn = (op & 0x00FF) as u8;
-                let rng: u8 = random();
+                let rng: u8 = self.rng.gen();
                 self.v_reg[x] = rng & nn;
             }
             (0xD, _, _, _) => {
@@ -333,40 +1549,17 @@ impl Chip8 {
                 let x_coord = self.v_reg[digit2 as usize] as u16;
                 let y_coord = self.v_reg[digit3 as usize] as u16;
 
-                // Get the height of the sprite
-                let num_rows = digit4;
-
-                // Keep track of whether we've flipped a pixel
-                let mut flipped = false;
-
-                // Iterate over each row of the sprite
-                for y_line in 0..num_rows {
-                    // Check which memory address our row's data is stored on
-                    let addr = self.i_reg + y_line as u16;
-                    let pixels = self.ram[addr as usize];
-
-                    // Iterate over each column in our row (rows are 8 bits long)
-                    for x_line in 0..8 {
-                        // Use a mask to fetch current pixel's bit and only flip if it's 1
-                        if (pixels & (0b1000_0000 >> x_line)) != 0 {
-                            let x = (x_coord + x_line) as usize % SCREEN_WIDTH;
-                            let y = (y_coord + y_line) as usize % SCREEN_HEIGHT;
-
-                            // Get pixel's index for our 1D screen array
-                            let idx = x + SCREEN_WIDTH * y;
-
-                            // Check if we're about to flip and set the new value
-                            flipped |= self.screen[idx];
-                            self.screen[idx] ^= true;
-                        }
-                    }
-                }
+                // Get the size of the sprite: N rows 8 pixels wide, or a
+                // 16x16 sprite when N is 0 in the high resolution mode
+                // (SCHIP). Otherwise, N = 0 draws nothing, as on the original
+                // interpreter.
+                let (height, width) = match digit4 {
+                    0 if self.hires => (LARGE_SPRITE_SIZE, LARGE_SPRITE_SIZE),
+                    num_rows => (num_rows, 8),
+                };
 
-                if flipped {
-                    self.v_reg[0xF] = 1;
-                } else {
-                    self.v_reg[0xF] = 0;
-                }
+                self.draw_sprite(x_coord, y_coord, height, width);
+                self.waiting_for_vblank = self.quirks.display_wait;
             }
             (0xE, _, 9, 0xE) => {
                 let x = digit2 as usize;
@@ -392,18 +1585,29 @@ impl Chip8 {
             }
             (0xF, _, 0, 0xA) => {
                 let x = digit2 as usize;
-                let mut pressed = false;
 
-                for i in 0..self.keys.len() {
-                    if self.keys[i] {
+                // When several keys are held, the one pressed first wins
+                let first = (0..NUM_KEYS)
+                    .filter(|i| self.keys[*i])
+                    .min_by_key(|i| self.pressed_at[*i]);
+
+                match first {
+                    Some(i) => {
                         self.v_reg[x] = i as u8;
-                        pressed = true;
-                        break;
-                    }
-                }
 
-                if !pressed {
-                    self.pc -= 2;
+                        // A toggled key counts as a single press, otherwise
+                        // every following wait would accept it right away
+                        if self.latched[i] {
+                            self.keys[i] = false;
+                            self.latched[i] = false;
+                        }
+                    }
+                    None => {
+                        self.pc = self.pc.wrapping_sub(2);
+                        if self.quirks.wrap_pc {
+                            self.pc %= RAM_SIZE as u16;
+                        }
+                    }
                 }
             }
             (0xF, _, 1, 5) => {
@@ -413,61 +1617,1794 @@ impl Chip8 {
             (0xF, _, 1, 8) => {
                 let x = digit2 as usize;
                 self.st = self.v_reg[x];
+                if self.beep_trigger == BeepTrigger::Set {
+                    self.beep = Some(self.st);
+                }
             }
             (0xF, _, 1, 0xE) => {
                 let x = digit2 as usize;
                 let vx = self.v_reg[x] as u16;
                 self.i_reg = self.i_reg.wrapping_add(vx);
+
+                if self.quirks.fx1e_sets_vf {
+                    let overflow = self.i_reg > 0x0FFF;
+                    self.v_reg[0xF] = if overflow { 1 } else { 0 };
+                }
             }
             (0xF, _, 2, 9) => {
                 let x = digit2 as usize;
                 let c = self.v_reg[x] as u16;
-                self.i_reg = c * 5;
+                self.i_reg = self.font_base + c * self.font_glyph_size;
             }
             (0xF, _, 3, 3) => {
-                // TODO: optimize?
                 let x = digit2 as usize;
-                let vx = self.v_reg[x] as f32;
-
-                let hundreds = (vx * 100.0).floor() as u8;
-                let tens = ((vx * 10.0) % 10.0).floor() as u8;
-                let ones = (vx % 10.0) as u8;
+                let vx = self.v_reg[x];
+                let digits = [vx / 100, vx / 10 % 10, vx % 10];
 
-                self.ram[self.i_reg as usize] = hundreds;
-                self.ram[(self.i_reg + 1) as usize] = tens;
-                self.ram[(self.i_reg + 2) as usize] = ones;
+                let i = self.i_reg as usize;
+                for (idx, digit) in digits.into_iter().enumerate() {
+                    let addr = self.ram_index(i + idx)?;
+                    self.write_ram(addr, digit)?;
+                }
             }
             (0xF, _, 5, 5) => {
                 let x = digit2 as usize;
                 let i = self.i_reg as usize;
                 for idx in 0..=x {
-                    self.ram[i + idx] = self.v_reg[idx];
+                    let addr = self.ram_index(i + idx)?;
+                    self.write_ram(addr, self.v_reg[idx])?;
+                }
+                if self.quirks.load_store_increments_i {
+                    self.i_reg = self.i_reg.wrapping_add(x as u16 + 1);
                 }
             }
             (0xF, _, 6, 5) => {
                 let x = digit2 as usize;
                 let i = self.i_reg as usize;
                 for idx in 0..=x {
-                    self.v_reg[idx] = self.ram[i + idx];
+                    let addr = self.ram_index(i + idx)?;
+                    self.v_reg[idx] = self.ram[addr];
+                }
+                if self.quirks.load_store_increments_i {
+                    self.i_reg = self.i_reg.wrapping_add(x as u16 + 1);
                 }
             }
-            (_, _, _, _) => unimplemented!("Unimplemented opcode: {:X}", op),
+            (_, _, _, _) => return Err(Chip8Error::UnsupportedOpcode(op)),
         };
+
+        Ok(())
     }
 
-    /// Pushes a new value onto the machine's stack
+    /// Draws a sprite stored in RAM (starting at the address in the I register)
+    /// onto the screen, and sets VF if any pixel was flipped off. Each row of
+    /// the sprite takes as many bytes as needed to hold its width.
+    ///
+    /// Malformed sizes are clamped to the largest legitimate sprite (16x16),
+    /// and no more than `sprite_pixel_limit` pixels are drawn.
+    /// The sprite's starting position always wraps around the screen, but its
+    /// pixels are clipped at the edges with the `clip_sprites` quirk.
     ///
     /// # Arguments
     ///
-    /// * `val` - The value to push onto the stack
-    fn push(&mut self, val: u16) {
-        self.stack[self.sp as usize] = val;
-        self.sp += 1;
+    /// * `x_coord` - X coordinate of the sprite's top-left corner
+    /// * `y_coord` - Y coordinate of the sprite's top-left corner
+    /// * `height` - Height of the sprite in rows
+    /// * `width` - Width of the sprite in pixels
+    fn draw_sprite(&mut self, x_coord: u16, y_coord: u16, height: u16, width: u16) {
+        let (screen_width, screen_height) = self.display_dimensions();
+        let height = height.min(MAX_SPRITE_ROWS);
+        let width = width.min(MAX_SPRITE_WIDTH);
+        let row_bytes = width.div_ceil(8);
+        self.last_collisions.clear();
+
+        // Keep track of whether we've flipped a pixel, and how many we've drawn
+        let mut flipped = false;
+        let mut drawn = 0;
+
+        // Iterate over each row of the sprite
+        'rows: for y_line in 0..height {
+            // Iterate over each column in our row
+            for x_line in 0..width {
+                // Check which memory address the column's data is stored on
+                let offset = (y_line * row_bytes + x_line / 8) as usize;
+                let addr = (self.i_reg as usize + offset) % RAM_SIZE;
+                let pixels = self.ram[addr];
+
+                // Use a mask to fetch current pixel's bit and only flip if it's 1
+                if (pixels & (0b1000_0000 >> (x_line % 8))) != 0 {
+                    if drawn >= self.sprite_pixel_limit {
+                        break 'rows;
+                    }
+                    drawn += 1;
+
+                    let x = x_coord as usize % screen_width + x_line as usize;
+                    let y = y_coord as usize % screen_height + y_line as usize;
+                    if self.quirks.clip_sprites && (x >= screen_width || y >= screen_height) {
+                        // The pixel isn't drawn, but may still collide with
+                        // the one it would have wrapped onto
+                        if self.quirks.clip_counts_collision {
+                            flipped |=
+                                self.screen[x % screen_width + screen_width * (y % screen_height)];
+                        }
+                        continue;
+                    }
+                    let x = x % screen_width;
+                    let y = y % screen_height;
+
+                    // Get pixel's index for our 1D screen array
+                    let idx = x + screen_width * y;
+
+                    // Check if we're about to flip and set the new value
+                    flipped |= self.screen[idx];
+                    if self.screen[idx] {
+                        self.last_collisions.push(idx);
+                        self.collision_counts[idx] = self.collision_counts[idx].saturating_add(1);
+                    }
+                    let previous = self.screen[idx];
+                    match self.draw_mode {
+                        DrawMode::Xor => self.screen[idx] ^= true,
+                        DrawMode::Set => self.screen[idx] = true,
+                    }
+                    if self.screen[idx] != previous {
+                        self.pixels_toggled += 1;
+                    }
+                }
+            }
+        }
+
+        if flipped {
+            self.v_reg[0xF] = 1;
+        } else {
+            self.v_reg[0xF] = 0;
+        }
     }
 
-    /// Pops and returns a value off the machine's stack
-    fn pop(&mut self) -> u16 {
-        self.sp -= 1;
-        self.stack[self.sp as usize]
+    /// Stores the result of an operation in VX and its flag in VF, in the
+    /// order given by the `vf_result_last` quirk
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Index of the register to store the result in
+    /// * `result` - Result of the operation
+    /// * `flag` - Value to store in VF
+    fn set_with_flag(&mut self, x: usize, result: u8, flag: u8) {
+        if self.quirks.vf_result_last {
+            self.v_reg[0xF] = flag;
+            self.v_reg[x] = result;
+        } else {
+            self.v_reg[x] = result;
+            self.v_reg[0xF] = flag;
+        }
+    }
+
+    /// Switches between the low and high resolution modes, resizing the
+    /// screen buffer on a change of mode. With the `res_switch_clears` quirk
+    /// the display is cleared; otherwise the picture is kept, with each lores
+    /// pixel becoming a 2x2 block of hires pixels (and each 2x2 block becoming
+    /// a single pixel, lit if its top-left pixel was, the other way around).
+    ///
+    /// # Arguments
+    ///
+    /// * `hires` - Whether to switch to the high resolution mode
+    fn set_hires(&mut self, hires: bool) {
+        if hires == self.hires {
+            return;
+        }
+        if self.quirks.res_switch_clears {
+            self.clear_display();
+        }
+
+        let (old_width, _) = self.display_dimensions();
+        self.hires = hires;
+        let (width, height) = self.display_dimensions();
+        let ratio = (HIRES_WIDTH / SCREEN_WIDTH) as f32;
+        let scale = if hires { 1.0 / ratio } else { ratio };
+        let old = std::mem::take(&mut self.screen);
+        self.screen = (0..width * height)
+            .map(|idx| {
+                let x = ((idx % width) as f32 * scale) as usize;
+                let y = ((idx / width) as f32 * scale) as usize;
+                old[x + old_width * y]
+            })
+            .collect();
+
+        self.last_frame = vec![false; width * height];
+        self.shown = vec![false; width * height];
+        self.collision_counts = vec![0; width * height];
+        self.last_collisions.clear();
+        self.update_shown();
+    }
+
+    /// Returns the value the shift opcodes operate on: VY with the `shift_vy`
+    /// quirk, or VX otherwise
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Index of the VX register
+    /// * `y` - Index of the VY register
+    fn shift_source(&self, x: usize, y: usize) -> u8 {
+        if self.quirks.shift_vy {
+            self.v_reg[y]
+        } else {
+            self.v_reg[x]
+        }
+    }
+
+    /// Returns the amount of pixels the horizontal scroll opcodes move
+    /// the screen by
+    fn scroll_amount(&self) -> usize {
+        if self.hires {
+            HIRES_SCROLL
+        } else if self.quirks.lores_scroll_full {
+            LORES_SCROLL_FULL
+        } else {
+            LORES_SCROLL_HALF
+        }
+    }
+
+    /// Scrolls the screen to the right, filling the leftmost columns
+    /// with blank pixels
+    ///
+    /// # Arguments
+    ///
+    /// * `amount` - Amount of pixels to scroll by
+    fn scroll_right(&mut self, amount: usize) {
+        let (width, _) = self.display_dimensions();
+        let before = self.screen.clone();
+        for row in self.screen.chunks_mut(width) {
+            row.rotate_right(amount);
+            row[..amount].fill(false);
+        }
+        self.screen_changed(&before);
+    }
+
+    /// Scrolls the screen to the left, filling the rightmost columns
+    /// with blank pixels
+    ///
+    /// # Arguments
+    ///
+    /// * `amount` - Amount of pixels to scroll by
+    fn scroll_left(&mut self, amount: usize) {
+        let (width, _) = self.display_dimensions();
+        let before = self.screen.clone();
+        for row in self.screen.chunks_mut(width) {
+            row.rotate_left(amount);
+            row[width - amount..].fill(false);
+        }
+        self.screen_changed(&before);
+    }
+
+    /// Counts the pixels that changed since a previous copy of the screen
+    /// as toggled, and updates the pixels shown
+    ///
+    /// # Arguments
+    ///
+    /// * `before` - The screen before it changed
+    fn screen_changed(&mut self, before: &[bool]) {
+        let changed = self
+            .screen
+            .iter()
+            .zip(before)
+            .filter(|(now, then)| now != then)
+            .count();
+        self.pixels_toggled += changed as u64;
+        self.update_shown();
+    }
+
+    /// Pushes a new value onto the machine's stack, failing if every
+    /// stack level is already in use
+    ///
+    /// # Arguments
+    ///
+    /// * `val` - The value to push onto the stack
+    fn push(&mut self, val: u16) -> Result<(), Chip8Error> {
+        let depth = self.stack.len();
+        let slot = self
+            .stack
+            .get_mut(self.sp as usize)
+            .ok_or(Chip8Error::StackOverflow(depth))?;
+        *slot = val;
+        self.sp += 1;
+        Ok(())
+    }
+
+    /// Writes a byte to the RAM on behalf of the program, unless the
+    /// address is in the font region (every glyph from the font base on)
+    /// and it's protected
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - Address to write to
+    /// * `value` - The byte to write
+    fn write_ram(&mut self, addr: usize, value: u8) -> Result<(), Chip8Error> {
+        let font_start = self.font_base as usize;
+        let font_end = font_start + NUM_GLYPHS * self.font_glyph_size as usize;
+        if self.protect_font && (font_start..font_end).contains(&addr) {
+            return Err(Chip8Error::FontWrite(addr as u16));
+        }
+        self.ram[addr] = value;
+        Ok(())
+    }
+
+    /// Returns the index of the RAM an address accessed by the program refers
+    /// to, handling addresses past the end as set by `ram_overflow_action`
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address accessed by the program
+    fn ram_index(&self, addr: usize) -> Result<usize, Chip8Error> {
+        if addr < RAM_SIZE {
+            return Ok(addr);
+        }
+        match self.ram_overflow_action {
+            RamOverflowAction::Fail => Err(Chip8Error::RamOutOfBounds(
+                addr.min(u16::MAX as usize) as u16
+            )),
+            RamOverflowAction::Wrap => Ok(addr % RAM_SIZE),
+            RamOverflowAction::Clamp => Ok(RAM_SIZE - 1),
+        }
+    }
+
+    /// Pops and returns a value off the machine's stack, failing if it's
+    /// empty. This is called by `00EE` (after the program counter moved past
+    /// it), so the error reports the address of the return instruction.
+    fn pop(&mut self) -> Result<u16, Chip8Error> {
+        if self.sp == 0 {
+            return Err(Chip8Error::EmptyStackReturn(self.pc.wrapping_sub(2)));
+        }
+        self.sp -= 1;
+        Ok(self.stack[self.sp as usize])
+    }
+}
+
+/// Checks that a program can be loaded at the start of the program area
+///
+/// # Arguments
+///
+/// * `data` - The program to check
+fn check_program(data: &[u8]) -> Result<(), LoadError> {
+    if data.is_empty() {
+        return Err(LoadError::Empty);
+    }
+
+    if START_ADDR as usize + data.len() > RAM_SIZE {
+        return Err(LoadError::TooLarge(data.len()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describe_opcode_finds_most_specific_pattern() {
+        assert!(!OPCODES.is_empty());
+        assert_eq!(describe_opcode(0x00E0), Some(("00E0", "Clear the display")));
+        assert_eq!(describe_opcode(0x8124).unwrap().0, "8XY4");
+        assert_eq!(describe_opcode(0xD125).unwrap().0, "DXYN");
+        assert_eq!(describe_opcode(0xD120).unwrap().0, "DXY0");
+        assert_eq!(describe_opcode(0x5121), None);
+        assert_eq!(describe_opcode(0xF0FF), None);
+    }
+
+    #[test]
+    fn opcode_table_matches_disassembler() {
+        for op in 0..=0xFFFF {
+            assert_eq!(
+                describe_opcode(op).is_some(),
+                disasm::disassemble(op).is_some(),
+                "{:04X}",
+                op
+            );
+        }
+    }
+
+    #[test]
+    fn opcode_count_matches_reachable_patterns() {
+        let patterns: HashSet<_> = (0..=0xFFFF)
+            .filter_map(|op| describe_opcode(op).map(|(pattern, _)| pattern))
+            .collect();
+        assert_eq!(patterns.len(), OPCODE_COUNT);
+    }
+
+    #[test]
+    fn opcode_table_matches_execute() {
+        for (pattern, _) in OPCODES {
+            let op = pattern
+                .chars()
+                .fold(0, |op, c| (op << 4) | c.to_digit(16).unwrap_or(0) as u16);
+            let mut chip8 = Chip8::new();
+            if op == 0x00EE {
+                chip8.push(START_ADDR).unwrap();
+            }
+            assert!(chip8.execute(op).is_ok(), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn unknown_opcodes_are_errors() {
+        for op in [0x5121u16, 0xE000, 0xF0FF] {
+            let mut chip8 = Chip8::new();
+            chip8.load(&op.to_be_bytes()).unwrap();
+            assert_eq!(chip8.tick(), Err(Chip8Error::UnsupportedOpcode(op)));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "debug-screen")]
+    fn set_pixel_updates_display() {
+        let mut chip8 = Chip8::new();
+
+        chip8.set_pixel(3, 2, true);
+        chip8.set_pixel(SCREEN_WIDTH, 0, true);
+        assert!(chip8.get_display()[2 * SCREEN_WIDTH + 3]);
+        assert_eq!(chip8.get_display().iter().filter(|p| **p).count(), 1);
+
+        chip8.set_pixel(3, 2, false);
+        assert!(!chip8.get_display()[2 * SCREEN_WIDTH + 3]);
+
+        chip8.set_pixel(0, 0, true);
+        chip8.clear_display();
+        assert!(chip8.get_display().iter().all(|p| !p));
+    }
+
+    #[test]
+    fn display_dimensions_match_display_buffer() {
+        let chip8 = Chip8::new();
+        let (width, height) = chip8.display_dimensions();
+
+        assert_eq!((width, height), (SCREEN_WIDTH, SCREEN_HEIGHT));
+        assert_eq!(chip8.get_display().len(), width * height);
+    }
+
+    #[test]
+    fn packed_display_round_trips() {
+        let mut chip8 = Chip8::new();
+        chip8.v_reg[0] = 8;
+        chip8.v_reg[1] = 1;
+        chip8.i_reg = 0; // Sprite of the digit 0
+        chip8.execute(0xD015).unwrap();
+
+        let packed = chip8.display_packed();
+        assert_eq!(packed.len(), SCREEN_SIZE / 8);
+        // The digit's rows start at the second byte of the second row
+        assert_eq!(packed[SCREEN_WIDTH / 8 + 1], 0xF0);
+        assert_eq!(packed[2 * SCREEN_WIDTH / 8 + 1], 0x90);
+        assert_eq!(packed.iter().map(|byte| byte.count_ones()).sum::<u32>(), 14);
+
+        assert_eq!(unpack_display(&packed), chip8.get_display());
+    }
+
+    #[test]
+    fn render_to_rgba_scales_display_with_palette() {
+        let mut chip8 = Chip8::new();
+        chip8.screen[0] = true;
+        chip8.screen[SCREEN_SIZE - 1] = true;
+        let palette = Palette {
+            foreground: Rgb(0xFF, 0xCC, 0x00),
+            background: Rgb(0x10, 0x20, 0x30),
+        };
+
+        let pixels = chip8.render_to_rgba(3, &palette);
+        let width = SCREEN_WIDTH * 3;
+        assert_eq!(pixels.len(), width * SCREEN_HEIGHT * 3 * 4);
+
+        let pixel = |x: usize, y: usize| &pixels[(y * width + x) * 4..][..4];
+        assert_eq!(pixel(0, 0), [0xFF, 0xCC, 0x00, 0xFF]);
+        assert_eq!(pixel(2, 2), [0xFF, 0xCC, 0x00, 0xFF]);
+        assert_eq!(pixel(3, 0), [0x10, 0x20, 0x30, 0xFF]);
+        assert_eq!(pixel(0, 3), [0x10, 0x20, 0x30, 0xFF]);
+        assert_eq!(
+            pixel(width - 1, SCREEN_HEIGHT * 3 - 1),
+            [0xFF, 0xCC, 0x00, 0xFF]
+        );
+        assert_eq!(
+            pixel(width - 4, SCREEN_HEIGHT * 3 - 1),
+            [0x10, 0x20, 0x30, 0xFF]
+        );
+
+        // A scale of 0 renders at the display's own size
+        assert_eq!(chip8.render_to_rgba(0, &palette).len(), SCREEN_SIZE * 4);
+    }
+
+    #[test]
+    fn clear_display_only_blanks_the_screen() {
+        let mut chip8 = Chip8::new();
+        chip8.v_reg[1] = 0x12;
+        chip8.i_reg = 0; // Sprite of the digit 0
+        chip8.execute(0xD005).unwrap();
+        assert!(chip8.get_display().iter().any(|pixel| *pixel));
+
+        chip8.clear_display();
+        assert!(chip8.get_display().iter().all(|pixel| !pixel));
+        assert_eq!(chip8.v_reg[1], 0x12);
+        assert_eq!(chip8.pc, START_ADDR);
+    }
+
+    #[test]
+    fn font_sprite_uses_font_base_and_glyph_size() {
+        let mut chip8 = Chip8::new();
+        chip8.v_reg[3] = 0xA;
+        chip8.execute(0xF329).unwrap();
+        assert_eq!(chip8.i_reg, 0x032);
+
+        let mut chip8 = Chip8::builder().font_base(0x050).build();
+        assert_eq!(&chip8.ram[0x050..0x0A0], &FONTSET);
+        chip8.v_reg[3] = 0xA;
+        chip8.execute(0xF329).unwrap();
+        assert_eq!(chip8.i_reg, 0x082);
+
+        let mut chip8 = Chip8::builder()
+            .font_base(0x050)
+            .font_glyph_size(10)
+            .build();
+        chip8.v_reg[3] = 0xA;
+        chip8.execute(0xF329).unwrap();
+        assert_eq!(chip8.i_reg, 0x0B4);
+    }
+
+    #[test]
+    fn seeded_machines_get_same_random_numbers() {
+        let mut chip8_1 = Chip8::builder().seed(42).build();
+        let mut chip8_2 = Chip8::builder().seed(42).build();
+        for _ in 0..8 {
+            chip8_1.execute(0xC0FF).unwrap();
+            chip8_2.execute(0xC0FF).unwrap();
+            assert_eq!(chip8_1.v_reg[0], chip8_2.v_reg[0]);
+        }
+    }
+
+    #[test]
+    fn disabled_opcodes_are_skipped() {
+        let mut chip8 = Chip8::builder().disable_opcode("CXNN").build();
+        chip8.v_reg[0] = 0x12;
+        chip8.execute(0xC0FF).unwrap();
+        assert_eq!(chip8.v_reg[0], 0x12);
+
+        chip8.execute(0x6034).unwrap();
+        assert_eq!(chip8.v_reg[0], 0x34);
+    }
+
+    #[test]
+    fn disabled_opcodes_can_fail() {
+        let mut chip8 = Chip8::builder()
+            .disable_opcode("FX55")
+            .disabled_opcode_action(DisabledOpcodeAction::Fail)
+            .build();
+        chip8.load(&[0xF0, 0x55]).unwrap();
+        chip8.i_reg = 0x300;
+        chip8.v_reg[0] = 0xAB;
+
+        assert_eq!(chip8.tick(), Err(Chip8Error::DisabledOpcode(0xF055)));
+        assert_eq!(chip8.ram[0x300], 0);
+        assert!(chip8.is_halted());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown opcode pattern")]
+    fn disable_opcode_rejects_unknown_patterns() {
+        Chip8::builder().disable_opcode("CXYZ");
+    }
+
+    #[test]
+    fn font_writes_are_allowed_by_default() {
+        let mut chip8 = Chip8::new();
+        chip8.v_reg[0] = 0xAB;
+        chip8.i_reg = 0x10;
+
+        chip8.execute(0xF055).unwrap();
+        assert_eq!(chip8.ram[0x10], 0xAB);
+    }
+
+    #[test]
+    fn font_writes_fail_when_protected() {
+        let mut chip8 = Chip8::builder().protect_font(true).build();
+        chip8.v_reg[0] = 0xAB;
+        chip8.i_reg = 0x10;
+
+        assert_eq!(chip8.execute(0xF055), Err(Chip8Error::FontWrite(0x10)));
+        assert_eq!(chip8.ram[0x10], FONTSET[0x10]);
+
+        chip8.i_reg = FONTSET_SIZE as u16;
+        chip8.execute(0xF055).unwrap();
+        assert_eq!(chip8.ram[FONTSET_SIZE], 0xAB);
+    }
+
+    #[test]
+    fn wait_for_key_accepts_first_pressed_key() {
+        let mut chip8 = Chip8::new();
+        chip8.keypress(0x9, true);
+        chip8.keypress(0x2, true);
+
+        chip8.execute(0xF30A).unwrap();
+        assert_eq!(chip8.v_reg[3], 0x9);
+
+        // Pressing a held key again doesn't move it back in the order
+        chip8.keypress(0x9, true);
+        chip8.execute(0xF30A).unwrap();
+        assert_eq!(chip8.v_reg[3], 0x9);
+
+        chip8.keypress(0x9, false);
+        chip8.keypress(0x9, true);
+        chip8.execute(0xF30A).unwrap();
+        assert_eq!(chip8.v_reg[3], 0x2);
+    }
+
+    #[test]
+    fn toggle_key_latches_until_toggled_again() {
+        let mut chip8 = Chip8::new();
+
+        assert!(chip8.toggle_key(0x5));
+        assert!(chip8.keys[0x5]);
+        assert!(!chip8.toggle_key(0x5));
+        assert!(!chip8.keys[0x5]);
+    }
+
+    #[test]
+    fn wait_for_key_releases_toggled_key() {
+        let mut chip8 = Chip8::new();
+        chip8.toggle_key(0x5);
+
+        chip8.execute(0xF30A).unwrap();
+        assert_eq!(chip8.v_reg[3], 0x5);
+        assert!(!chip8.keys[0x5]);
+
+        // Held keys are left alone
+        chip8.keypress(0x6, true);
+        chip8.execute(0xF30A).unwrap();
+        assert_eq!(chip8.v_reg[3], 0x6);
+        assert!(chip8.keys[0x6]);
+    }
+
+    #[test]
+    fn opcode_histogram_counts_executed_patterns() {
+        let mut chip8 = Chip8::new();
+        chip8
+            .load(&[0x60, 0x01, 0x61, 0x02, 0x80, 0x14, 0x12, 0x00])
+            .unwrap();
+
+        chip8.tick().unwrap();
+        assert!(chip8.opcode_histogram().is_empty());
+
+        chip8.set_opcode_profiling(true);
+        for _ in 0..7 {
+            chip8.tick().unwrap();
+        }
+        assert_eq!(
+            chip8.opcode_histogram(),
+            vec![("6XNN", 3), ("1NNN", 2), ("8XY4", 2)]
+        );
+
+        chip8.set_opcode_profiling(false);
+        assert!(chip8.opcode_histogram().is_empty());
+    }
+
+    #[test]
+    fn load_computes_rom_hash() {
+        let mut chip8 = Chip8::new();
+        chip8.load(b"a").unwrap();
+
+        assert_eq!(chip8.rom_hash(), 0xAF63_DC4C_8601_EC8C);
+    }
+
+    #[test]
+    fn load_rejects_empty_rom() {
+        let mut chip8 = Chip8::new();
+        assert_eq!(chip8.load(&[]), Err(LoadError::Empty));
+    }
+
+    #[test]
+    fn load_rejects_rom_larger_than_ram() {
+        let mut chip8 = Chip8::new();
+        let rom = [0; RAM_SIZE];
+        assert_eq!(chip8.load(&rom), Err(LoadError::TooLarge(RAM_SIZE)));
+    }
+
+    #[test]
+    fn load_at_copies_data_to_address() {
+        let mut chip8 = Chip8::new();
+        assert_eq!(chip8.load_at(0x300, &[0xAB, 0xCD, 0xEF]), Ok(()));
+
+        assert_eq!(chip8.peek_ram(0x2FF), 0x00);
+        assert_eq!(chip8.peek_ram(0x300), 0xAB);
+        assert_eq!(chip8.peek_ram(0x301), 0xCD);
+        assert_eq!(chip8.peek_ram(0x302), 0xEF);
+        assert_eq!(chip8.peek_ram(0x303), 0x00);
+    }
+
+    #[test]
+    fn load_at_rejects_data_past_end_of_ram() {
+        let mut chip8 = Chip8::new();
+        let result = chip8.load_at(RAM_SIZE - 1, &[0xAB, 0xCD]);
+        assert_eq!(
+            result,
+            Err(LoadError::OutOfBounds {
+                addr: 0xFFF,
+                len: 2
+            })
+        );
+        assert_eq!(chip8.peek_ram(RAM_SIZE - 1), 0x00);
+    }
+
+    #[test]
+    fn snapshot_matches_machine_state() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x6A, 0x2F, 0xA3, 0x00]).unwrap();
+        chip8.tick().unwrap();
+
+        let snapshot = chip8.snapshot();
+        assert_eq!(snapshot.pc, chip8.pc);
+        assert_eq!(snapshot.pc, 0x202);
+        assert_eq!(snapshot.v_reg, chip8.v_reg);
+        assert_eq!(snapshot.v_reg[0xA], 0x2F);
+        assert_eq!(snapshot.i_reg, chip8.i_reg);
+        assert_eq!(snapshot.ram, chip8.ram);
+        assert_eq!(snapshot.screen, chip8.screen);
+        assert_eq!(snapshot.stack, chip8.stack);
+        assert_eq!(snapshot.keys, chip8.keys);
+
+        chip8.tick().unwrap();
+        assert_eq!(chip8.i_reg, 0x300);
+        assert_eq!(snapshot.i_reg, 0);
+    }
+
+    #[test]
+    fn stack_overflows_past_default_depth() {
+        let mut chip8 = Chip8::new();
+        for _ in 0..DEFAULT_STACK_DEPTH {
+            chip8.execute(0x2300).unwrap();
+        }
+
+        let result = chip8.execute(0x2300);
+        assert_eq!(result, Err(Chip8Error::StackOverflow(DEFAULT_STACK_DEPTH)));
+        assert_eq!(chip8.sp as usize, DEFAULT_STACK_DEPTH);
+    }
+
+    #[test]
+    fn return_with_empty_stack_fails() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x00, 0xEE]).unwrap();
+
+        assert_eq!(chip8.tick(), Err(Chip8Error::EmptyStackReturn(0x200)));
+        assert!(chip8.is_halted());
+        assert_eq!(chip8.sp(), 0);
+    }
+
+    #[test]
+    fn builder_configures_stack_depth() {
+        let mut chip8 = Chip8::builder().stack_depth(32).build();
+        for _ in 0..32 {
+            chip8.execute(0x2300).unwrap();
+        }
+        assert_eq!(chip8.sp, 32);
+        assert_eq!(chip8.stack.len(), 32);
+
+        assert_eq!(chip8.execute(0x2300), Err(Chip8Error::StackOverflow(32)));
+    }
+
+    #[test]
+    fn stack_returns_active_calls() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x22, 0x04, 0x00, 0x00, 0x22, 0x08]).unwrap();
+        assert!(chip8.stack().is_empty());
+
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        assert_eq!(chip8.sp(), 2);
+        assert_eq!(chip8.stack(), &[0x202, 0x206]);
+
+        chip8.execute(0x00EE).unwrap();
+        assert_eq!(chip8.stack(), &[0x202]);
+    }
+
+    #[test]
+    fn fetch_fails_past_end_of_ram() {
+        let mut chip8 = Chip8::new();
+        chip8.pc = (RAM_SIZE - 2) as u16;
+        assert_eq!(chip8.fetch(), Ok(0x0000));
+        assert_eq!(chip8.pc as usize, RAM_SIZE);
+
+        assert_eq!(chip8.fetch(), Err(Chip8Error::PcOutOfBounds(0x1000)));
+
+        chip8.pc = (RAM_SIZE - 1) as u16;
+        assert_eq!(chip8.fetch(), Err(Chip8Error::PcOutOfBounds(0xFFF)));
+    }
+
+    #[test]
+    fn fetch_wraps_around_ram_with_quirk() {
+        let quirks = Quirks {
+            wrap_pc: true,
+            ..Default::default()
+        };
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.ram[RAM_SIZE - 1] = 0xAB;
+        chip8.ram[0] = 0xCD;
+
+        chip8.pc = (RAM_SIZE - 2) as u16;
+        assert_eq!(chip8.fetch(), Ok(0x00AB));
+        assert_eq!(chip8.pc, 0);
+
+        chip8.pc = (RAM_SIZE - 1) as u16;
+        assert_eq!(chip8.fetch(), Ok(0xABCD));
+        assert_eq!(chip8.pc, 1);
+    }
+
+    #[test]
+    fn wait_for_key_at_top_of_ram_wraps_with_quirk() {
+        let quirks = Quirks {
+            wrap_pc: true,
+            ..Default::default()
+        };
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.ram[RAM_SIZE - 2] = 0xF0;
+        chip8.ram[RAM_SIZE - 1] = 0x0A;
+        chip8.pc = (RAM_SIZE - 2) as u16;
+
+        chip8.tick().unwrap();
+        assert_eq!(chip8.pc as usize, RAM_SIZE - 2);
+        chip8.tick().unwrap();
+        assert_eq!(chip8.pc as usize, RAM_SIZE - 2);
+    }
+
+    #[test]
+    fn press_key_char_maps_hex_characters_to_keys() {
+        let mut chip8 = Chip8::new();
+
+        assert!(chip8.press_key_char('A'));
+        assert!(chip8.keys[0xA]);
+        assert!(chip8.press_key_char('f'));
+        assert!(chip8.keys[0xF]);
+
+        assert!(chip8.release_key_char('a'));
+        assert!(!chip8.keys[0xA]);
+
+        assert!(!chip8.press_key_char('G'));
+        assert_eq!(chip8.keys.iter().filter(|key| **key).count(), 1);
+    }
+
+    #[test]
+    fn take_beep_reports_sound_timer_writes_once() {
+        let mut chip8 = Chip8::new();
+        assert_eq!(chip8.take_beep(), None);
+
+        chip8.v_reg[0x2] = 1;
+        chip8.execute(0xF218).unwrap();
+        assert_eq!(chip8.take_beep(), Some(1));
+        assert_eq!(chip8.take_beep(), None);
+    }
+
+    #[test]
+    fn timers_run_out_together() {
+        let mut chip8 = Chip8::new();
+        chip8.v_reg[0x2] = 2;
+        chip8.execute(0xF215).unwrap();
+        chip8.execute(0xF218).unwrap();
+
+        chip8.tick_timers();
+        assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (1, 1));
+
+        chip8.tick_timers();
+        assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (0, 0));
+
+        chip8.tick_timers();
+        assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (0, 0));
+
+        // The beep was only reported when the sound timer was set
+        assert_eq!(chip8.take_beep(), Some(2));
+        assert_eq!(chip8.take_beep(), None);
+    }
+
+    #[test]
+    fn beep_fires_once_as_sound_timer_runs_out() {
+        let mut chip8 = Chip8::builder().beep_trigger(BeepTrigger::RunOut).build();
+        chip8.v_reg[0x2] = 2;
+        chip8.execute(0xF215).unwrap();
+        chip8.execute(0xF218).unwrap();
+        assert_eq!(chip8.take_beep(), None);
+
+        chip8.tick_timers();
+        assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (1, 1));
+        assert_eq!(chip8.take_beep(), None);
+
+        chip8.tick_timers();
+        assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (0, 0));
+        assert_eq!(chip8.take_beep(), Some(1));
+
+        chip8.tick_timers();
+        assert_eq!(chip8.take_beep(), None);
+    }
+
+    #[test]
+    fn decode_at_decodes_without_executing() {
+        let mut chip8 = Chip8::new();
+        chip8.load_at(0x300, &[0xD1, 0x25]).unwrap();
+
+        let decoded = chip8.decode_at(0x300);
+        assert_eq!(decoded.op, 0xD125);
+        assert_eq!(decoded.fields.x, 0x1);
+        assert_eq!(decoded.fields.y, 0x2);
+        assert_eq!(decoded.fields.n, 0x5);
+        assert_eq!(decoded.mnemonic.as_deref(), Some("DRW V1, V2, 5"));
+        assert_eq!(chip8.pc, START_ADDR);
+
+        assert_eq!(chip8.decode_at(0x2FF).op, 0x00D1);
+    }
+
+    #[test]
+    fn exit_opcode_halts_until_resumed() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x00, 0xFD, 0x61, 0x05]).unwrap();
+
+        chip8.tick().unwrap();
+        assert!(chip8.is_halted());
+        chip8.tick().unwrap();
+        assert_eq!(chip8.pc, 0x202);
+        assert_eq!(chip8.v_reg[0x1], 0);
+
+        chip8.resume();
+        assert!(!chip8.is_halted());
+        chip8.tick().unwrap();
+        assert_eq!(chip8.pc, 0x204);
+        assert_eq!(chip8.v_reg[0x1], 5);
+    }
+
+    #[test]
+    fn errors_halt_the_machine() {
+        let mut chip8 = Chip8::builder().stack_depth(1).build();
+        chip8.load(&[0x22, 0x00]).unwrap();
+
+        chip8.tick().unwrap();
+        assert_eq!(chip8.tick(), Err(Chip8Error::StackOverflow(1)));
+        assert!(chip8.is_halted());
+    }
+
+    #[test]
+    fn tick_timers_dt_decrements_once_per_period() {
+        let mut chip8 = Chip8::new();
+        chip8.dt = 10;
+        chip8.st = 10;
+
+        chip8.tick_timers_dt(Duration::from_millis(50));
+        assert_eq!(chip8.dt, 7);
+        assert_eq!(chip8.st, 7);
+
+        chip8.tick_timers_dt(Duration::from_millis(10));
+        assert_eq!(chip8.dt, 7);
+        chip8.tick_timers_dt(Duration::from_millis(10));
+        assert_eq!(chip8.dt, 6);
+
+        chip8.tick_timers();
+        assert_eq!(chip8.dt, 5);
+    }
+
+    #[test]
+    fn lores_scroll_moves_half_pixels_by_default() {
+        let mut chip8 = Chip8::new();
+        chip8.screen[10] = true;
+
+        chip8.execute(0x00FB).unwrap();
+        assert!(chip8.screen[12]);
+        assert_eq!(chip8.screen.iter().filter(|pixel| **pixel).count(), 1);
+
+        chip8.execute(0x00FC).unwrap();
+        assert!(chip8.screen[10]);
+        assert_eq!(chip8.screen.iter().filter(|pixel| **pixel).count(), 1);
+    }
+
+    #[test]
+    fn lores_scroll_moves_full_pixels_with_quirk() {
+        let quirks = Quirks {
+            lores_scroll_full: true,
+            ..Default::default()
+        };
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.screen[10] = true;
+
+        chip8.execute(0x00FB).unwrap();
+        assert!(chip8.screen[14]);
+        assert_eq!(chip8.screen.iter().filter(|pixel| **pixel).count(), 1);
+
+        chip8.execute(0x00FC).unwrap();
+        chip8.execute(0x00FC).unwrap();
+        chip8.execute(0x00FC).unwrap();
+        assert!(chip8.screen[2]);
+        assert_eq!(chip8.screen.iter().filter(|pixel| **pixel).count(), 1);
+
+        chip8.execute(0x00FC).unwrap();
+        assert!(chip8.screen.iter().all(|pixel| !*pixel));
+    }
+
+    #[test]
+    fn fx1e_leaves_vf_untouched_by_default() {
+        let mut chip8 = Chip8::new();
+        chip8.i_reg = 0x0FFE;
+        chip8.v_reg[0x3] = 0x05;
+        chip8.v_reg[0xF] = 0x42;
+
+        chip8.execute(0xF31E).unwrap();
+        assert_eq!(chip8.i_reg, 0x1003);
+        assert_eq!(chip8.v_reg[0xF], 0x42);
+    }
+
+    #[test]
+    fn fx1e_sets_vf_on_overflow_with_quirk() {
+        let quirks = Quirks {
+            fx1e_sets_vf: true,
+            ..Default::default()
+        };
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.i_reg = 0x0FFE;
+        chip8.v_reg[0x3] = 0x01;
+
+        chip8.execute(0xF31E).unwrap();
+        assert_eq!(chip8.i_reg, 0x0FFF);
+        assert_eq!(chip8.v_reg[0xF], 0);
+
+        chip8.execute(0xF31E).unwrap();
+        assert_eq!(chip8.i_reg, 0x1000);
+        assert_eq!(chip8.v_reg[0xF], 1);
+    }
+
+    #[test]
+    fn shift_uses_vy_with_quirk() {
+        let mut chip8 = Chip8::new();
+        chip8.v_reg[0x1] = 0b0000_0010;
+        chip8.v_reg[0x2] = 0b1000_0001;
+        chip8.execute(0x8126).unwrap();
+        assert_eq!(chip8.v_reg[0x1], 0b0000_0001);
+        assert_eq!(chip8.v_reg[0xF], 0);
+
+        let quirks = Quirks {
+            shift_vy: true,
+            ..Default::default()
+        };
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.v_reg[0x1] = 0b0000_0010;
+        chip8.v_reg[0x2] = 0b1000_0001;
+        chip8.execute(0x8126).unwrap();
+        assert_eq!(chip8.v_reg[0x1], 0b0100_0000);
+        assert_eq!(chip8.v_reg[0xF], 1);
+    }
+
+    #[test]
+    fn flag_wins_over_result_when_x_is_vf() {
+        // (opcode, VF, V1, expected VF): the flag, never the result
+        let cases = [
+            (0x8F14, 0x10, 0x02, 0), // Sum 0x12, no carry
+            (0x8F14, 0xF0, 0x20, 1), // Sum 0x10, carry
+            (0x8F15, 0x10, 0x02, 1), // Difference 0x0E, no borrow
+            (0x8F15, 0x02, 0x10, 0), // Difference 0xF2, borrow
+            (0x8F17, 0x02, 0x10, 1), // Difference 0x0E, no borrow
+            (0x8F16, 0x12, 0x00, 0), // Shifted 0x09, bit 0 clear
+            (0x8F1E, 0x81, 0x00, 1), // Shifted 0x02, bit 7 set
+        ];
+
+        for (op, vf, v1, expected) in cases {
+            let mut chip8 = Chip8::new();
+            chip8.v_reg[0xF] = vf;
+            chip8.v_reg[0x1] = v1;
+            chip8.execute(op).unwrap();
+            assert_eq!(chip8.v_reg[0xF], expected, "{:04X}", op);
+        }
+    }
+
+    #[test]
+    fn shifted_out_bit_wins_in_vf_with_shift_vy_quirk() {
+        let quirks = Quirks {
+            shift_vy: true,
+            ..Default::default()
+        };
+        // (opcode, V1, expected VF): the bit shifted out of VY, not the result
+        let cases = [
+            (0x8F16, 0b0000_0011, 1), // Shifted 0x01, bit 0 set
+            (0x8F16, 0b1111_1110, 0), // Shifted 0x7F, bit 0 clear
+            (0x8F1E, 0b1100_0000, 1), // Shifted 0x80, bit 7 set
+            (0x8F1E, 0b0111_1111, 0), // Shifted 0xFE, bit 7 clear
+        ];
+
+        for (op, v1, expected) in cases {
+            let mut chip8 = Chip8::with_quirks(quirks);
+            chip8.v_reg[0xF] = 0xAA;
+            chip8.v_reg[0x1] = v1;
+            chip8.execute(op).unwrap();
+            assert_eq!(chip8.v_reg[0xF], expected, "{:04X} with V1={:#04X}", op, v1);
+            assert_eq!(chip8.v_reg[0x1], v1);
+        }
+    }
+
+    #[test]
+    fn vf_result_last_quirk_keeps_result_in_vf() {
+        let mut chip8 = Chip8::new();
+        chip8.v_reg[0xF] = 0xFF;
+        chip8.v_reg[0x1] = 0x02;
+        chip8.execute(0x8F14).unwrap();
+        assert_eq!(chip8.v_reg[0xF], 1);
+
+        let quirks = Quirks {
+            vf_result_last: true,
+            ..Default::default()
+        };
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.v_reg[0xF] = 0xFF;
+        chip8.v_reg[0x1] = 0x02;
+        chip8.execute(0x8F14).unwrap();
+        assert_eq!(chip8.v_reg[0xF], 0x01);
+
+        chip8.v_reg[0xF] = 0x10;
+        chip8.execute(0x8F14).unwrap();
+        assert_eq!(chip8.v_reg[0xF], 0x12);
+    }
+
+    #[test]
+    fn vf_reset_quirk_clears_vf_on_logic_opcodes() {
+        let quirks = Quirks {
+            vf_reset: true,
+            ..Default::default()
+        };
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.v_reg[0xF] = 1;
+        chip8.execute(0x8121).unwrap();
+        assert_eq!(chip8.v_reg[0xF], 0);
+    }
+
+    #[test]
+    fn jump_vx_quirk_uses_register_from_opcode() {
+        let quirks = Quirks {
+            jump_vx: true,
+            ..Default::default()
+        };
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.v_reg[0x0] = 0x10;
+        chip8.v_reg[0x3] = 0x04;
+        chip8.execute(0xB300).unwrap();
+        assert_eq!(chip8.pc, 0x304);
+    }
+
+    #[test]
+    fn load_store_increments_i_with_quirk() {
+        let mut chip8 = Chip8::new();
+        chip8.i_reg = 0x300;
+        chip8.execute(0xF255).unwrap();
+        assert_eq!(chip8.i_reg, 0x300);
+
+        let quirks = Quirks {
+            load_store_increments_i: true,
+            ..Default::default()
+        };
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.i_reg = 0x300;
+        chip8.execute(0xF255).unwrap();
+        assert_eq!(chip8.i_reg, 0x303);
+        chip8.execute(0xF165).unwrap();
+        assert_eq!(chip8.i_reg, 0x305);
+    }
+
+    #[test]
+    fn display_wait_quirk_pauses_until_next_frame() {
+        let quirks = Quirks {
+            display_wait: true,
+            ..Default::default()
+        };
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.load(&[0xD0, 0x01, 0x60, 0x05]).unwrap();
+
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        assert_eq!(chip8.pc, 0x202);
+
+        chip8.tick_timers();
+        chip8.tick().unwrap();
+        assert_eq!(chip8.pc, 0x204);
+        assert_eq!(chip8.v_reg[0x0], 0x05);
+    }
+
+    #[test]
+    fn draw_clips_sprites_with_quirk() {
+        let quirks = Quirks {
+            clip_sprites: true,
+            ..Default::default()
+        };
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.i_reg = 0x300;
+        chip8.ram[0x300] = 0xFF;
+        chip8.ram[0x301] = 0xFF;
+
+        chip8.draw_sprite((SCREEN_WIDTH - 4) as u16, (SCREEN_HEIGHT - 1) as u16, 2, 8);
+
+        let lit = chip8.get_display().iter().filter(|pixel| **pixel).count();
+        assert_eq!(lit, 4);
+
+        // The starting position still wraps around
+        chip8.draw_sprite(SCREEN_WIDTH as u16, 0, 1, 8);
+        assert!(chip8.screen[0]);
+    }
+
+    #[test]
+    fn clipped_pixels_collide_only_with_quirk() {
+        let collision = |clip_counts_collision: bool| {
+            let quirks = Quirks {
+                clip_sprites: true,
+                clip_counts_collision,
+                ..Default::default()
+            };
+            let mut chip8 = Chip8::with_quirks(quirks);
+            chip8.i_reg = 0x300;
+            chip8.ram[0x300] = 0xFF;
+            // Lit pixel where the sprite's clipped part would wrap onto
+            chip8.screen[1] = true;
+
+            chip8.draw_sprite((SCREEN_WIDTH - 4) as u16, 0, 1, 8);
+            // Clipped pixels are never drawn
+            assert!(chip8.screen[1]);
+            assert!(chip8.screen[SCREEN_WIDTH - 1]);
+            chip8.v_reg[0xF]
+        };
+
+        assert_eq!(collision(false), 0);
+        assert_eq!(collision(true), 1);
+    }
+
+    #[test]
+    fn draw_wraps_only_sprite_origin_with_clip_quirk() {
+        let lit_columns = |quirks: Quirks| {
+            let mut chip8 = Chip8::with_quirks(quirks);
+            chip8.i_reg = 0x300;
+            chip8.ram[0x300] = 0xFF;
+
+            chip8.draw_sprite((2 * SCREEN_WIDTH - 4) as u16, 0, 1, 8);
+            (0..SCREEN_WIDTH)
+                .filter(|x| chip8.screen[*x])
+                .collect::<Vec<_>>()
+        };
+
+        // The origin wraps to X = 60, and the pixels past the edge are
+        // clipped: without wrapping the origin, nothing would be drawn
+        let clip = Quirks {
+            clip_sprites: true,
+            ..Default::default()
+        };
+        assert_eq!(lit_columns(clip), vec![60, 61, 62, 63]);
+
+        // Without the quirk, the pixels past the edge wrap around too
+        assert_eq!(
+            lit_columns(Quirks::default()),
+            vec![0, 1, 2, 3, 60, 61, 62, 63]
+        );
+    }
+
+    #[test]
+    fn anti_flicker_keeps_erased_sprite_lit_for_a_frame() {
+        let quirks = Quirks {
+            anti_flicker: true,
+            ..Default::default()
+        };
+        let mut chip8 = Chip8::with_quirks(quirks);
+        // Draws the "0" font sprite at every frame, erasing it in between
+        chip8.load(&[0xD0, 0x05, 0x12, 0x00]).unwrap();
+
+        // Frame 1: draw
+        chip8.tick().unwrap();
+        chip8.tick_timers();
+        assert!(chip8.get_display()[0]);
+
+        // Frame 2: erase, which collides but stays on the display
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        assert_eq!(chip8.v_reg[0xF], 1);
+        assert!(!chip8.screen[0]);
+        chip8.tick_timers();
+        assert!(chip8.get_display()[0]);
+
+        // Frame 3: draw again
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        assert_eq!(chip8.v_reg[0xF], 0);
+        chip8.tick_timers();
+        assert!(chip8.get_display()[0]);
+
+        // Without the quirk, the display shows the erased frame
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0xD0, 0x05, 0x12, 0x00]).unwrap();
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        assert!(!chip8.get_display()[0]);
+    }
+
+    #[test]
+    fn draw_clamps_out_of_range_row_count() {
+        let mut chip8 = Chip8::new();
+        chip8.i_reg = 0x300;
+        chip8.ram[0x300..0x300 + 40].copy_from_slice(&[0xFF; 40]);
+
+        chip8.draw_sprite(0, 0, 40, 8);
+
+        let display = chip8.get_display();
+        for y in 0..SCREEN_HEIGHT {
+            let expected = y < MAX_SPRITE_ROWS as usize;
+            assert_eq!(display[y * SCREEN_WIDTH], expected);
+        }
+        assert_eq!(chip8.v_reg[0xF], 0);
+    }
+
+    #[test]
+    fn draw_sprite_reads_each_row_for_several_sizes() {
+        for (height, width) in [(1, 8), (5, 8), (15, 8), (16, 16), (3, 12)] {
+            let mut chip8 = Chip8::new();
+            chip8.i_reg = 0x300;
+            chip8.ram[0x300..0x300 + 32].copy_from_slice(&[0xFF; 32]);
+
+            chip8.draw_sprite(0, 0, height, width);
+
+            let display = chip8.get_display();
+            for y in 0..SCREEN_HEIGHT {
+                for x in 0..SCREEN_WIDTH {
+                    let expected = y < height as usize && x < width as usize;
+                    assert_eq!(display[x + SCREEN_WIDTH * y], expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn draw_sprite_wraps_around_end_of_address_space() {
+        let mut chip8 = Chip8::new();
+        chip8.i_reg = 0xFFFF;
+        chip8.ram[RAM_SIZE - 1] = 0x80;
+        chip8.ram[0] = 0x40;
+
+        chip8.draw_sprite(0, 0, 2, 8);
+
+        assert!(chip8.screen[0]);
+        assert!(chip8.screen[SCREEN_WIDTH + 1]);
+    }
+
+    #[test]
+    fn draw_sprite_uses_two_bytes_per_row_when_wide() {
+        let mut chip8 = Chip8::new();
+        chip8.i_reg = 0x300;
+        chip8.ram[0x300..0x304].copy_from_slice(&[0x80, 0x01, 0x00, 0x80]);
+
+        chip8.draw_sprite(0, 0, 2, 16);
+
+        let lit: Vec<usize> = (0..SCREEN_WIDTH * SCREEN_HEIGHT)
+            .filter(|idx| chip8.screen[*idx])
+            .collect();
+        assert_eq!(lit, vec![0, 15, SCREEN_WIDTH + 8]);
+    }
+
+    #[test]
+    fn dxy0_draws_large_sprite() {
+        let mut chip8 = Chip8::new();
+        chip8.i_reg = 0x300;
+        chip8.ram[0x300..0x300 + 32].copy_from_slice(&[0xFF; 32]);
+
+        // Large sprites are only drawn in the high resolution mode
+        chip8.execute(0xD000).unwrap();
+        assert!(chip8.get_display().iter().all(|pixel| !pixel));
+
+        chip8.execute(0x00FF).unwrap();
+        chip8.execute(0xD000).unwrap();
+
+        let lit = chip8.get_display().iter().filter(|pixel| **pixel).count();
+        assert_eq!(lit, 16 * 16);
+        assert_eq!(chip8.v_reg[0xF], 0);
+    }
+
+    #[test]
+    fn draw_in_set_mode_never_erases_pixels() {
+        let mut chip8 = Chip8::new();
+        chip8.set_draw_mode(DrawMode::Set);
+        chip8.i_reg = 0x300;
+        chip8.ram[0x300] = 0b1100_0000;
+
+        chip8.draw_sprite(0, 0, 1, 8);
+        chip8.draw_sprite(0, 0, 1, 8);
+
+        assert!(chip8.screen[0]);
+        assert!(chip8.screen[1]);
+        assert_eq!(chip8.v_reg[0xF], 1);
+    }
+
+    #[test]
+    fn pixels_toggled_counts_draws_and_clears() {
+        let mut chip8 = Chip8::new();
+        chip8.i_reg = 0x300;
+        chip8.ram[0x300] = 0b1110_0000;
+
+        chip8.draw_sprite(0, 0, 1, 8);
+        assert_eq!(chip8.pixels_toggled(), 3);
+
+        chip8.execute(0x00E0).unwrap();
+        assert_eq!(chip8.pixels_toggled(), 6);
+
+        chip8.execute(0x00E0).unwrap();
+        assert_eq!(chip8.pixels_toggled(), 6);
+    }
+
+    #[test]
+    fn pixels_toggled_counts_scrolls() {
+        let mut chip8 = Chip8::new();
+        chip8.execute(0x00FB).unwrap();
+        assert_eq!(chip8.pixels_toggled(), 0);
+
+        // Two lit pixels, 2 apart: scrolling by 2 moves one onto the other
+        chip8.screen[0] = true;
+        chip8.screen[2] = true;
+        chip8.execute(0x00FB).unwrap();
+        assert_eq!(chip8.pixels_toggled(), 2);
+
+        chip8.execute(0x00FC).unwrap();
+        assert_eq!(chip8.pixels_toggled(), 4);
+
+        // Pixels scrolled off the edge of the screen are toggled off
+        chip8.execute(0x00FC).unwrap();
+        chip8.execute(0x00FC).unwrap();
+        assert_eq!(chip8.pixels_toggled(), 6);
+        assert!(chip8.screen.iter().all(|pixel| !pixel));
+    }
+
+    #[test]
+    fn draw_records_last_collisions() {
+        let mut chip8 = Chip8::new();
+        chip8.i_reg = 0x300;
+        chip8.ram[0x300] = 0b1100_0000;
+        chip8.ram[0x301] = 0b0110_0000;
+
+        chip8.draw_sprite(0, 0, 1, 8);
+        assert!(chip8.last_collisions().is_empty());
+
+        chip8.i_reg = 0x301;
+        chip8.draw_sprite(0, 0, 1, 8);
+        assert_eq!(chip8.last_collisions(), &[1]);
+
+        chip8.draw_sprite(0, 5, 1, 8);
+        assert!(chip8.last_collisions().is_empty());
+
+        chip8.clear_last_collisions();
+        assert!(chip8.last_collisions().is_empty());
+    }
+
+    #[test]
+    fn draw_accumulates_collision_counts() {
+        let mut chip8 = Chip8::new();
+        chip8.i_reg = 0x300;
+        chip8.ram[0x300] = 0b1000_0000;
+
+        for _ in 0..4 {
+            chip8.draw_sprite(0, 0, 1, 8);
+        }
+        assert_eq!(chip8.collision_counts()[0], 2);
+        assert_eq!(chip8.collision_counts()[1..].iter().sum::<u32>(), 0);
+
+        chip8.load(&[0x00, 0xE0]).unwrap();
+        assert_eq!(chip8.collision_counts()[0], 0);
+    }
+
+    #[test]
+    fn draw_respects_sprite_pixel_limit() {
+        let mut chip8 = Chip8::new();
+        chip8.set_sprite_pixel_limit(4);
+        chip8.i_reg = 0x300;
+        chip8.ram[0x300] = 0xFF;
+
+        chip8.draw_sprite(0, 0, 1, 8);
+
+        let lit = chip8.get_display().iter().filter(|pixel| **pixel).count();
+        assert_eq!(lit, 4);
+    }
+
+    #[test]
+    fn poke_ram_writes_bytes() {
+        let mut chip8 = Chip8::builder().protect_font(true).build();
+        chip8.load(&[0x00, 0xE0]).unwrap();
+
+        chip8.poke_ram(0x300, 0xAB).unwrap();
+        chip8.poke_ram(0x301, 0xCD).unwrap();
+        chip8.poke_ram(0x200, 0x12).unwrap();
+        assert_eq!(&chip8.ram[0x300..0x302], &[0xAB, 0xCD]);
+        assert_eq!(chip8.ram[0x200], 0x12);
+
+        assert_eq!(
+            chip8.poke_ram(RAM_SIZE, 0xFF),
+            Err(LoadError::OutOfBounds {
+                addr: RAM_SIZE,
+                len: 1
+            }
+            .into())
+        );
+        assert_eq!(
+            chip8.poke_ram(0x010, 0xFF),
+            Err(Chip8Error::FontWrite(0x010).into())
+        );
+    }
+
+    #[test]
+    fn hot_swap_keeps_running_context() {
+        let mut chip8 = Chip8::new();
+        chip8
+            .load(&[0x60, 0x2A, 0xA3, 0x00, 0x00, 0xE0, 0x22, 0x00])
+            .unwrap();
+        for _ in 0..4 {
+            chip8.tick().unwrap();
+        }
+        chip8.keypress(5, true);
+        assert_eq!(chip8.sp(), 1);
+
+        chip8.hot_swap(&[0x61, 0x07]).unwrap();
+        assert_eq!(chip8.pc(), START_ADDR);
+        assert_eq!(chip8.sp(), 0);
+        assert_eq!(&chip8.ram[0x200..0x204], &[0x61, 0x07, 0x00, 0x00]);
+        assert_eq!(chip8.register(0), 0x2A);
+        assert_eq!(chip8.i_reg, 0x300);
+        assert!(chip8.keys[5]);
+
+        assert_eq!(chip8.hot_swap(&[]), Err(LoadError::Empty));
+        assert_eq!(chip8.ram[0x200], 0x61);
+    }
+
+    #[test]
+    fn quirks_set_by_name() {
+        let mut quirks = Quirks::default();
+        for (name, _) in Quirks::default().flags() {
+            assert!(quirks.set(name, true));
+        }
+        assert!(quirks.flags().iter().all(|(_, value)| *value));
+
+        assert!(quirks.set("clip_sprites", false));
+        assert!(!quirks.clip_sprites);
+        assert!(!quirks.set("unknown", true));
+    }
+
+    #[test]
+    fn recent_instructions_keeps_last_executed_in_order() {
+        let mut chip8 = Chip8::builder().trace_depth(3).build();
+        // Sets V0 to V3, then jumps to itself forever
+        chip8
+            .load(&[0x60, 0x00, 0x61, 0x01, 0x62, 0x02, 0x63, 0x03, 0x12, 0x08])
+            .unwrap();
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        assert_eq!(
+            chip8.recent_instructions().collect::<Vec<_>>(),
+            vec![(0x200, 0x6000), (0x202, 0x6101)]
+        );
+
+        for _ in 0..4 {
+            chip8.tick().unwrap();
+        }
+        assert_eq!(
+            chip8.recent_instructions().collect::<Vec<_>>(),
+            vec![(0x206, 0x6303), (0x208, 0x1208), (0x208, 0x1208)]
+        );
+
+        chip8.load(&[0x00, 0xEE]).unwrap();
+        assert_eq!(chip8.recent_instructions().count(), 0);
+
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x00, 0xEE]).unwrap();
+        assert!(chip8.tick().is_err());
+        assert_eq!(chip8.recent_instructions().last(), Some((0x200, 0x00EE)));
+    }
+
+    #[test]
+    fn zero_trace_depth_disables_trace() {
+        let mut chip8 = Chip8::builder().trace_depth(0).build();
+        chip8.load(&[0x60, 0x01, 0x12, 0x02]).unwrap();
+        for _ in 0..10 {
+            chip8.tick().unwrap();
+        }
+        assert_eq!(chip8.recent_instructions().count(), 0);
+    }
+
+    #[test]
+    fn advance_frame_reports_draws_and_beeps() {
+        let mut chip8 = Chip8::new();
+        // Draws the "0" glyph, sets the sound timer to 10, then loops forever
+        chip8
+            .load(&[0xD0, 0x05, 0x60, 0x0A, 0xF0, 0x18, 0x12, 0x06])
+            .unwrap();
+
+        let report = chip8.advance_frame(10);
+        assert_eq!(
+            report,
+            FrameReport {
+                cycles: 10,
+                drew: true,
+                beep: Some(10),
+                error: None,
+            }
+        );
+        assert_eq!(chip8.sound_timer(), 9);
+
+        let report = chip8.advance_frame(10);
+        assert_eq!(report.cycles, 10);
+        assert!(!report.drew);
+        assert_eq!(report.beep, None);
+    }
+
+    #[test]
+    fn advance_frame_stops_on_errors() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x60, 0x01, 0x00, 0xEE]).unwrap();
+
+        let report = chip8.advance_frame(10);
+        assert_eq!(report.cycles, 1);
+        assert_eq!(report.error, Some(Chip8Error::EmptyStackReturn(0x202)));
+        assert_eq!(chip8.advance_frame(10).cycles, 0);
+    }
+
+    #[test]
+    fn hires_mode_switches_display_size() {
+        let mut chip8 = Chip8::new();
+        // Switches to hires, draws a pixel past the lores width, then
+        // switches back to lores
+        chip8
+            .load(&[
+                0x00, 0xFF, 0x60, 0x64, 0x61, 0x28, 0xA3, 0x00, 0xD0, 0x11, 0x00, 0xFE,
+            ])
+            .unwrap();
+        chip8.ram[0x300] = 0x80;
+
+        chip8.tick().unwrap();
+        assert!(chip8.is_hires());
+        assert_eq!(chip8.display_dimensions(), (HIRES_WIDTH, HIRES_HEIGHT));
+        assert_eq!(chip8.get_display().len(), HIRES_WIDTH * HIRES_HEIGHT);
+
+        for _ in 0..4 {
+            chip8.tick().unwrap();
+        }
+        assert!(chip8.get_display()[100 + 40 * HIRES_WIDTH]);
+
+        chip8.tick().unwrap();
+        assert!(!chip8.is_hires());
+        assert_eq!(chip8.display_dimensions(), (SCREEN_WIDTH, SCREEN_HEIGHT));
+        assert_eq!(chip8.get_display().len(), SCREEN_SIZE);
+    }
+
+    #[test]
+    fn resolution_switch_clears_display_with_quirk() {
+        // Draws the top-left pixel in lores, switches to hires, then back
+        let program = [0xA3, 0x00, 0xD0, 0x01, 0x00, 0xFE, 0x00, 0xFF, 0x00, 0xFE];
+        let lit = |chip8: &Chip8| chip8.get_display().iter().filter(|p| **p).count();
+
+        let mut chip8 = Chip8::new();
+        chip8.load(&program).unwrap();
+        chip8.ram[0x300] = 0x80;
+        for _ in 0..3 {
+            chip8.tick().unwrap();
+        }
+        assert_eq!(lit(&chip8), 1, "staying in lores doesn't clear");
+        chip8.tick().unwrap();
+        assert!(chip8.is_hires());
+        assert_eq!(lit(&chip8), 0);
+        chip8.screen[0] = true;
+        chip8.tick().unwrap();
+        assert!(!chip8.is_hires());
+        assert_eq!(lit(&chip8), 0);
+
+        let quirks = Quirks {
+            res_switch_clears: false,
+            ..Default::default()
+        };
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.load(&program).unwrap();
+        chip8.ram[0x300] = 0x80;
+        for _ in 0..4 {
+            chip8.tick().unwrap();
+        }
+        // The lores pixel is scaled up to a 2x2 block, and back down
+        assert!(chip8.is_hires());
+        assert_eq!(lit(&chip8), 4);
+        for idx in [0, 1, HIRES_WIDTH, HIRES_WIDTH + 1] {
+            assert!(chip8.get_display()[idx]);
+        }
+        chip8.tick().unwrap();
+        assert!(!chip8.is_hires());
+        assert_eq!(lit(&chip8), 1);
+        assert!(chip8.get_display()[0]);
+    }
+
+    #[test]
+    fn out_of_range_keys_are_ignored() {
+        let mut chip8 = Chip8::new();
+        let before = chip8.snapshot();
+
+        chip8.keypress(99, true);
+        assert!(!chip8.toggle_key(NUM_KEYS));
+        assert_eq!(chip8.snapshot(), before);
+        assert_eq!(chip8.presses, 0);
+    }
+
+    #[test]
+    fn store_and_load_past_end_of_ram_follow_action() {
+        // Stores V0 to VF with I 4 bytes before the end of the RAM
+        let mut chip8 = Chip8::new();
+        chip8.i_reg = (RAM_SIZE - 4) as u16;
+        assert_eq!(
+            chip8.execute(0xFF55),
+            Err(Chip8Error::RamOutOfBounds(RAM_SIZE as u16))
+        );
+        assert_eq!(
+            chip8.execute(0xFF65),
+            Err(Chip8Error::RamOutOfBounds(RAM_SIZE as u16))
+        );
+
+        let mut chip8 = Chip8::builder()
+            .ram_overflow_action(RamOverflowAction::Wrap)
+            .build();
+        chip8.i_reg = (RAM_SIZE - 4) as u16;
+        chip8.v_reg = core::array::from_fn(|idx| idx as u8 + 1);
+        chip8.execute(0xFF55).unwrap();
+        assert_eq!(&chip8.ram[RAM_SIZE - 4..], &[1, 2, 3, 4]);
+        assert_eq!(chip8.ram[0], 5);
+        chip8.v_reg = [0; NUM_REGS];
+        chip8.execute(0xFF65).unwrap();
+        assert_eq!(chip8.v_reg[4], 5);
+
+        let mut chip8 = Chip8::builder()
+            .ram_overflow_action(RamOverflowAction::Clamp)
+            .build();
+        chip8.i_reg = (RAM_SIZE - 4) as u16;
+        chip8.v_reg = core::array::from_fn(|idx| idx as u8 + 1);
+        chip8.execute(0xFF55).unwrap();
+        assert_eq!(&chip8.ram[RAM_SIZE - 4..], &[1, 2, 3, 16]);
+        chip8.execute(0xFF65).unwrap();
+        assert_eq!(chip8.v_reg[15], 16);
+    }
+
+    #[test]
+    fn bcd_stores_decimal_digits() {
+        let mut chip8 = Chip8::new();
+        chip8.i_reg = 0x300;
+        chip8.v_reg[4] = 234;
+        chip8.execute(0xF433).unwrap();
+        assert_eq!(&chip8.ram[0x300..0x303], &[2, 3, 4]);
+
+        chip8.v_reg[4] = 7;
+        chip8.execute(0xF433).unwrap();
+        assert_eq!(&chip8.ram[0x300..0x303], &[0, 0, 7]);
+
+        // Digits past the end of the RAM follow the overflow action
+        chip8.i_reg = (RAM_SIZE - 2) as u16;
+        assert_eq!(
+            chip8.execute(0xF433),
+            Err(Chip8Error::RamOutOfBounds(RAM_SIZE as u16))
+        );
+    }
+
+    #[test]
+    fn ram_accesses_with_i_near_end_of_address_space_wrap() {
+        let quirks = Quirks {
+            load_store_increments_i: true,
+            ..Default::default()
+        };
+        let mut chip8 = Chip8::builder()
+            .quirks(quirks)
+            .ram_overflow_action(RamOverflowAction::Wrap)
+            .build();
+        chip8.v_reg = core::array::from_fn(|idx| idx as u8 + 1);
+
+        // 0xFFFE wraps to the last 2 bytes of the RAM
+        chip8.i_reg = 0xFFFE;
+        chip8.execute(0xF355).unwrap();
+        assert_eq!(&chip8.ram[RAM_SIZE - 2..], &[1, 2]);
+        assert_eq!(&chip8.ram[..2], &[3, 4]);
+        // I wraps around too instead of overflowing
+        assert_eq!(chip8.i_reg, 0x0002);
+
+        chip8.i_reg = 0xFFFF;
+        chip8.v_reg = [0; NUM_REGS];
+        chip8.execute(0xF165).unwrap();
+        assert_eq!(&chip8.v_reg[..2], &[2, 3]);
+        assert_eq!(chip8.i_reg, 0x0001);
+
+        chip8.i_reg = 0xFFFF;
+        chip8.v_reg[0] = 234;
+        chip8.execute(0xF033).unwrap();
+        assert_eq!(chip8.ram[RAM_SIZE - 1], 2);
+        assert_eq!(&chip8.ram[..2], &[3, 4]);
     }
 }
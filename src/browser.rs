@@ -0,0 +1,186 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::{error, warn};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::frontend::{self, Display, PresentMode, WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::text::{draw_text, CHAR_WIDTH, LINE_HEIGHT};
+
+/// File extensions recognized as Chip-8 ROMs
+const ROM_EXTENSIONS: [&str; 2] = ["ch8", "rom"];
+
+/// Size of each font pixel in the menu, in window pixels
+const TEXT_SCALE: u32 = 4;
+
+/// Margin around the menu, in window pixels
+const MARGIN: u32 = 16;
+
+/// Returns the paths of every ROM file in the given directory, sorted by name
+///
+/// # Arguments
+///
+/// * `dir` - Directory to scan
+pub fn find_roms(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut roms = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_rom = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ROM_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+            .unwrap_or(false);
+
+        if is_rom && path.is_file() {
+            roms.push(path);
+        }
+    }
+
+    roms.sort();
+    Ok(roms)
+}
+
+/// Shows a navigable list of ROMs in an SDL2 window, where the arrow keys
+/// move the selection and Enter picks a ROM. Returns the picked ROM along
+/// with the window's last position, or `None` if the window was closed
+/// (or couldn't be opened).
+///
+/// # Arguments
+///
+/// * `roms` - Paths of the ROMs to choose from
+/// * `position` - Position of the window's top-left corner on the desktop,
+///   or `None` to center it
+pub fn select_rom(roms: &[PathBuf], position: Option<(i32, i32)>) -> Option<(PathBuf, (i32, i32))> {
+    let Display {
+        sdl_context,
+        mut canvas,
+    } = match frontend::open_display(position, 1.0, frontend::SCALE, PresentMode::Vsync) {
+        Ok(display) => display,
+        Err(err) => {
+            error!("Couldn't open the ROM menu: {}", err);
+            return None;
+        }
+    };
+    let mut event_pump = sdl_context.event_pump().unwrap();
+
+    let mut selected: usize = 0;
+    let mut render_failed = false;
+
+    loop {
+        for evt in event_pump.poll_iter() {
+            match evt {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => {
+                    return None;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::Up),
+                    ..
+                } => {
+                    selected = selected.checked_sub(1).unwrap_or(roms.len() - 1);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::Down),
+                    ..
+                } => {
+                    selected = (selected + 1) % roms.len();
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::Return),
+                    ..
+                } => {
+                    // The game opens where the menu was left, even if moved
+                    return Some((roms[selected].clone(), canvas.window().position()));
+                }
+                _ => (),
+            }
+        }
+
+        match draw_menu(roms, selected, &mut canvas) {
+            Ok(()) => render_failed = false,
+            Err(err) => {
+                // Only report the first failure, as it's retried every frame
+                if !render_failed {
+                    warn!("Couldn't draw the ROM menu, retrying: {}", err);
+                }
+                render_failed = true;
+            }
+        }
+    }
+}
+
+/// Draws the list of ROMs to the canvas, scrolling it so that the selected
+/// ROM is always visible.
+///
+/// # Arguments
+///
+/// * `roms` - Paths of the ROMs to choose from
+/// * `selected` - Index of the selected ROM
+/// * `canvas` - SDL2 canvas to draw to
+fn draw_menu(roms: &[PathBuf], selected: usize, canvas: &mut Canvas<Window>) -> Result<(), String> {
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+
+    let line_height = LINE_HEIGHT * TEXT_SCALE;
+    let max_chars = ((WINDOW_WIDTH - 2 * MARGIN) / (CHAR_WIDTH * TEXT_SCALE)) as usize;
+
+    // Leave room for the title and a blank line below it
+    let visible = ((WINDOW_HEIGHT - 2 * MARGIN) / line_height) as usize - 2;
+    let first = selected.saturating_sub(visible - 1);
+
+    draw_text(
+        canvas,
+        "SELECT A ROM (UP / DOWN, ENTER)",
+        MARGIN as i32,
+        MARGIN as i32,
+        TEXT_SCALE,
+    )?;
+
+    for (i, rom) in roms.iter().enumerate().skip(first).take(visible) {
+        let name = rom
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let marker = if i == selected { "> " } else { "  " };
+        let line: String = format!("{}{}", marker, name)
+            .chars()
+            .take(max_chars)
+            .collect();
+
+        let y = MARGIN + (i - first + 2) as u32 * line_height;
+        draw_text(canvas, &line, MARGIN as i32, y as i32, TEXT_SCALE)?;
+    }
+
+    canvas.present();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    #[test]
+    fn find_roms_only_returns_rom_files() {
+        let dir = std::env::temp_dir().join("dorustos-find-roms-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested.ch8")).unwrap();
+        fs::write(dir.join("TETRIS.ch8"), [0x00]).unwrap();
+        fs::write(dir.join("BLITZ.ROM"), [0x00]).unwrap();
+        fs::write(dir.join("README.txt"), [0x00]).unwrap();
+
+        let roms = super::find_roms(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(roms, vec![dir.join("BLITZ.ROM"), dir.join("TETRIS.ch8")]);
+    }
+}
@@ -0,0 +1,57 @@
+/// A ROM embedded in the emulator, so that it runs without any files
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BuiltinRom {
+    /// Name the ROM is selected by
+    pub name: &'static str,
+    /// Short description of the ROM
+    pub description: &'static str,
+    /// Contents of the ROM
+    pub data: &'static [u8],
+}
+
+/// ROMs embedded in the emulator. They're written for this project (under
+/// the same license), so they can be freely distributed along with it.
+pub const BUILTIN_ROMS: &[BuiltinRom] = &[BuiltinRom {
+    name: "bounce",
+    description: "A digit bouncing off the edges of the screen",
+    data: include_bytes!("../roms/bounce.ch8"),
+}];
+
+/// Returns the embedded ROM with the given name, if any
+///
+/// # Arguments
+///
+/// * `name` - Name of the ROM
+pub fn find(name: &str) -> Option<&'static BuiltinRom> {
+    BUILTIN_ROMS.iter().find(|rom| rom.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Chip8;
+
+    #[test]
+    fn builtin_roms_load_and_run() {
+        for rom in BUILTIN_ROMS {
+            let mut chip8 = Chip8::new();
+            chip8.load(rom.data).unwrap();
+
+            for _ in 0..120 {
+                let report = chip8.advance_frame(10);
+                assert_eq!(report.error, None, "{}", rom.name);
+            }
+            assert!(
+                chip8.get_display().iter().any(|pixel| *pixel),
+                "{}",
+                rom.name
+            );
+        }
+    }
+
+    #[test]
+    fn find_selects_rom_by_name() {
+        assert_eq!(find("bounce").map(|rom| rom.name), Some("bounce"));
+        assert_eq!(find("missing"), None);
+    }
+}
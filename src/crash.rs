@@ -0,0 +1,287 @@
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+
+use log::{error, warn};
+
+use crate::headless::format_registers;
+use dorustos::backend::{Chip8, Chip8Error};
+use dorustos::disasm;
+
+/// Amount of RAM bytes written on each line of a crash dump
+const RAM_BYTES_PER_LINE: usize = 16;
+
+thread_local! {
+    /// Copy of the machine running on this thread as of its latest frame,
+    /// for the panic hook to dump
+    static RUNNING: RefCell<Option<Chip8>> = const { RefCell::new(None) };
+}
+
+/// Returns the path a crash dump of a ROM is written to: a file named after
+/// the hash of the ROM, in the user's local data directory (or the temporary
+/// directory, if there's none), e.g.
+/// `~/.local/share/dorustos/crashes/0123456789abcdef.txt` on Linux
+///
+/// # Arguments
+///
+/// * `rom_hash` - Hash of the ROM that crashed
+pub fn dump_path(rom_hash: u64) -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("dorustos")
+        .join("crashes")
+        .join(format!("{:016x}.txt", rom_hash))
+}
+
+/// Returns the path a crash dump is written to when the emulator itself
+/// panics while running a ROM: next to the ROM's regular crash dump (see
+/// `dump_path`), e.g. `~/.local/share/dorustos/crashes/0123456789abcdef-panic.txt`
+///
+/// # Arguments
+///
+/// * `rom_hash` - Hash of the ROM that was running
+pub fn panic_dump_path(rom_hash: u64) -> PathBuf {
+    dump_path(rom_hash).with_file_name(format!("{:016x}-panic.txt", rom_hash))
+}
+
+/// Returns a crash dump of a machine that stopped with an error: the error
+/// and the instruction that caused it, the registers and stack, the most
+/// recently executed instructions, and the contents of the screen and RAM
+///
+/// # Arguments
+///
+/// * `chip8` - Chip-8 emulator instance, right after the error
+/// * `err` - The error the machine stopped with
+pub fn format_dump(chip8: &Chip8, err: &Chip8Error) -> String {
+    let trace: Vec<(u16, u16)> = chip8.recent_instructions().collect();
+    let mut dump = String::new();
+
+    writeln!(dump, "Error: {}", err).unwrap();
+    // Errors while fetching happen before the failing instruction is traced
+    let failing = match err {
+        Chip8Error::PcOutOfBounds(_) => None,
+        _ => trace.last(),
+    };
+    match failing {
+        Some(&(addr, op)) => writeln!(
+            dump,
+            "Failing instruction: {}",
+            format_instruction(addr, op)
+        )
+        .unwrap(),
+        None => writeln!(dump, "Failing instruction: none").unwrap(),
+    }
+    write_state(&mut dump, chip8);
+
+    dump
+}
+
+/// Returns a crash dump of a machine the emulator was running when it
+/// panicked: the panic message, followed by the same state as `format_dump`.
+/// Like every crash dump, it's meant to be read, and can't be loaded back
+/// into the emulator.
+///
+/// # Arguments
+///
+/// * `chip8` - Chip-8 emulator instance, as of its latest frame
+/// * `message` - Message of the panic
+pub fn format_panic_dump(chip8: &Chip8, message: &str) -> String {
+    let mut dump = String::new();
+
+    writeln!(dump, "Panic: {}", message).unwrap();
+    write_state(&mut dump, chip8);
+
+    dump
+}
+
+/// Writes the state of a machine to a crash dump: its ROM hash, registers,
+/// stack, recently executed instructions, screen and RAM
+///
+/// # Arguments
+///
+/// * `dump` - The crash dump to write to
+/// * `chip8` - Chip-8 emulator instance
+fn write_state(dump: &mut String, chip8: &Chip8) {
+    let snapshot = chip8.snapshot();
+    let trace: Vec<(u16, u16)> = chip8.recent_instructions().collect();
+
+    writeln!(dump, "ROM hash: {:016x}", chip8.rom_hash()).unwrap();
+    writeln!(dump, "Registers: {}", format_registers(&snapshot)).unwrap();
+    let stack: Vec<String> = chip8
+        .stack()
+        .iter()
+        .map(|addr| format!("{:#05X}", addr))
+        .collect();
+    writeln!(dump, "Stack: [{}]", stack.join(", ")).unwrap();
+
+    writeln!(dump, "\nLast {} instructions:", trace.len()).unwrap();
+    for &(addr, op) in &trace {
+        writeln!(dump, "{}", format_instruction(addr, op)).unwrap();
+    }
+
+    writeln!(dump, "\nScreen:").unwrap();
+    let (width, _) = chip8.display_dimensions();
+    for row in snapshot.screen.chunks(width) {
+        let row: String = row.iter().map(|lit| if *lit { '#' } else { '.' }).collect();
+        writeln!(dump, "{}", row).unwrap();
+    }
+
+    writeln!(dump, "\nRAM:").unwrap();
+    for (line, bytes) in snapshot.ram.chunks(RAM_BYTES_PER_LINE).enumerate() {
+        let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        writeln!(
+            dump,
+            "{:#05X}: {}",
+            line * RAM_BYTES_PER_LINE,
+            bytes.join(" ")
+        )
+        .unwrap();
+    }
+}
+
+/// Returns an instruction with its address, opcode and assembly mnemonic
+/// (e.g. `0x200: 00EE  RET`)
+///
+/// # Arguments
+///
+/// * `addr` - Address of the instruction
+/// * `op` - Operation code of the instruction
+fn format_instruction(addr: u16, op: u16) -> String {
+    let mnemonic = disasm::disassemble(op).unwrap_or_else(|| "unknown".to_string());
+    format!("{:#05X}: {:04X}  {}", addr, op, mnemonic)
+}
+
+/// Writes a crash dump to a file, creating its directory if needed
+///
+/// # Arguments
+///
+/// * `chip8` - Chip-8 emulator instance, right after the error
+/// * `err` - The error the machine stopped with
+/// * `path` - Path of the file to write
+pub fn write_dump(chip8: &Chip8, err: &Chip8Error, path: &Path) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, format_dump(chip8, err))
+}
+
+/// Writes a crash dump of a machine that stopped with an error to its usual
+/// location (see `dump_path`), and prints where it was written
+///
+/// # Arguments
+///
+/// * `chip8` - Chip-8 emulator instance, right after the error
+/// * `err` - The error the machine stopped with
+pub fn report(chip8: &Chip8, err: &Chip8Error) {
+    let path = dump_path(chip8.rom_hash());
+    match write_dump(chip8, err, &path) {
+        Ok(()) => error!("Wrote a crash dump to {}", path.display()),
+        Err(err) => warn!("Couldn't write a crash dump to {}: {}", path.display(), err),
+    }
+}
+
+/// Keeps a copy of the machine running on this thread, for the panic hook
+/// to dump if the emulator panics later on. Meant to be called once per
+/// frame, as copying the machine every instruction would slow it down.
+///
+/// # Arguments
+///
+/// * `chip8` - Chip-8 emulator instance
+pub fn remember(chip8: &Chip8) {
+    RUNNING.with(|running| {
+        if let Ok(mut running) = running.try_borrow_mut() {
+            *running = Some(chip8.clone());
+        }
+    });
+}
+
+/// Installs a panic hook that, after printing the panic as usual, writes a
+/// crash dump of the machine last passed to `remember` on the panicking
+/// thread (see `panic_dump_path`). This is best-effort: the dump is as of
+/// the latest frame, and nothing is written if the panic happens before
+/// the first one, on another thread, or while writing the dump itself.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        write_panic_dump(info);
+    }));
+}
+
+/// Writes a crash dump of the machine last passed to `remember`, if any,
+/// for a panic on the current thread
+///
+/// # Arguments
+///
+/// * `info` - Information about the panic
+fn write_panic_dump(info: &PanicHookInfo) {
+    let running = RUNNING.try_with(|running| running.try_borrow().ok().and_then(|c| c.clone()));
+    let Ok(Some(chip8)) = running else {
+        return;
+    };
+
+    let path = panic_dump_path(chip8.rom_hash());
+    let dump = format_panic_dump(&chip8, &info.to_string());
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::write(&path, dump));
+    match written {
+        Ok(()) => error!("Wrote a crash dump to {}", path.display()),
+        Err(err) => warn!("Couldn't write a crash dump to {}: {}", path.display(), err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dorustos::backend::Chip8;
+
+    #[test]
+    fn errors_produce_dump_file() {
+        let mut chip8 = Chip8::new();
+        // Sets V0, then returns without having called a subroutine
+        chip8.load(&[0x60, 0x2A, 0x00, 0xEE]).unwrap();
+        chip8.tick().unwrap();
+        let err = chip8.tick().unwrap_err();
+
+        let dir = std::env::temp_dir().join(format!("dorustos-crash-{}", std::process::id()));
+        let path = dir.join("crash.txt");
+        super::write_dump(&chip8, &err, &path).unwrap();
+
+        let dump = std::fs::read_to_string(&path).unwrap();
+        assert!(dump.starts_with(&format!("Error: {}\n", err)));
+        assert!(dump.contains("Failing instruction: 0x202: 00EE  RET\n"));
+        assert!(dump.contains("Last 2 instructions:\n0x200: 602A"));
+        assert!(dump.contains("V0=0x2A"));
+        assert!(dump.contains("0x200: 60 2A 00 EE 00"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_opcode_produces_dump() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0xF0, 0xFF]).unwrap();
+        let err = chip8.tick().unwrap_err();
+
+        let dump = super::format_dump(&chip8, &err);
+        assert!(dump.starts_with("Error: Unsupported opcode: F0FF\n"));
+        assert!(dump.contains("Failing instruction: 0x200: F0FF"));
+    }
+
+    #[test]
+    fn panic_dump_holds_message_and_state() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x60, 0x2A, 0x12, 0x02]).unwrap();
+        chip8.tick().unwrap();
+
+        let dump = super::format_panic_dump(&chip8, "something broke");
+        assert!(dump.starts_with("Panic: something broke\nROM hash: "));
+        assert!(dump.contains("V0=0x2A"));
+        assert!(dump.contains("Last 1 instructions:\n0x200: 602A"));
+
+        let path = super::panic_dump_path(0xAB);
+        assert!(path.ends_with("crashes/00000000000000ab-panic.txt"));
+    }
+}
@@ -0,0 +1,105 @@
+use std::time::{Duration, Instant};
+
+use dorustos::backend::NUM_KEYS;
+
+/// Filters out key chatter: a release immediately followed by a new press
+/// of the same key (within the debounce window) is ignored, so the key stays
+/// pressed. Releases are therefore delayed by the length of the window.
+pub struct Debouncer {
+    window: Duration,
+    pending: [Option<Instant>; NUM_KEYS],
+}
+
+impl Debouncer {
+    /// Returns a new debouncer
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - Time a release waits for a new press before it's applied
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: [None; NUM_KEYS],
+        }
+    }
+
+    /// Registers a key press, returning whether it should reach the keypad.
+    /// A press that cancels a pending release doesn't, as the key never
+    /// stopped being pressed.
+    ///
+    /// # Arguments
+    ///
+    /// * `btn` - Index of the key in the keypad
+    pub fn press(&mut self, btn: usize) -> bool {
+        self.pending[btn].take().is_none()
+    }
+
+    /// Registers a key release, which is held back until the debounce
+    /// window passes without a new press
+    ///
+    /// # Arguments
+    ///
+    /// * `btn` - Index of the key in the keypad
+    /// * `now` - Moment the key was released
+    pub fn release(&mut self, btn: usize, now: Instant) {
+        self.pending[btn] = Some(now);
+    }
+
+    /// Returns the keys whose releases are due, i.e. that weren't pressed
+    /// again within the debounce window
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - Current moment
+    pub fn due_releases(&mut self, now: Instant) -> Vec<usize> {
+        let mut due = Vec::new();
+
+        for (btn, pending) in self.pending.iter_mut().enumerate() {
+            if pending.is_some_and(|released| now - released >= self.window) {
+                *pending = None;
+                due.push(btn);
+            }
+        }
+
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Debouncer;
+
+    #[test]
+    fn debouncer_ignores_release_followed_by_quick_press() {
+        let mut debouncer = Debouncer::new(Duration::from_millis(20));
+        let start = Instant::now();
+
+        assert!(debouncer.press(0x5));
+        debouncer.release(0x5, start);
+        assert!(!debouncer.press(0x5));
+        assert!(debouncer
+            .due_releases(start + Duration::from_millis(30))
+            .is_empty());
+    }
+
+    #[test]
+    fn debouncer_applies_release_after_window() {
+        let mut debouncer = Debouncer::new(Duration::from_millis(20));
+        let start = Instant::now();
+
+        debouncer.release(0x5, start);
+        assert!(debouncer
+            .due_releases(start + Duration::from_millis(10))
+            .is_empty());
+        assert_eq!(
+            debouncer.due_releases(start + Duration::from_millis(20)),
+            vec![0x5]
+        );
+        assert!(debouncer
+            .due_releases(start + Duration::from_millis(40))
+            .is_empty());
+        assert!(debouncer.press(0x5));
+    }
+}
@@ -0,0 +1,446 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::stdin;
+use std::ops::Range;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+use dorustos::backend::{Chip8, RAM_SIZE, START_ADDR};
+use dorustos::disasm::{self, DecodedOp};
+use log::error;
+
+/// A command that can be typed into the debugger console
+#[derive(Debug, PartialEq)]
+enum Command {
+    /// Sets a breakpoint at the given address
+    Break(u16),
+    /// Removes the breakpoint at the given address
+    Delete(u16),
+    /// Pauses execution whenever the given value changes
+    Watch(WatchTarget),
+    /// Removes the watchpoint on the given value
+    Unwatch(WatchTarget),
+    /// Lists all breakpoints and watchpoints
+    List,
+    /// Removes all breakpoints and watchpoints
+    Clear,
+    /// Writes the given range of the RAM to a file
+    Dump(String, Range<usize>),
+    /// Prints the fields and mnemonic of the given operation code
+    Decode(u16),
+    /// Resumes execution of a halted machine
+    Resume,
+    /// Prints the return addresses on the call stack
+    Stack,
+    /// Prints the most recently executed instructions
+    Trace,
+}
+
+/// A value of the machine's state that can be watched for changes
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum WatchTarget {
+    /// One of the V0 to VF registers
+    Register(usize),
+    /// A byte in the RAM
+    Memory(usize),
+}
+
+impl WatchTarget {
+    /// Returns the current value of the target in the given machine
+    ///
+    /// # Arguments
+    ///
+    /// * `chip8` - Chip-8 emulator instance
+    fn read(&self, chip8: &Chip8) -> u8 {
+        match *self {
+            WatchTarget::Register(idx) => chip8.register(idx),
+            WatchTarget::Memory(addr) => chip8.peek_ram(addr),
+        }
+    }
+
+    /// Returns a human-readable name for the target
+    fn name(&self) -> String {
+        match *self {
+            WatchTarget::Register(idx) => format!("V{:X}", idx),
+            WatchTarget::Memory(addr) => format!("mem {:#05X}", addr),
+        }
+    }
+}
+
+/// A watched value, along with the last value seen
+struct Watchpoint {
+    target: WatchTarget,
+    value: u8,
+}
+
+/// An interactive debugger that reads commands from the standard input
+/// while the emulator is running
+pub struct Debugger {
+    breakpoints: HashSet<u16>,
+    resume_pc: Option<u16>,
+    watchpoints: Vec<Watchpoint>,
+    commands: Receiver<String>,
+}
+
+impl Debugger {
+    /// Returns a new debugger with no breakpoints, and starts listening
+    /// for commands on the standard input
+    pub fn new() -> Self {
+        let (sender, receiver) = channel();
+
+        thread::spawn(move || {
+            for line in stdin().lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            breakpoints: HashSet::new(),
+            resume_pc: None,
+            watchpoints: Vec::new(),
+            commands: receiver,
+        }
+    }
+
+    /// Runs every command typed into the console since the last call
+    ///
+    /// # Arguments
+    ///
+    /// * `chip8` - Chip-8 emulator instance being debugged
+    pub fn process_commands(&mut self, chip8: &mut Chip8) {
+        while let Ok(line) = self.commands.try_recv() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match parse_command(&line) {
+                Ok(command) => self.run_command(command, chip8),
+                Err(msg) => error!("{}", msg),
+            }
+        }
+    }
+
+    /// Returns whether execution should pause before running the instruction
+    /// at the given address. A breakpoint that was just hit is skipped once,
+    /// so that execution can be resumed from it.
+    ///
+    /// # Arguments
+    ///
+    /// * `pc` - Address of the next instruction to execute
+    pub fn should_break(&mut self, pc: u16) -> bool {
+        if self.resume_pc.take() == Some(pc) || !self.breakpoints.contains(&pc) {
+            return false;
+        }
+
+        println!("hit breakpoint at {:#05X}", pc);
+        self.resume_pc = Some(pc);
+        true
+    }
+
+    /// Returns whether any watched value changed since the last check,
+    /// printing the old and new values of every change
+    ///
+    /// # Arguments
+    ///
+    /// * `chip8` - Chip-8 emulator instance being debugged
+    pub fn check_watchpoints(&mut self, chip8: &Chip8) -> bool {
+        let mut changed = false;
+
+        for watchpoint in self.watchpoints.iter_mut() {
+            let value = watchpoint.target.read(chip8);
+            if value != watchpoint.value {
+                println!(
+                    "watchpoint {} changed: {:#04X} -> {:#04X}",
+                    watchpoint.target.name(),
+                    watchpoint.value,
+                    value
+                );
+                watchpoint.value = value;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    /// Executes a single debugger command
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to execute
+    /// * `chip8` - Chip-8 emulator instance being debugged
+    fn run_command(&mut self, command: Command, chip8: &mut Chip8) {
+        match command {
+            Command::Break(addr) => {
+                self.breakpoints.insert(addr);
+                println!("breakpoint set at {:#05X}", addr);
+            }
+            Command::Delete(addr) => {
+                if self.breakpoints.remove(&addr) {
+                    println!("breakpoint removed at {:#05X}", addr);
+                } else {
+                    println!("no breakpoint at {:#05X}", addr);
+                }
+            }
+            Command::Watch(target) => {
+                if !self.watchpoints.iter().any(|w| w.target == target) {
+                    let value = target.read(chip8);
+                    self.watchpoints.push(Watchpoint { target, value });
+                }
+                println!("watching {}", target.name());
+            }
+            Command::Unwatch(target) => {
+                let count = self.watchpoints.len();
+                self.watchpoints.retain(|w| w.target != target);
+
+                if self.watchpoints.len() < count {
+                    println!("stopped watching {}", target.name());
+                } else {
+                    println!("not watching {}", target.name());
+                }
+            }
+            Command::List => {
+                let mut addrs: Vec<_> = self.breakpoints.iter().collect();
+                addrs.sort();
+
+                if addrs.is_empty() && self.watchpoints.is_empty() {
+                    println!("no breakpoints or watchpoints set");
+                }
+                for addr in addrs {
+                    println!("breakpoint at {:#05X}", addr);
+                }
+                for watchpoint in self.watchpoints.iter() {
+                    println!(
+                        "watchpoint on {} (currently {:#04X})",
+                        watchpoint.target.name(),
+                        watchpoint.value
+                    );
+                }
+            }
+            Command::Clear => {
+                self.breakpoints.clear();
+                self.watchpoints.clear();
+                println!("all breakpoints and watchpoints cleared");
+            }
+            Command::Dump(path, range) => {
+                let bytes: Vec<u8> = range.clone().map(|addr| chip8.peek_ram(addr)).collect();
+
+                match fs::write(&path, bytes) {
+                    Ok(()) => println!(
+                        "RAM {:#05X}..{:#05X} dumped to {}",
+                        range.start, range.end, path
+                    ),
+                    Err(err) => error!("Couldn't write {}: {}", path, err),
+                }
+            }
+            Command::Decode(op) => print_decoded(&DecodedOp::new(op)),
+            Command::Resume => {
+                if chip8.is_halted() {
+                    chip8.resume();
+                    println!("resumed at {:#05X}", chip8.pc());
+                } else {
+                    println!("not halted");
+                }
+            }
+            Command::Stack => {
+                if chip8.stack().is_empty() {
+                    println!("stack is empty");
+                }
+                for (level, addr) in chip8.stack().iter().enumerate().rev() {
+                    println!("#{} {:#05X}", level, addr);
+                }
+            }
+            Command::Trace => {
+                if chip8.recent_instructions().next().is_none() {
+                    println!("no instructions traced");
+                }
+                for (addr, op) in chip8.recent_instructions() {
+                    let mnemonic = disasm::disassemble(op);
+                    let mnemonic = mnemonic.as_deref().unwrap_or("(unsupported opcode)");
+                    println!("{:#05X} {:04X} {}", addr, op, mnemonic);
+                }
+            }
+        }
+    }
+}
+
+/// Prints a labeled breakdown of the fields of an operation code, along
+/// with its mnemonic
+///
+/// # Arguments
+///
+/// * `decoded` - The decoded instruction
+fn print_decoded(decoded: &DecodedOp) {
+    let fields = &decoded.fields;
+    let (digit1, digit2, digit3, digit4) = fields.digits;
+    let mnemonic = decoded
+        .mnemonic
+        .as_deref()
+        .unwrap_or("(unsupported opcode)");
+
+    println!("opcode   {:#06X}", decoded.op);
+    println!(
+        "digits   {:X} {:X} {:X} {:X}",
+        digit1, digit2, digit3, digit4
+    );
+    println!("x        {:X}", fields.x);
+    println!("y        {:X}", fields.y);
+    println!("nnn      {:#05X}", fields.nnn);
+    println!("nn       {:#04X}", fields.nn);
+    println!("n        {:#03X}", fields.n);
+    println!("mnemonic {}", mnemonic);
+}
+
+/// Parses a line typed into the debugger console into a command
+///
+/// # Arguments
+///
+/// * `line` - The line to parse
+fn parse_command(line: &str) -> Result<Command, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+
+    match words.as_slice() {
+        ["break", addr] => parse_addr(addr).map(Command::Break),
+        ["delete", addr] => parse_addr(addr).map(Command::Delete),
+        ["watch", target @ ..] => parse_watch_target(target).map(Command::Watch),
+        ["unwatch", target @ ..] => parse_watch_target(target).map(Command::Unwatch),
+        ["list"] => Ok(Command::List),
+        ["clear"] => Ok(Command::Clear),
+        ["resume"] => Ok(Command::Resume),
+        ["stack"] => Ok(Command::Stack),
+        ["trace"] => Ok(Command::Trace),
+        ["dump", path] => Ok(Command::Dump(
+            path.to_string(),
+            START_ADDR as usize..RAM_SIZE,
+        )),
+        ["dump", path, start, end] => {
+            let start = parse_addr(start)? as usize;
+            let end = parse_addr(end)? as usize;
+            if start >= end || end > RAM_SIZE {
+                return Err(format!("Invalid range: {:#05X}..{:#05X}", start, end));
+            }
+            Ok(Command::Dump(path.to_string(), start..end))
+        }
+        ["decode", op] => parse_addr(op)
+            .map(Command::Decode)
+            .map_err(|_| format!("Invalid opcode: {}", op)),
+        _ => Err(format!("Unknown command: {}", line.trim())),
+    }
+}
+
+/// Parses the target of a watchpoint, either a register (`v5`) or
+/// a RAM address (`mem 0x300`)
+///
+/// # Arguments
+///
+/// * `words` - The words describing the target
+fn parse_watch_target(words: &[&str]) -> Result<WatchTarget, String> {
+    match words {
+        ["mem", addr] => {
+            let addr = parse_addr(addr)? as usize;
+            if addr >= RAM_SIZE {
+                return Err(format!("Address out of range: {:#05X}", addr));
+            }
+            Ok(WatchTarget::Memory(addr))
+        }
+        [reg] if reg.len() == 2 && reg.to_lowercase().starts_with('v') => {
+            u8::from_str_radix(&reg[1..], 16)
+                .map(|idx| WatchTarget::Register(idx as usize))
+                .map_err(|_| format!("Invalid register: {}", reg))
+        }
+        _ => Err(format!("Invalid watch target: {}", words.join(" "))),
+    }
+}
+
+/// Parses a hexadecimal address, with or without a `0x` prefix
+///
+/// # Arguments
+///
+/// * `text` - The address to parse
+fn parse_addr(text: &str) -> Result<u16, String> {
+    let digits = text.trim_start_matches("0x").trim_start_matches("0X");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("Invalid address: {}", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, WatchTarget};
+
+    #[test]
+    fn parse_command_parses_breakpoint_commands() {
+        let result_1 = super::parse_command("break 0x2A6");
+        assert_eq!(result_1, Ok(Command::Break(0x2A6)));
+
+        let result_2 = super::parse_command("delete 2a6");
+        assert_eq!(result_2, Ok(Command::Delete(0x2A6)));
+
+        let result_3 = super::parse_command(" list ");
+        assert_eq!(result_3, Ok(Command::List));
+
+        let result_4 = super::parse_command("clear");
+        assert_eq!(result_4, Ok(Command::Clear));
+
+        let result_6 = super::parse_command("resume");
+        assert_eq!(result_6, Ok(Command::Resume));
+
+        let result_7 = super::parse_command("stack");
+        assert_eq!(result_7, Ok(Command::Stack));
+
+        let result_8 = super::parse_command("trace");
+        assert_eq!(result_8, Ok(Command::Trace));
+
+        let result_5 = super::parse_command("break nowhere");
+        assert!(result_5.is_err());
+    }
+
+    #[test]
+    fn parse_command_parses_watchpoint_commands() {
+        let result_1 = super::parse_command("watch v5");
+        assert_eq!(result_1, Ok(Command::Watch(WatchTarget::Register(0x5))));
+
+        let result_2 = super::parse_command("watch VF");
+        assert_eq!(result_2, Ok(Command::Watch(WatchTarget::Register(0xF))));
+
+        let result_3 = super::parse_command("unwatch mem 0x300");
+        assert_eq!(result_3, Ok(Command::Unwatch(WatchTarget::Memory(0x300))));
+
+        let result_4 = super::parse_command("watch v10");
+        assert!(result_4.is_err());
+
+        let result_5 = super::parse_command("watch mem 0x1000");
+        assert!(result_5.is_err());
+    }
+
+    #[test]
+    fn parse_command_parses_dump_commands() {
+        let result_1 = super::parse_command("dump ram.bin");
+        assert_eq!(
+            result_1,
+            Ok(Command::Dump("ram.bin".to_string(), 0x200..0x1000))
+        );
+
+        let result_2 = super::parse_command("dump ram.bin 0x300 0x310");
+        assert_eq!(
+            result_2,
+            Ok(Command::Dump("ram.bin".to_string(), 0x300..0x310))
+        );
+
+        let result_3 = super::parse_command("dump ram.bin 0x310 0x300");
+        assert!(result_3.is_err());
+
+        let result_4 = super::parse_command("dump ram.bin 0x300 0x1001");
+        assert!(result_4.is_err());
+    }
+
+    #[test]
+    fn parse_command_parses_decode_commands() {
+        let result_1 = super::parse_command("decode 0xD125");
+        assert_eq!(result_1, Ok(Command::Decode(0xD125)));
+
+        let result_2 = super::parse_command("decode 12345");
+        assert!(result_2.is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use crate::backend::START_ADDR;
+
+/// The fields an operation code is split into when it's decoded
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fields {
+    /// The four hexadecimal digits of the operation code, most significant first
+    pub digits: (u16, u16, u16, u16),
+    /// Index of the VX register (second digit)
+    pub x: usize,
+    /// Index of the VY register (third digit)
+    pub y: usize,
+    /// Address (lowest 12 bits)
+    pub nnn: u16,
+    /// Byte constant (lowest 8 bits)
+    pub nn: u8,
+    /// Nibble constant (lowest 4 bits)
+    pub n: u8,
+}
+
+impl Fields {
+    /// Splits an operation code into its fields
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - The operation code to decode
+    pub fn new(op: u16) -> Self {
+        let digits = digits(op);
+
+        Self {
+            digits,
+            x: digits.1 as usize,
+            y: digits.2 as usize,
+            nnn: op & 0x0FFF,
+            nn: (op & 0x00FF) as u8,
+            n: digits.3 as u8,
+        }
+    }
+}
+
+/// A decoded instruction: its operation code, fields and mnemonic
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedOp {
+    /// The operation code
+    pub op: u16,
+    /// The fields the operation code is split into
+    pub fields: Fields,
+    /// The assembly mnemonic, or `None` if the operation code isn't supported
+    pub mnemonic: Option<String>,
+}
+
+impl DecodedOp {
+    /// Decodes an operation code
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - The operation code to decode
+    pub fn new(op: u16) -> Self {
+        Self {
+            op,
+            fields: Fields::new(op),
+            mnemonic: disassemble(op),
+        }
+    }
+}
+
+/// Returns the four hexadecimal digits of an operation code, most
+/// significant first
+///
+/// # Arguments
+///
+/// * `op` - The operation code to split
+pub fn digits(op: u16) -> (u16, u16, u16, u16) {
+    (
+        (op & 0xF000) >> 12,
+        (op & 0x0F00) >> 8,
+        (op & 0x00F0) >> 4,
+        op & 0x000F,
+    )
+}
+
+/// Returns the assembly mnemonic of an operation code (e.g. `DRW V1, V2, 5`),
+/// or `None` if the virtual machine doesn't support it
+///
+/// # Arguments
+///
+/// * `op` - The operation code to disassemble
+pub fn disassemble(op: u16) -> Option<String> {
+    let Fields {
+        digits,
+        x,
+        y,
+        nnn,
+        nn,
+        n,
+    } = Fields::new(op);
+
+    let mnemonic = match digits {
+        (0, 0, 0, 0) => "NOP".to_string(),
+        (0, 0, 0xE, 0) => "CLS".to_string(),
+        (0, 0, 0xE, 0xE) => "RET".to_string(),
+        (0, 0, 0xF, 0xB) => "SCR".to_string(),
+        (0, 0, 0xF, 0xC) => "SCL".to_string(),
+        (0, 0, 0xF, 0xD) => "EXIT".to_string(),
+        (0, 0, 0xF, 0xE) => "LOW".to_string(),
+        (0, 0, 0xF, 0xF) => "HIGH".to_string(),
+        (1, _, _, _) => format!("JP {:#05X}", nnn),
+        (2, _, _, _) => format!("CALL {:#05X}", nnn),
+        (3, _, _, _) => format!("SE V{:X}, {:#04X}", x, nn),
+        (4, _, _, _) => format!("SNE V{:X}, {:#04X}", x, nn),
+        (5, _, _, 0) => format!("SE V{:X}, V{:X}", x, y),
+        (6, _, _, _) => format!("LD V{:X}, {:#04X}", x, nn),
+        (7, _, _, _) => format!("ADD V{:X}, {:#04X}", x, nn),
+        (8, _, _, 0) => format!("LD V{:X}, V{:X}", x, y),
+        (8, _, _, 1) => format!("OR V{:X}, V{:X}", x, y),
+        (8, _, _, 2) => format!("AND V{:X}, V{:X}", x, y),
+        (8, _, _, 3) => format!("XOR V{:X}, V{:X}", x, y),
+        (8, _, _, 4) => format!("ADD V{:X}, V{:X}", x, y),
+        (8, _, _, 5) => format!("SUB V{:X}, V{:X}", x, y),
+        (8, _, _, 6) => format!("SHR V{:X}", x),
+        (8, _, _, 7) => format!("SUBN V{:X}, V{:X}", x, y),
+        (8, _, _, 0xE) => format!("SHL V{:X}", x),
+        (9, _, _, 0) => format!("SNE V{:X}, V{:X}", x, y),
+        (0xA, _, _, _) => format!("LD I, {:#05X}", nnn),
+        (0xB, _, _, _) => format!("JP V0, {:#05X}", nnn),
+        (0xC, _, _, _) => format!("RND V{:X}, {:#04X}", x, nn),
+        (0xD, _, _, _) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        (0xE, _, 9, 0xE) => format!("SKP V{:X}", x),
+        (0xE, _, 0xA, 1) => format!("SKNP V{:X}", x),
+        (0xF, _, 0, 7) => format!("LD V{:X}, DT", x),
+        (0xF, _, 0, 0xA) => format!("LD V{:X}, K", x),
+        (0xF, _, 1, 5) => format!("LD DT, V{:X}", x),
+        (0xF, _, 1, 8) => format!("LD ST, V{:X}", x),
+        (0xF, _, 1, 0xE) => format!("ADD I, V{:X}", x),
+        (0xF, _, 2, 9) => format!("LD F, V{:X}", x),
+        (0xF, _, 3, 3) => format!("LD B, V{:X}", x),
+        (0xF, _, 5, 5) => format!("LD [I], V{:X}", x),
+        (0xF, _, 6, 5) => format!("LD V{:X}, [I]", x),
+        (_, _, _, _) => return None,
+    };
+
+    Some(mnemonic)
+}
+
+/// Returns the address and operation code of every word of a ROM the
+/// virtual machine doesn't support, as if the ROM was loaded at the start
+/// of the program area. Every word is treated as an instruction, so data
+/// stored in the ROM (e.g. sprites) may show up as well.
+///
+/// # Arguments
+///
+/// * `data` - The contents of the ROM
+pub fn unsupported_ops(data: &[u8]) -> Vec<(u16, u16)> {
+    data.chunks_exact(2)
+        .enumerate()
+        .map(|(idx, word)| {
+            let addr = START_ADDR + 2 * idx as u16;
+            (addr, u16::from_be_bytes([word[0], word[1]]))
+        })
+        .filter(|&(_, op)| disassemble(op).is_none())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_split_operation_code() {
+        let fields = Fields::new(0xD125);
+        assert_eq!(fields.digits, (0xD, 0x1, 0x2, 0x5));
+        assert_eq!(fields.x, 0x1);
+        assert_eq!(fields.y, 0x2);
+        assert_eq!(fields.nnn, 0x125);
+        assert_eq!(fields.nn, 0x25);
+        assert_eq!(fields.n, 0x5);
+    }
+
+    #[test]
+    fn disassemble_resolves_mnemonics() {
+        assert_eq!(disassemble(0x00E0), Some("CLS".to_string()));
+        assert_eq!(disassemble(0x2ABC), Some("CALL 0xABC".to_string()));
+        assert_eq!(disassemble(0x6A2F), Some("LD VA, 0x2F".to_string()));
+        assert_eq!(disassemble(0xD125), Some("DRW V1, V2, 5".to_string()));
+        assert_eq!(disassemble(0xF365), Some("LD V3, [I]".to_string()));
+        assert_eq!(disassemble(0x5121), None);
+        assert_eq!(disassemble(0xFFFF), None);
+    }
+
+    #[test]
+    fn unsupported_ops_lists_addresses() {
+        let rom = [0x00, 0xE0, 0x51, 0x21, 0x12, 0x00, 0xFF, 0xFF, 0x60];
+        assert_eq!(
+            unsupported_ops(&rom),
+            vec![(0x202, 0x5121), (0x206, 0xFFFF)]
+        );
+        assert!(unsupported_ops(&[0x00, 0xE0]).is_empty());
+    }
+}
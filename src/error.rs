@@ -0,0 +1,123 @@
+use thiserror::Error;
+
+use crate::backend::{RAM_SIZE, START_ADDR};
+
+/// Any error returned by the emulator's library, for callers that want to
+/// handle every kind of error in one place
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum Error {
+    /// A program couldn't be loaded into the virtual machine
+    #[error(transparent)]
+    Load(#[from] LoadError),
+    /// The virtual machine stopped while executing a program
+    #[error(transparent)]
+    Execution(#[from] Chip8Error),
+}
+
+/// Errors that can happen while loading a program into the virtual machine
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum LoadError {
+    /// The program has no instructions
+    #[error("ROM is empty")]
+    Empty,
+    /// The program (of the given size in bytes) doesn't fit in the RAM
+    #[error("ROM is too large ({0} bytes, at most {max} fit in RAM)", max = RAM_SIZE - START_ADDR as usize)]
+    TooLarge(usize),
+    /// The data doesn't fit in the RAM when placed at the given address
+    #[error("{len} bytes don't fit in RAM at address {addr:#05X}")]
+    OutOfBounds {
+        /// Address the data was going to be placed at
+        addr: usize,
+        /// Size of the data in bytes
+        len: usize,
+    },
+}
+
+/// Errors that can happen while executing instructions on the virtual machine
+#[derive(Clone, Copy, Debug, Error, PartialEq)]
+pub enum Chip8Error {
+    /// A subroutine was called with every stack level (of the given depth) in use
+    #[error("Stack overflow: subroutine calls nested deeper than {0} levels")]
+    StackOverflow(usize),
+    /// The program counter (at the given address) ran off the end of the RAM
+    #[error("Program counter ran off the end of the RAM (at {0:#05X})")]
+    PcOutOfBounds(u16),
+    /// The program wrote to the font region (at the given address) while
+    /// it was protected
+    #[error("Program wrote to the protected font region (at {0:#05X})")]
+    FontWrite(u16),
+    /// The program returned from a subroutine (at the given address) while
+    /// no subroutine was running, which usually means it jumped into data
+    #[error("Returned from a subroutine with an empty stack (at {0:#05X})")]
+    EmptyStackReturn(u16),
+    /// The program ran an opcode (the given one) that was disabled
+    #[error("Opcode {0:04X} is disabled")]
+    DisabledOpcode(u16),
+    /// The program stored or loaded registers past the end of the RAM (at
+    /// the given address)
+    #[error("Program accessed the RAM past its end (at {0:#05X})")]
+    RamOutOfBounds(u16),
+    /// The program ran an opcode (the given one) that the emulator doesn't
+    /// support
+    #[error("Unsupported opcode: {0:04X}")]
+    UnsupportedOpcode(u16),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_errors_display_details() {
+        assert_eq!(LoadError::Empty.to_string(), "ROM is empty");
+        assert_eq!(
+            LoadError::TooLarge(4000).to_string(),
+            "ROM is too large (4000 bytes, at most 3584 fit in RAM)"
+        );
+        assert_eq!(
+            LoadError::OutOfBounds {
+                addr: 0xFFE,
+                len: 4
+            }
+            .to_string(),
+            "4 bytes don't fit in RAM at address 0xFFE"
+        );
+    }
+
+    #[test]
+    fn chip8_errors_display_details() {
+        assert_eq!(
+            Chip8Error::StackOverflow(16).to_string(),
+            "Stack overflow: subroutine calls nested deeper than 16 levels"
+        );
+        assert_eq!(
+            Chip8Error::PcOutOfBounds(0x1000).to_string(),
+            "Program counter ran off the end of the RAM (at 0x1000)"
+        );
+        assert_eq!(
+            Chip8Error::EmptyStackReturn(0x2A4).to_string(),
+            "Returned from a subroutine with an empty stack (at 0x2A4)"
+        );
+        assert_eq!(
+            Chip8Error::DisabledOpcode(0xC0FF).to_string(),
+            "Opcode C0FF is disabled"
+        );
+        assert_eq!(
+            Chip8Error::RamOutOfBounds(0x1003).to_string(),
+            "Program accessed the RAM past its end (at 0x1003)"
+        );
+    }
+
+    #[test]
+    fn error_wraps_specific_errors() {
+        let err: Error = Chip8Error::FontWrite(0x10).into();
+        assert_eq!(err, Error::Execution(Chip8Error::FontWrite(0x10)));
+        assert_eq!(
+            err.to_string(),
+            "Program wrote to the protected font region (at 0x010)"
+        );
+
+        let err: Error = LoadError::Empty.into();
+        assert_eq!(err.to_string(), "ROM is empty");
+    }
+}
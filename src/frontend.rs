@@ -1,23 +1,311 @@
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Scancode};
 use sdl2::pixels::Color;
-use sdl2::rect::Rect;
+use sdl2::rect::{Point, Rect};
 use sdl2::render::Canvas;
 use sdl2::video::Window;
+use sdl2::{hint, Sdl, VideoSubsystem};
 
-use crate::backend::{Chip8, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::audio::Beeper;
+use crate::crash;
+use crate::debounce::Debouncer;
+use crate::debugger::Debugger;
+use crate::recorder::GifRecorder;
+use crate::text::draw_text;
+use dorustos::backend::{Chip8, NUM_KEYS, SCREEN_HEIGHT, SCREEN_WIDTH};
+use dorustos::palette::{Palette, Rgb};
+use log::{debug, error, info, warn};
 
 /// A scaling factor for the screen
-const SCALE: u32 = 15;
+pub const SCALE: u32 = 15;
 
 /// Scaled width of the window
-const WINDOW_WIDTH: u32 = (SCREEN_WIDTH as u32) * SCALE;
+pub const WINDOW_WIDTH: u32 = (SCREEN_WIDTH as u32) * SCALE;
 
 /// Scaled height of the window
-const WINDOW_HEIGHT: u32 = (SCREEN_HEIGHT as u32) * SCALE;
+pub const WINDOW_HEIGHT: u32 = (SCREEN_HEIGHT as u32) * SCALE;
+
+/// Title of the emulator's window
+const WINDOW_TITLE: &str = "dorustos Chip-8 Emulator";
+
+/// Shortest time between two updates of the window title with `--title-debug`
+const TITLE_UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Smallest scaling factor the window can be shrunk to
+pub const MIN_SCALE: u32 = 4;
+
+/// Largest scaling factor the window can be opened with
+pub const MAX_SCALE: u32 = 40;
+
+/// Smallest and largest ratio between the width and height of each pixel
+pub const ASPECT_RANGE: (f32, f32) = (0.25, 4.0);
+
+/// Default amount of CPU ticks to run per frame
+pub const TICKS_PER_FRAME: usize = 10;
+
+/// Time between two frames (frames are drawn at 60 Hz)
+pub const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Default longest time a single frame can advance the timers by
+pub const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+/// Color used to highlight pixels where a sprite draw collided
+const COLLISION_COLOR: Color = Color::RGB(255, 0, 0);
+
+/// Color of the pixels that collided the least, with `--collision-heatmap`
+const HEAT_COLD: Rgb = Rgb(0x40, 0x00, 0x80);
+
+/// Color of the pixels that collided the most, with `--collision-heatmap`
+const HEAT_HOT: Rgb = Rgb(0xFF, 0xE0, 0x00);
+
+/// How far the checkerboard drawn with `--grid` shades "off" pixels towards
+/// the foreground color, between 0 and 1
+const GRID_SHADE: f32 = 0.15;
+
+/// Amount of upcoming audio samples shown by `--audio-meter`
+const WAVEFORM_SAMPLES: usize = 512;
+
+/// Size of the waveform drawn with `--audio-meter`, in window pixels
+const WAVEFORM_SIZE: (u32, u32) = (160, 60);
+
+/// Distance between the waveform and the top-right corner of the window
+const WAVEFORM_MARGIN: u32 = 8;
+
+/// Key that pauses and resumes execution in debug mode
+const PAUSE_KEY: Keycode = Keycode::F5;
+
+/// Key that executes a single instruction while paused in debug mode
+const STEP_KEY: Keycode = Keycode::F6;
+
+/// Key that runs until the display changes while paused in debug mode
+const DRAW_STEP_KEY: Keycode = Keycode::F8;
+
+/// Most instructions run by the draw step key, in case the program never
+/// changes the display
+const MAX_DRAW_STEPS: usize = 100_000;
+
+/// Key that restarts the program once the machine is halted
+const RESTART_KEY: Keycode = Keycode::R;
+
+/// Message shown while the machine is halted
+const HALTED_MESSAGE: &str = "HALTED - PRESS R TO RESTART";
+
+/// Color of the halted message
+const HALTED_COLOR: Color = Color::RGB(255, 0, 0);
+
+/// Size of each font pixel of the halted message, in window pixels
+const HALTED_TEXT_SCALE: u32 = 4;
+
+/// Key that toggles slow motion
+const SLOWMO_KEY: Keycode = Keycode::F7;
+
+/// Time the step key must be held down before it starts repeating
+const STEP_REPEAT_DELAY: Duration = Duration::from_millis(400);
+
+/// Delay added between frames in slow motion, when no delay was configured
+const DEFAULT_SLOWMO_DELAY: Duration = Duration::from_millis(200);
+
+/// How drawn frames are shown on the window, trading latency for tearing
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PresentMode {
+    /// Wait for the monitor's vertical sync, which also paces the game:
+    /// no tearing, but up to a frame of latency, and the game runs too fast
+    /// on monitors refreshing faster than 60 Hz
+    #[default]
+    Vsync,
+    /// Show frames as soon as they're drawn, pacing the game with a timer:
+    /// the lowest latency, but tearing may show
+    Immediate,
+    /// Draw frames right away but hold them back until the end of their
+    /// time slot, so that they're shown at a steady rate: less judder than
+    /// immediate, with about a frame of latency, and tearing may still show
+    Paced,
+}
+
+impl PresentMode {
+    /// Returns whether frames are paced by a timer instead of vsync, i.e.
+    /// whether the emulator must wait until the end of each frame itself
+    ///
+    /// # Arguments
+    ///
+    /// * `drawn` - Whether the frame is drawn (see `--frame-skip`)
+    fn paces_manually(self, drawn: bool) -> bool {
+        // Frames that aren't presented aren't paced by vsync either
+        !drawn || self != PresentMode::Vsync
+    }
+}
+
+/// How the keypad learns about the keyboard
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum InputMode {
+    /// Apply each key press and release event as it arrives: every press
+    /// reaches the keypad, even one shorter than a frame, but a key stays
+    /// stuck if its release event is lost (e.g. when the window loses focus)
+    #[default]
+    Event,
+    /// Read the state of the whole keyboard once per frame: keys can't get
+    /// stuck, but presses shorter than a frame are missed
+    Poll,
+}
+
+/// Options that customize how the emulator runs
+pub struct Options {
+    /// Whether the debugging keys (pause / resume and single-stepping) are enabled
+    pub debug: bool,
+    /// Whether the emulator starts paused, before executing the first instruction
+    pub step_on_start: bool,
+    /// Whether every keypad event is logged to the standard error
+    pub log_input: bool,
+    /// Whether the pixels where the most recent sprite draw collided are
+    /// highlighted for one frame
+    pub show_collisions: bool,
+    /// Whether "off" pixels are drawn as a checkerboard, to show the pixel grid
+    pub grid: bool,
+    /// Whether the pixels are colored by how many times they collided over
+    /// the whole session
+    pub collision_heatmap: bool,
+    /// Whether keypad keys are toggled by each press instead of being held
+    pub sticky_keys: bool,
+    /// Amount of CPU ticks to run per frame
+    pub ticks_per_frame: usize,
+    /// Colors used to draw the screen
+    pub palette: Palette,
+    /// Maximum time to wait for an event while paused, in milliseconds
+    /// (0 to keep polling without waiting)
+    pub paused_poll_ms: u32,
+    /// Delay added between frames in slow motion, if it starts enabled
+    pub slowmo: Option<Duration>,
+    /// Time a key release waits for a new press of the same key before it's
+    /// applied, to filter out key chatter (if any)
+    pub debounce: Option<Duration>,
+    /// WAV file to play as the beep instead of a square wave, if any
+    pub beep_wav: Option<PathBuf>,
+    /// Amount of frames each drawn frame stands for: only one out of every
+    /// `frame_skip` frames is drawn
+    pub frame_skip: u64,
+    /// Shortest time a beep plays for, so that very short beeps are audible
+    pub min_beep: Duration,
+    /// Whether the waveform of the beep is drawn in a corner of the window
+    pub audio_meter: bool,
+    /// Whether the window title shows the program counter and the
+    /// instruction about to execute
+    pub title_debug: bool,
+    /// Time between the steps repeated while the step key is held, if they
+    /// are repeated at all
+    pub step_repeat: Option<Duration>,
+    /// Longest time a single frame can advance the timers by, however long
+    /// it actually took
+    pub max_frame_time: Duration,
+    /// How drawn frames are shown on the window, which must match the mode
+    /// the display was opened with
+    pub present_mode: PresentMode,
+    /// How the keypad learns about the keyboard. Polling ignores sticky keys
+    /// and debouncing.
+    pub input_mode: InputMode,
+    /// Second set of keyboard keys mapped to the keypad, if any
+    pub keymap2: Option<Keymap2>,
+}
+
+/// Repeats the step key while it's held down, after an initial delay
+struct StepRepeat {
+    interval: Option<Duration>,
+    next: Option<Instant>,
+}
+
+impl StepRepeat {
+    /// Returns a new step key repeater
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - Time between repeated steps, or `None` to never repeat
+    fn new(interval: Option<Duration>) -> Self {
+        Self {
+            interval,
+            next: None,
+        }
+    }
+
+    /// Registers that the step key was pressed, starting the initial delay
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - Moment the key was pressed
+    fn press(&mut self, now: Instant) {
+        if self.interval.is_some() {
+            self.next = Some(now + STEP_REPEAT_DELAY);
+        }
+    }
+
+    /// Registers that the step key was released, stopping the repeats
+    fn release(&mut self) {
+        self.next = None;
+    }
+
+    /// Returns whether the step key is held down (and will be repeated)
+    fn is_held(&self) -> bool {
+        self.next.is_some()
+    }
+
+    /// Returns how many repeated steps are due, since the last call
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - Current moment
+    fn due(&mut self, now: Instant) -> u32 {
+        let (Some(interval), Some(next)) = (self.interval, self.next.as_mut()) else {
+            return 0;
+        };
+
+        let mut steps = 0;
+        while *next <= now {
+            *next += interval;
+            steps += 1;
+        }
+        steps
+    }
+}
 
-/// Amount of CPU ticks to run per frame
-const TICKS_PER_FRAME: usize = 10;
+/// An open window to draw on, along with the SDL2 context it belongs to
+pub struct Display {
+    /// SDL2 context the window was opened with
+    pub sdl_context: Sdl,
+    /// Canvas to draw on the window
+    pub canvas: Canvas<Window>,
+}
+
+/// Opens the emulator's window. Fails if SDL2 can't open one, e.g. when
+/// there's no display available (over SSH, or in a container).
+///
+/// # Arguments
+///
+/// * `position` - Position of the window's top-left corner on the desktop,
+///   or `None` to center it
+/// * `aspect` - Ratio between the width and height of each pixel (1 for
+///   square pixels), within `ASPECT_RANGE`
+/// * `scale` - Size of each pixel of the screen when the window opens,
+///   between `MIN_SCALE` and `MAX_SCALE`
+/// * `present_mode` - How drawn frames are shown on the window
+pub fn open_display(
+    position: Option<(i32, i32)>,
+    aspect: f32,
+    scale: u32,
+    present_mode: PresentMode,
+) -> Result<Display, String> {
+    let sdl_context = sdl2::init()?;
+    let video_subsystem = sdl_context.video()?;
+    let width = aspect_width(WINDOW_WIDTH, aspect);
+    let window = open_window(&video_subsystem, position, width, scale)?;
+    let canvas = create_canvas(window, width, present_mode)?;
+
+    Ok(Display {
+        sdl_context,
+        canvas,
+    })
+}
 
 /// Runs a Chip-8 emulator instance with a loaded program using SDL2 to draw the screen
 /// and capture the keypresses.
@@ -25,24 +313,71 @@ const TICKS_PER_FRAME: usize = 10;
 /// # Arguments
 ///
 /// * `chip8` - Chip-8 emulator instance
-pub fn run_game(mut chip8: Chip8) {
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem
-        .window("dorustos Chip-8 Emulator", WINDOW_WIDTH, WINDOW_HEIGHT)
-        .position_centered()
-        .opengl()
-        .build()
-        .unwrap();
-
-    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
+/// * `display` - Window to run the emulator in
+/// * `options` - Options to customize how the emulator runs
+/// * `recorder` - Recorder to capture the display into a GIF, if any
+///
+/// Returns the emulator instance as it was when the window was closed, along
+/// with the scale the window was left at (which changes when it's resized).
+pub fn run_game(
+    mut chip8: Chip8,
+    display: Display,
+    options: &Options,
+    mut recorder: Option<GifRecorder>,
+) -> (Chip8, u32) {
+    let Display {
+        sdl_context,
+        mut canvas,
+    } = display;
     canvas.clear();
     canvas.present();
 
     let mut event_pump = sdl_context.event_pump().unwrap();
+    let mut beeper = match Beeper::new(&sdl_context, options.beep_wav.as_deref(), options.min_beep)
+    {
+        Ok(beeper) => Some(beeper),
+        Err(err) => {
+            debug!("Sound disabled: {}", err);
+            None
+        }
+    };
+
+    // Keep a copy of the machine as it was before running, to restart from it
+    let initial = chip8.clone();
+    let mut paused = options.step_on_start;
+    let mut debugger = options.debug.then(Debugger::new);
+    let mut debouncer = options.debounce.map(Debouncer::new);
+    let mut last_frame = Instant::now();
+    let start = last_frame;
+    let mut frame: u64 = 0;
+    let mut render_failed = false;
+    let slowmo_delay = options.slowmo.unwrap_or(DEFAULT_SLOWMO_DELAY);
+    let mut slowmo = options.slowmo.is_some();
+    let mut step_repeat = StepRepeat::new(options.step_repeat);
+    let mut waveform = [0.0; WAVEFORM_SAMPLES];
+    let mut title_updated: Option<Instant> = None;
+    let mut polled = [false; NUM_KEYS];
+    let polling = options.input_mode == InputMode::Poll;
 
     'gameloop: loop {
-        for evt in event_pump.poll_iter() {
+        let mut steps = 0;
+        let mut draw_step = false;
+
+        let now = Instant::now();
+        // After a long frame (e.g. a breakpoint, or the OS stalling the
+        // process), the timers only catch up on part of the lost time
+        let elapsed = (now - last_frame).min(options.max_frame_time);
+        last_frame = now;
+
+        // While paused, sleep until an event arrives (or the timeout passes)
+        // instead of spinning, so that the emulator barely uses the CPU
+        let first_evt = if paused && options.paused_poll_ms > 0 && !step_repeat.is_held() {
+            event_pump.wait_event_timeout(options.paused_poll_ms)
+        } else {
+            None
+        };
+
+        for evt in first_evt.into_iter().chain(event_pump.poll_iter()) {
             match evt {
                 Event::Quit { .. }
                 | Event::KeyDown {
@@ -52,60 +387,798 @@ pub fn run_game(mut chip8: Chip8) {
                     break 'gameloop;
                 }
                 Event::KeyDown {
-                    keycode: Some(key), ..
+                    keycode: Some(PAUSE_KEY),
+                    ..
+                } if options.debug => {
+                    paused = !paused;
+                }
+                // Holding the step key repeats it at our own rate, so the
+                // system's key repeat is ignored
+                Event::KeyDown {
+                    keycode: Some(STEP_KEY),
+                    repeat,
+                    ..
+                } if options.debug && paused && !repeat => {
+                    steps += 1;
+                    step_repeat.press(Instant::now());
+                }
+                Event::KeyUp {
+                    keycode: Some(STEP_KEY),
+                    ..
+                } => {
+                    step_repeat.release();
+                }
+                Event::KeyDown {
+                    keycode: Some(DRAW_STEP_KEY),
+                    ..
+                } if options.debug && paused => {
+                    draw_step = true;
+                }
+                Event::KeyDown {
+                    keycode: Some(SLOWMO_KEY),
+                    ..
                 } => {
-                    if let Some(btn) = key2btn(key) {
-                        chip8.keypress(btn, true);
+                    slowmo = !slowmo;
+                }
+                Event::KeyDown {
+                    keycode: Some(RESTART_KEY),
+                    ..
+                } if chip8.is_halted() => {
+                    chip8 = initial.clone();
+                }
+                // Repeated KeyDown events (sent while a key is held) are ignored,
+                // so only genuine presses reach the keypad
+                Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } if !polling => {
+                    if let Some(btn) = resolve_key(key, options.keymap2.as_ref()) {
+                        // A quick re-press after a release is chatter: the key
+                        // is still pressed as far as the keypad knows
+                        let chatter = debouncer.as_mut().is_some_and(|d| !d.press(btn));
+                        if options.sticky_keys {
+                            let pressed = chip8.toggle_key(btn);
+                            if options.log_input {
+                                log_input(start, frame, btn, pressed);
+                            }
+                        } else if !chatter {
+                            send_key(&mut chip8, options, start, frame, btn, true);
+                        }
                     }
                 }
+                // With sticky keys, releases don't reach the keypad
                 Event::KeyUp {
                     keycode: Some(key), ..
-                } => {
-                    if let Some(btn) = key2btn(key) {
-                        chip8.keypress(btn, false);
+                } if !options.sticky_keys && !polling => {
+                    if let Some(btn) = resolve_key(key, options.keymap2.as_ref()) {
+                        match debouncer.as_mut() {
+                            Some(debouncer) => debouncer.release(btn, Instant::now()),
+                            None => send_key(&mut chip8, options, start, frame, btn, false),
+                        }
                     }
                 }
+                // The canvas' logical size scales the screen to the new size,
+                // so the next frame is drawn to fit
+                Event::Window {
+                    win_event: WindowEvent::Resized(width, height),
+                    ..
+                } => {
+                    debug!("Window resized to {}x{}", width, height);
+                }
+                // The display changed (e.g. a monitor was unplugged), and the
+                // renderer's resources were lost: the next frame redraws them
+                Event::RenderTargetsReset { .. } | Event::RenderDeviceReset { .. } => {
+                    info!("Display changed, redrawing the screen");
+                }
                 _ => (),
             }
         }
 
-        for _ in 0..TICKS_PER_FRAME {
-            chip8.tick();
+        if polling {
+            let keyboard = event_pump.keyboard_state();
+            let held = held_buttons(options.keymap2.as_ref(), |key| {
+                Scancode::from_keycode(key)
+                    .is_some_and(|scancode| keyboard.is_scancode_pressed(scancode))
+            });
+            for (btn, (held, polled)) in held.into_iter().zip(polled.iter_mut()).enumerate() {
+                if held != *polled {
+                    *polled = held;
+                    send_key(&mut chip8, options, start, frame, btn, held);
+                }
+            }
+        }
+
+        if let Some(debouncer) = debouncer.as_mut() {
+            for btn in debouncer.due_releases(Instant::now()) {
+                send_key(&mut chip8, options, start, frame, btn, false);
+            }
+        }
+
+        if let Some(debugger) = debugger.as_mut() {
+            debugger.process_commands(&mut chip8);
+        }
+
+        if paused {
+            steps += step_repeat.due(Instant::now());
+
+            for _ in 0..steps {
+                // Stepping past a display wait moves on to the next frame
+                if chip8.is_waiting_for_vblank() {
+                    chip8.tick_timers();
+                }
+                if let Err(err) = chip8.tick() {
+                    error!("{}", err);
+                    crash::report(&chip8, &err);
+                }
+                if let Some(debugger) = debugger.as_mut() {
+                    debugger.check_watchpoints(&chip8);
+                }
+            }
+
+            if draw_step {
+                match step_until_draw(&mut chip8, MAX_DRAW_STEPS) {
+                    Some(steps) => info!("Display changed after {} instructions", steps),
+                    None => info!("Display didn't change, stopped at {:#05X}", chip8.pc()),
+                }
+            }
+        } else {
+            for _ in 0..options.ticks_per_frame {
+                if let Some(debugger) = debugger.as_mut() {
+                    if debugger.should_break(chip8.pc()) {
+                        paused = true;
+                        break;
+                    }
+                }
+                if let Err(err) = chip8.tick() {
+                    error!("{}", err);
+                    crash::report(&chip8, &err);
+                    break;
+                }
+                if let Some(debugger) = debugger.as_mut() {
+                    if debugger.check_watchpoints(&chip8) {
+                        paused = true;
+                        break;
+                    }
+                }
+                // With the display wait quirk, a sprite draw ends the frame
+                if chip8.is_waiting_for_vblank() {
+                    break;
+                }
+            }
+            // In slow motion, timers advance by one frame per frame, so that
+            // they stay in step with the instructions
+            if slowmo {
+                chip8.tick_timers();
+            } else {
+                chip8.tick_timers_dt(elapsed);
+            }
+        }
+        if let Some(ticks) = chip8.take_beep() {
+            if let Some(beeper) = beeper.as_mut() {
+                beeper.beep(ticks);
+            }
+        }
+        // While paused, every frame is drawn to show each step right away
+        let drawn = paused || frame.is_multiple_of(options.frame_skip);
+        if drawn {
+            let audio_meter = match beeper.as_mut() {
+                Some(beeper) if options.audio_meter => {
+                    beeper.waveform(&mut waveform);
+                    Some(&waveform[..])
+                }
+                _ => None,
+            };
+            match draw_screen(
+                &chip8,
+                &mut canvas,
+                &options.palette,
+                options.show_collisions,
+                options.grid,
+                options.collision_heatmap,
+                audio_meter,
+            ) {
+                Ok(()) => render_failed = false,
+                Err(err) => {
+                    // Only report the first failure, as it's retried every frame
+                    if !render_failed {
+                        warn!("Couldn't draw the screen, retrying: {}", err);
+                    }
+                    render_failed = true;
+                }
+            }
+        }
+        if drawn && options.present_mode != PresentMode::Paced {
+            canvas.present();
+        }
+        // Without vsync, wait until the end of the frame to keep the game
+        // at the right speed
+        if options.present_mode.paces_manually(drawn) {
+            if let Some(remaining) = (now + FRAME_DURATION).checked_duration_since(Instant::now()) {
+                thread::sleep(remaining);
+            }
+        }
+        if drawn && options.present_mode == PresentMode::Paced {
+            canvas.present();
+        }
+        // Titles are updated a few times per second at most, as changing
+        // them every frame makes some window managers struggle
+        if options.title_debug
+            && title_updated.is_none_or(|updated| now - updated >= TITLE_UPDATE_INTERVAL)
+        {
+            let title = debug_title(&chip8);
+            if canvas.window().title() != title {
+                if let Err(err) = canvas.window_mut().set_title(&title) {
+                    debug!("Couldn't set the window title: {}", err);
+                }
+            }
+            title_updated = Some(now);
+        }
+        chip8.clear_last_collisions();
+        if !paused {
+            record_frame(&chip8, &mut recorder);
         }
-        chip8.tick_timers();
-        draw_screen(&chip8, &mut canvas)
+        crash::remember(&chip8);
+        frame += 1;
+
+        if slowmo {
+            thread::sleep(slowmo_delay);
+        }
+    }
+
+    let (logical_width, _) = canvas.logical_size();
+    (chip8, window_scale(canvas.window().size(), logical_width))
+}
+
+/// Opens the emulator's window
+///
+/// # Arguments
+///
+/// * `video_subsystem` - SDL2 video subsystem to open the window with
+/// * `position` - Position of the window's top-left corner on the desktop,
+///   or `None` to center it
+/// * `width` - Width of the window at the default scale, which differs from
+///   `WINDOW_WIDTH` when pixels aren't square
+/// * `scale` - Size of each pixel of the screen when the window opens
+fn open_window(
+    video_subsystem: &VideoSubsystem,
+    position: Option<(i32, i32)>,
+    width: u32,
+    scale: u32,
+) -> Result<Window, String> {
+    let mut builder = video_subsystem.window(
+        WINDOW_TITLE,
+        width * scale / SCALE,
+        SCREEN_HEIGHT as u32 * scale,
+    );
+    match position {
+        Some((x, y)) => builder.position(x, y),
+        None => builder.position_centered(),
+    };
+
+    let mut window = builder
+        .opengl()
+        .resizable()
+        .build()
+        .map_err(|err| err.to_string())?;
+    window
+        .set_minimum_size(width * MIN_SCALE / SCALE, SCREEN_HEIGHT as u32 * MIN_SCALE)
+        .map_err(|err| err.to_string())?;
+    Ok(window)
+}
+
+/// Creates a canvas to draw on the given window. Drawing happens at the
+/// window's original size, and is scaled to fill the window (keeping the
+/// aspect ratio, with nearest-neighbor scaling) whenever it's resized.
+///
+/// # Arguments
+///
+/// * `window` - The window to draw on
+/// * `width` - Width of the window's original size
+/// * `present_mode` - How drawn frames are shown on the window
+fn create_canvas(
+    window: Window,
+    width: u32,
+    present_mode: PresentMode,
+) -> Result<Canvas<Window>, String> {
+    hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
+
+    let mut builder = window.into_canvas();
+    if present_mode == PresentMode::Vsync {
+        builder = builder.present_vsync();
+    }
+    let mut canvas = builder.build().map_err(|err| err.to_string())?;
+    canvas
+        .set_logical_size(width, WINDOW_HEIGHT)
+        .map_err(|err| err.to_string())?;
+    Ok(canvas)
+}
+
+/// Captures the current display into the GIF recorder, if any. Once the
+/// recording is done (or fails), the recorder is dropped to finish the file.
+///
+/// # Arguments
+///
+/// * `chip8` - Chip-8 emulator instance
+/// * `recorder` - Recorder to capture the display into, if any
+pub fn record_frame(chip8: &Chip8, recorder: &mut Option<GifRecorder>) {
+    let Some(gif) = recorder.as_mut() else {
+        return;
+    };
+
+    if let Err(err) = gif.capture(chip8) {
+        error!("Couldn't record GIF frame: {}", err);
+        *recorder = None;
+    } else if !gif.is_recording() {
+        info!("GIF saved to {}", gif.path().display());
+        *recorder = None;
+    }
+}
+
+/// Registers a keypress in the emulator, logging it if requested
+///
+/// # Arguments
+///
+/// * `chip8` - Chip-8 emulator instance
+/// * `options` - Options to customize how the emulator runs
+/// * `start` - Moment at which the emulator started running
+/// * `frame` - Number of the current frame
+/// * `btn` - Index of the key in the keypad
+/// * `pressed` - Whether the key was pressed or released
+fn send_key(
+    chip8: &mut Chip8,
+    options: &Options,
+    start: Instant,
+    frame: u64,
+    btn: usize,
+    pressed: bool,
+) {
+    if options.log_input {
+        log_input(start, frame, btn, pressed);
     }
+    chip8.keypress(btn, pressed);
 }
 
-/// Draws the current screen buffer to the canvas.
+/// Logs a keypad event at the debug level, along with the time and frame at
+/// which it happened.
+///
+/// # Arguments
+///
+/// * `start` - Moment at which the emulator started running
+/// * `frame` - Number of the current frame
+/// * `btn` - Index of the key in the keypad
+/// * `pressed` - Whether the key was pressed or released
+fn log_input(start: Instant, frame: u64, btn: usize, pressed: bool) {
+    let state = if pressed { "pressed" } else { "released" };
+    debug!(
+        "[{:.3}s] frame {}: key {:X} {}",
+        start.elapsed().as_secs_f64(),
+        frame,
+        btn,
+        state
+    );
+}
+
+/// Draws the current screen buffer to the canvas, with a message on top if
+/// the machine is halted, without presenting it. Fails if the renderer can't
+/// draw, e.g. while the display is changing.
 ///
 /// # Arguments
 ///
 /// * `chip8` - Chip-8 emulator instance
 /// * `canvas` - SDL2 canvas to draw to
-fn draw_screen(chip8: &Chip8, canvas: &mut Canvas<Window>) {
+/// * `palette` - Colors to draw the screen with
+/// * `show_collisions` - Whether to highlight the pixels where the most recent
+///   sprite draw collided
+/// * `grid` - Whether to draw "off" pixels as a checkerboard
+/// * `heatmap` - Whether to color the pixels that collided by how often they did
+/// * `waveform` - Upcoming audio samples to draw in a corner, if any
+fn draw_screen(
+    chip8: &Chip8,
+    canvas: &mut Canvas<Window>,
+    palette: &Palette,
+    show_collisions: bool,
+    grid: bool,
+    heatmap: bool,
+    waveform: Option<&[f32]>,
+) -> Result<(), String> {
     // Clear canvas
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.set_draw_color(to_color(palette.background));
     canvas.clear();
 
     let screen_buf = chip8.get_display();
+    let (width, height) = chip8.display_dimensions();
+    let size = (width, height);
+    // Pixels are stretched horizontally to fill the canvas' logical width,
+    // which isn't `WINDOW_WIDTH` when they aren't square
+    let canvas_size = canvas.logical_size();
+    let (canvas_width, _) = canvas_size;
+
+    if grid {
+        // Shade every other "off" pixel, slightly towards the foreground
+        canvas.set_draw_color(to_color(
+            palette.background.mix(palette.foreground, GRID_SHADE),
+        ));
+
+        for (i, pixel) in screen_buf.iter().enumerate() {
+            let x = i % width;
+            let y = i / width;
+
+            if !*pixel && (x + y) % 2 == 1 {
+                canvas.fill_rect(pixel_rect(i, size, canvas_size))?;
+            }
+        }
+    }
 
-    // Set draw color to white, iterate and check if each point should be drawn
-    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    // Set draw color to the foreground, iterate and check if each point should be drawn
+    canvas.set_draw_color(to_color(palette.foreground));
 
     for (i, pixel) in screen_buf.iter().enumerate() {
         if *pixel {
-            // Convert from 1D array to 2D coordinates
-            let x = (i % SCREEN_WIDTH) as u32;
-            let y = (i / SCREEN_WIDTH) as u32;
+            canvas.fill_rect(pixel_rect(i, size, canvas_size))?;
+        }
+    }
+
+    if heatmap {
+        let counts = chip8.collision_counts();
+        let max = counts.iter().copied().max().unwrap_or(0);
 
-            // Draw a rectangle at the coordinates scaled up by SCALE value
-            let rect = Rect::new((x * SCALE) as i32, (y * SCALE) as i32, SCALE, SCALE);
-            canvas.fill_rect(rect).unwrap();
+        for (i, count) in counts.iter().enumerate() {
+            if let Some(rgb) = heat_color(*count, max) {
+                canvas.set_draw_color(to_color(rgb));
+                canvas.fill_rect(pixel_rect(i, size, canvas_size))?;
+            }
         }
     }
 
-    canvas.present();
+    if show_collisions {
+        canvas.set_draw_color(COLLISION_COLOR);
+
+        for i in chip8.last_collisions() {
+            canvas.fill_rect(pixel_rect(*i, size, canvas_size))?;
+        }
+    }
+
+    if chip8.is_halted() {
+        canvas.set_draw_color(HALTED_COLOR);
+        let scale = HALTED_TEXT_SCALE;
+        draw_text(canvas, HALTED_MESSAGE, scale as i32, scale as i32, scale)?;
+    }
+
+    if let Some(samples) = waveform {
+        let (wave_width, wave_height) = WAVEFORM_SIZE;
+        let area = Rect::new(
+            (canvas_width - wave_width - WAVEFORM_MARGIN) as i32,
+            WAVEFORM_MARGIN as i32,
+            wave_width,
+            wave_height,
+        );
+
+        canvas.set_draw_color(to_color(palette.background));
+        canvas.fill_rect(area)?;
+        canvas.set_draw_color(to_color(palette.foreground));
+        canvas.draw_rect(area)?;
+        canvas.draw_lines(waveform_points(samples, area).as_slice())?;
+    }
+
+    Ok(())
+}
+
+/// Returns the largest scale (size of each pixel of the screen) whose
+/// window fits in a window of the given size, between `MIN_SCALE` and
+/// `MAX_SCALE`
+///
+/// # Arguments
+///
+/// * `window_size` - Width and height of the window
+/// * `width` - Width of the window at the default scale, which differs from
+///   `WINDOW_WIDTH` when pixels aren't square
+fn window_scale(window_size: (u32, u32), width: u32) -> u32 {
+    let (window_width, window_height) = window_size;
+    let scale = (window_width * SCALE / width).min(window_height / SCREEN_HEIGHT as u32);
+    scale.clamp(MIN_SCALE, MAX_SCALE)
+}
+
+/// Returns the width of the window for pixels with the given aspect ratio,
+/// stretching (or shrinking) a window meant for square pixels horizontally
+///
+/// # Arguments
+///
+/// * `square_width` - Width of the window with square pixels
+/// * `aspect` - Ratio between the width and height of each pixel
+fn aspect_width(square_width: u32, aspect: f32) -> u32 {
+    (square_width as f32 * aspect).round() as u32
+}
+
+/// Returns the area of the canvas a pixel of the screen buffer covers. When
+/// the canvas' size isn't a multiple of the screen's, pixels differ by at
+/// most one column in width (or one row in height), so that they still tile
+/// the canvas exactly.
+///
+/// # Arguments
+///
+/// * `idx` - Index of the pixel in the screen buffer
+/// * `size` - Width and height of the screen buffer in pixels
+/// * `canvas_size` - Width and height of the canvas' logical size
+fn pixel_rect(idx: usize, size: (usize, usize), canvas_size: (u32, u32)) -> Rect {
+    let (width, height) = size;
+    let (canvas_width, canvas_height) = canvas_size;
+    let column = |x: usize| (x as u32 * canvas_width / width as u32) as i32;
+    let row = |y: usize| (y as u32 * canvas_height / height as u32) as i32;
+    let (x, y) = (idx % width, idx / width);
+
+    let (left, top) = (column(x), row(y));
+    let (right, bottom) = (column(x + 1), row(y + 1));
+    Rect::new(left, top, (right - left) as u32, (bottom - top) as u32)
+}
+
+/// Returns the window title showing the program counter and the instruction
+/// about to execute (e.g. `dorustos Chip-8 Emulator - 0x200: 6001 LD V0, 0x01`)
+///
+/// # Arguments
+///
+/// * `chip8` - Chip-8 emulator instance
+fn debug_title(chip8: &Chip8) -> String {
+    let pc = chip8.pc();
+    let decoded = chip8.decode_at(pc);
+    let mnemonic = decoded
+        .mnemonic
+        .as_deref()
+        .unwrap_or("(unsupported opcode)");
+    format!(
+        "{} - {:#05X}: {:04X} {}",
+        WINDOW_TITLE, pc, decoded.op, mnemonic
+    )
+}
+
+/// Executes instructions until one of them changes the display (e.g. a
+/// sprite draw or a screen clear), returning how many were executed.
+/// Returns `None` if the machine halts or `limit` instructions run first.
+///
+/// # Arguments
+///
+/// * `chip8` - Chip-8 emulator instance
+/// * `limit` - Most instructions to execute
+fn step_until_draw(chip8: &mut Chip8, limit: usize) -> Option<usize> {
+    let toggled = chip8.pixels_toggled();
+
+    for steps in 1..=limit {
+        // Stepping past a display wait moves on to the next frame
+        if chip8.is_waiting_for_vblank() {
+            chip8.tick_timers();
+        }
+        if let Err(err) = chip8.tick() {
+            error!("{}", err);
+            crash::report(chip8, &err);
+        }
+        if chip8.pixels_toggled() != toggled {
+            return Some(steps);
+        }
+        if chip8.is_halted() {
+            return None;
+        }
+    }
+
+    None
+}
+
+/// Returns the points of a line plotting audio samples (between -1 and 1)
+/// across an area of the window, one point per column
+///
+/// # Arguments
+///
+/// * `samples` - Audio samples to plot
+/// * `area` - Area of the window to plot them in
+fn waveform_points(samples: &[f32], area: Rect) -> Vec<Point> {
+    let center = area.center().y();
+    let amplitude = (area.height() / 2) as f32;
+
+    (0..area.width() as usize)
+        .map(|column| {
+            let sample = samples[column * samples.len() / area.width() as usize];
+            let y = center - (sample.clamp(-1.0, 1.0) * amplitude) as i32;
+            Point::new(area.x() + column as i32, y)
+        })
+        .collect()
+}
+
+/// Returns the color of a pixel in the collision heatmap, from cold to hot
+/// relative to the pixel that collided the most, or `None` if the pixel
+/// never collided
+///
+/// # Arguments
+///
+/// * `count` - How many times the pixel collided
+/// * `max` - How many times the pixel that collided the most did
+fn heat_color(count: u32, max: u32) -> Option<Rgb> {
+    if count == 0 {
+        return None;
+    }
+
+    Some(HEAT_COLD.mix(HEAT_HOT, count as f32 / max as f32))
+}
+
+/// Converts a palette color into a SDL2 color
+///
+/// # Arguments
+///
+/// * `rgb` - Color to convert
+fn to_color(rgb: Rgb) -> Color {
+    Color::RGB(rgb.0, rgb.1, rgb.2)
+}
+
+/// Buttons of the Chip-8 keypad, as they're laid out on the COSMAC VIP
+const KEYPAD_LAYOUT: [[usize; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// Keyboard keys mapped to a Chip-8 button by `key2btn`
+const MAPPED_KEYS: [Keycode; 16] = [
+    Keycode::Num1,
+    Keycode::Num2,
+    Keycode::Num3,
+    Keycode::Num4,
+    Keycode::Q,
+    Keycode::W,
+    Keycode::E,
+    Keycode::R,
+    Keycode::A,
+    Keycode::S,
+    Keycode::D,
+    Keycode::F,
+    Keycode::Z,
+    Keycode::X,
+    Keycode::C,
+    Keycode::V,
+];
+
+/// Returns the keymap as a table laid out like the keypad: each button,
+/// followed by the keyboard key mapped to it. The secondary keymap, if any,
+/// follows in a second table.
+///
+/// # Arguments
+///
+/// * `keymap2` - Secondary keymap, if any
+pub fn format_keymap(keymap2: Option<&Keymap2>) -> String {
+    let primary = format_layout(btn2key);
+    match keymap2 {
+        Some(keymap) => format!(
+            "{}\n\nSecond keymap:\n{}",
+            primary,
+            format_layout(|btn| keymap.keys.get(btn).copied())
+        ),
+        None => primary,
+    }
+}
+
+/// Returns a table laid out like the keypad, with each button followed by
+/// the keyboard key mapped to it
+///
+/// # Arguments
+///
+/// * `btn2key` - Returns the key mapped to a button, if any
+fn format_layout(btn2key: impl Fn(usize) -> Option<Keycode>) -> String {
+    let rows: Vec<String> = KEYPAD_LAYOUT
+        .iter()
+        .map(|row| {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|btn| {
+                    let key = btn2key(*btn).map_or("-".to_string(), |key| key.name());
+                    format!("{:X}: {}", btn, key)
+                })
+                .collect();
+            cells.join("  ")
+        })
+        .collect();
+
+    rows.join("\n")
+}
+
+/// Maps a Chip-8 button to the keyboard key mapped to it by `key2btn`
+///
+/// # Arguments
+///
+/// * `btn` - Index of the button in the keypad
+fn btn2key(btn: usize) -> Option<Keycode> {
+    MAPPED_KEYS
+        .iter()
+        .copied()
+        .find(|key| key2btn(*key) == Some(btn))
+}
+
+/// Keys of the secondary keymap selected with `--keymap2 right`, in the
+/// order of `KEYPAD_LAYOUT`: the right side of a QWERTY keyboard
+const RIGHT_KEYMAP: &str = "7890uiopjkl;m,./";
+
+/// A second set of keyboard keys mapped to the keypad, so that two players
+/// can share a keyboard. Both sets press the same buttons.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keymap2 {
+    /// Keyboard key mapped to each button of the keypad
+    keys: [Keycode; NUM_KEYS],
+}
+
+impl Keymap2 {
+    /// Parses a secondary keymap: either `right` for the right side of a
+    /// QWERTY keyboard, or 16 keys given in the order of the keypad's layout
+    /// (the rows `123C`, `456D`, `789E` and `A0BF`). Fails if a key is given
+    /// twice, or is already mapped by the primary keymap.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The keymap to parse
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = if text == "right" { RIGHT_KEYMAP } else { text };
+        let chars: Vec<char> = text.chars().collect();
+        if chars.len() != NUM_KEYS {
+            return Err(format!(
+                "Invalid keymap: expected {} keys, got {}",
+                NUM_KEYS,
+                chars.len()
+            ));
+        }
+
+        let mut keys = [Keycode::Escape; NUM_KEYS];
+        let buttons = KEYPAD_LAYOUT.iter().flatten();
+        for (c, btn) in chars.iter().zip(buttons) {
+            let key = Some(*c)
+                .filter(char::is_ascii_graphic)
+                .and_then(|c| Keycode::from_i32(c.to_ascii_lowercase() as i32))
+                .ok_or(format!("Invalid key in keymap: {}", c))?;
+            if key2btn(key).is_some() {
+                return Err(format!("Key {} is already mapped by the primary keymap", c));
+            }
+            // Compare the keys rather than the characters, since lowercase
+            // and uppercase letters are the same key
+            if keys.contains(&key) {
+                return Err(format!("Key {} is mapped twice", c));
+            }
+            keys[*btn] = key;
+        }
+
+        Ok(Keymap2 { keys })
+    }
+
+    /// Returns the button a keyboard key is mapped to, if any
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - SDL2 keycode to map
+    fn button(&self, key: Keycode) -> Option<usize> {
+        self.keys.iter().position(|mapped| *mapped == key)
+    }
+}
+
+/// Maps a SDL2 keycode to the Chip-8 button it's mapped to by either keymap
+///
+/// # Arguments
+///
+/// * `key` - SDL2 keycode to map
+/// * `keymap2` - Secondary keymap, if any
+fn resolve_key(key: Keycode, keymap2: Option<&Keymap2>) -> Option<usize> {
+    key2btn(key).or_else(|| keymap2.and_then(|keymap| keymap.button(key)))
+}
+
+/// Returns which Chip-8 buttons are held, given which keyboard keys are. A
+/// button is held while a key of either keymap is.
+///
+/// # Arguments
+///
+/// * `keymap2` - Secondary keymap, if any
+/// * `is_held` - Returns whether a keyboard key is held down
+fn held_buttons(keymap2: Option<&Keymap2>, is_held: impl Fn(Keycode) -> bool) -> [bool; NUM_KEYS] {
+    let secondary = keymap2.map_or(&[][..], |keymap| &keymap.keys[..]);
+    let mut held = [false; NUM_KEYS];
+    for key in MAPPED_KEYS.iter().chain(secondary) {
+        if let Some(btn) = resolve_key(*key, keymap2) {
+            held[btn] = held[btn] || is_held(*key);
+        }
+    }
+    held
 }
 
 /// Maps a SDL2 keycode to the respective Chip-8 button.
@@ -139,7 +1212,45 @@ fn key2btn(key: Keycode) -> Option<usize> {
 
 #[cfg(test)]
 mod tests {
+    use std::time::{Duration, Instant};
+
     use sdl2::keyboard::Keycode;
+    use sdl2::rect::{Point, Rect};
+
+    use dorustos::backend::{Chip8, HIRES_HEIGHT, HIRES_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    use super::{
+        aspect_width, heat_color, held_buttons, pixel_rect, resolve_key, step_until_draw,
+        waveform_points, window_scale, Keymap2, PresentMode, StepRepeat, HEAT_COLD, HEAT_HOT,
+        MAX_SCALE, MIN_SCALE, SCALE, STEP_REPEAT_DELAY, WINDOW_HEIGHT, WINDOW_WIDTH,
+    };
+
+    #[test]
+    fn step_repeat_starts_after_delay() {
+        let mut repeat = StepRepeat::new(Some(Duration::from_millis(50)));
+        let start = Instant::now();
+
+        repeat.press(start);
+        assert_eq!(repeat.due(start + STEP_REPEAT_DELAY / 2), 0);
+        assert_eq!(repeat.due(start + STEP_REPEAT_DELAY), 1);
+        assert_eq!(
+            repeat.due(start + STEP_REPEAT_DELAY + Duration::from_millis(120)),
+            2
+        );
+
+        repeat.release();
+        assert_eq!(repeat.due(start + STEP_REPEAT_DELAY * 10), 0);
+    }
+
+    #[test]
+    fn step_repeat_can_be_disabled() {
+        let mut repeat = StepRepeat::new(None);
+        let start = Instant::now();
+
+        repeat.press(start);
+        assert!(!repeat.is_held());
+        assert_eq!(repeat.due(start + STEP_REPEAT_DELAY * 10), 0);
+    }
 
     #[test]
     fn key2btn_maps_appropriately() {
@@ -155,4 +1266,219 @@ mod tests {
         let result_4 = super::key2btn(Keycode::K);
         assert_eq!(result_4, None);
     }
+
+    #[test]
+    fn held_buttons_follow_keyboard() {
+        let held = held_buttons(None, |key| key == Keycode::Q || key == Keycode::V);
+        let buttons: Vec<usize> = (0..held.len()).filter(|btn| held[*btn]).collect();
+        assert_eq!(buttons, vec![0x4, 0xF]);
+
+        assert_eq!(held_buttons(None, |_| false), [false; 16]);
+        assert_eq!(held_buttons(None, |_| true), [true; 16]);
+
+        // Keys of the secondary keymap hold buttons too
+        let keymap2 = Keymap2::parse("right").unwrap();
+        let held = held_buttons(Some(&keymap2), |key| key == Keycode::Slash);
+        assert!(held[0xF]);
+        assert_eq!(held_buttons(None, |key| key == Keycode::Slash), [false; 16]);
+    }
+
+    #[test]
+    fn resolve_key_reads_both_keymaps() {
+        let keymap2 = Keymap2::parse("right").unwrap();
+
+        // Primary keys still work alongside the secondary keymap
+        assert_eq!(resolve_key(Keycode::Num1, Some(&keymap2)), Some(0x1));
+        assert_eq!(resolve_key(Keycode::V, Some(&keymap2)), Some(0xF));
+        // The secondary keymap follows the keypad's layout
+        assert_eq!(resolve_key(Keycode::Num7, Some(&keymap2)), Some(0x1));
+        assert_eq!(resolve_key(Keycode::P, Some(&keymap2)), Some(0xD));
+        assert_eq!(resolve_key(Keycode::Semicolon, Some(&keymap2)), Some(0xE));
+        assert_eq!(resolve_key(Keycode::Comma, Some(&keymap2)), Some(0x0));
+        assert_eq!(resolve_key(Keycode::Num7, None), None);
+        assert_eq!(resolve_key(Keycode::Space, Some(&keymap2)), None);
+
+        let custom = Keymap2::parse("7890UIOPJKLHNM,.").unwrap();
+        assert_eq!(resolve_key(Keycode::H, Some(&custom)), Some(0xE));
+    }
+
+    #[test]
+    fn keymap2_rejects_overlapping_keys() {
+        assert!(Keymap2::parse("7890uiopjkl;m,.").is_err());
+        assert_eq!(
+            Keymap2::parse("7890uiopjkl;m,.q"),
+            Err("Key q is already mapped by the primary keymap".to_string())
+        );
+        assert_eq!(
+            Keymap2::parse("7890uiopjkl;m,.7"),
+            Err("Key 7 is mapped twice".to_string())
+        );
+        assert_eq!(
+            Keymap2::parse("7890uiopjkl;mU./"),
+            Err("Key U is mapped twice".to_string())
+        );
+        assert!(Keymap2::parse("7890uiopjkl;m,. ").is_err());
+    }
+
+    #[test]
+    fn btn2key_reverses_key2btn() {
+        for btn in 0..16 {
+            let key = super::btn2key(btn).unwrap();
+            assert_eq!(super::key2btn(key), Some(btn));
+        }
+        assert_eq!(super::btn2key(16), None);
+    }
+
+    #[test]
+    fn format_keymap_follows_keypad_layout() {
+        let keymap = super::format_keymap(None);
+        let rows: Vec<&str> = keymap.lines().collect();
+        assert_eq!(
+            rows,
+            vec![
+                "1: 1  2: 2  3: 3  C: 4",
+                "4: Q  5: W  6: E  D: R",
+                "7: A  8: S  9: D  E: F",
+                "A: Z  0: X  B: C  F: V",
+            ]
+        );
+
+        let keymap2 = Keymap2::parse("right").unwrap();
+        let keymap = super::format_keymap(Some(&keymap2));
+        let rows: Vec<&str> = keymap.lines().skip(4).collect();
+        assert_eq!(
+            rows,
+            vec![
+                "",
+                "Second keymap:",
+                "1: 7  2: 8  3: 9  C: 0",
+                "4: U  5: I  6: O  D: P",
+                "7: J  8: K  9: L  E: ;",
+                "A: M  0: ,  B: .  F: /",
+            ]
+        );
+    }
+
+    #[test]
+    fn debug_title_shows_next_instruction() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x60, 0x01, 0x00, 0xE0]).unwrap();
+        assert_eq!(
+            super::debug_title(&chip8),
+            "dorustos Chip-8 Emulator - 0x200: 6001 LD V0, 0x01"
+        );
+
+        chip8.tick().unwrap();
+        assert_eq!(
+            super::debug_title(&chip8),
+            "dorustos Chip-8 Emulator - 0x202: 00E0 CLS"
+        );
+    }
+
+    #[test]
+    fn heat_color_scales_with_count() {
+        assert_eq!(heat_color(0, 10), None);
+        assert_eq!(heat_color(10, 10), Some(HEAT_HOT));
+        assert_eq!(heat_color(1, 1_000_000), Some(HEAT_COLD));
+        assert_eq!(heat_color(5, 10), Some(HEAT_COLD.mix(HEAT_HOT, 0.5)));
+    }
+
+    #[test]
+    fn window_scale_fits_resized_window() {
+        let height = SCREEN_HEIGHT as u32;
+        assert_eq!(
+            window_scale((WINDOW_WIDTH, WINDOW_HEIGHT), WINDOW_WIDTH),
+            SCALE
+        );
+        assert_eq!(window_scale((64 * 8, height * 8), WINDOW_WIDTH), 8);
+        // Stretching the window one way only leaves bars on the other sides
+        assert_eq!(window_scale((64 * 20, height * 9), WINDOW_WIDTH), 9);
+
+        // Non-square pixels make the window wider at the same scale
+        let wide = aspect_width(WINDOW_WIDTH, 2.0);
+        assert_eq!(window_scale((128 * 10, height * 10), wide), 10);
+
+        assert_eq!(window_scale((10, 10), WINDOW_WIDTH), MIN_SCALE);
+        assert_eq!(
+            window_scale((64 * 100, height * 100), WINDOW_WIDTH),
+            MAX_SCALE
+        );
+    }
+
+    #[test]
+    fn pixel_rect_tiles_stretched_canvas() {
+        const LORES: (usize, usize) = (SCREEN_WIDTH, SCREEN_HEIGHT);
+        assert_eq!(aspect_width(WINDOW_WIDTH, 1.0), WINDOW_WIDTH);
+        assert_eq!(
+            pixel_rect(SCREEN_WIDTH + 2, LORES, (WINDOW_WIDTH, WINDOW_HEIGHT)),
+            Rect::new(30, 15, 15, 15)
+        );
+
+        // Hires pixels are half as big, rounded so that they tile the canvas
+        assert_eq!(
+            pixel_rect(
+                HIRES_WIDTH + 2,
+                (HIRES_WIDTH, HIRES_HEIGHT),
+                (WINDOW_WIDTH, WINDOW_HEIGHT)
+            ),
+            Rect::new(15, 7, 7, 8)
+        );
+
+        // 4:3 pixels don't fit a whole amount of columns each
+        let canvas_width = aspect_width(WINDOW_WIDTH, 4.0 / 3.0);
+        assert_eq!(canvas_width, 1280);
+        let mut left = 0;
+        for x in 0..SCREEN_WIDTH {
+            let rect = pixel_rect(x, LORES, (canvas_width, WINDOW_HEIGHT));
+            assert_eq!(rect.x(), left);
+            assert_eq!(rect.width(), 20);
+            left = rect.right();
+        }
+        assert_eq!(left, canvas_width as i32);
+
+        let canvas_width = aspect_width(WINDOW_WIDTH, 0.7);
+        let widths: Vec<u32> = (0..SCREEN_WIDTH)
+            .map(|x| pixel_rect(x, LORES, (canvas_width, WINDOW_HEIGHT)).width())
+            .collect();
+        assert!(widths.iter().all(|width| *width == 10 || *width == 11));
+        assert_eq!(widths.iter().sum::<u32>(), canvas_width);
+    }
+
+    #[test]
+    fn step_until_draw_stops_at_display_change() {
+        let mut chip8 = Chip8::new();
+        chip8
+            .load(&[0x60, 0x05, 0x70, 0x01, 0xD0, 0x05, 0x12, 0x06])
+            .unwrap();
+        assert_eq!(step_until_draw(&mut chip8, 100), Some(3));
+        assert_eq!(chip8.pc(), 0x206);
+
+        // The program now loops forever without drawing
+        assert_eq!(step_until_draw(&mut chip8, 100), None);
+    }
+
+    #[test]
+    fn waveform_points_span_area() {
+        let area = Rect::new(10, 20, 4, 10);
+        let points = waveform_points(&[0.0, 1.0, -1.0, 0.5], area);
+
+        let expected = [(10, 25), (11, 20), (12, 30), (13, 23)];
+        let expected: Vec<_> = expected.iter().map(|&p| Point::from(p)).collect();
+        assert_eq!(points, expected);
+
+        // Longer buffers are sampled evenly across the width
+        let points = waveform_points(&[1.0, 0.0, -1.0, 0.0], Rect::new(0, 0, 2, 10));
+        assert_eq!(points, vec![Point::new(0, 0), Point::new(1, 10)]);
+    }
+
+    #[test]
+    fn only_vsync_paces_drawn_frames() {
+        assert!(!PresentMode::Vsync.paces_manually(true));
+        assert!(PresentMode::Immediate.paces_manually(true));
+        assert!(PresentMode::Paced.paces_manually(true));
+
+        // Skipped frames aren't presented, so they're always paced manually
+        assert!(PresentMode::Vsync.paces_manually(false));
+        assert!(PresentMode::Immediate.paces_manually(false));
+    }
 }
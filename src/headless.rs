@@ -0,0 +1,269 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::frontend::{record_frame, FRAME_DURATION};
+use crate::recorder::GifRecorder;
+use dorustos::backend::{Chip8, Chip8Error, Chip8Snapshot};
+
+/// Options that customize how the emulator runs in headless mode
+pub struct Options {
+    /// Amount of frames to run for
+    pub frames: u64,
+    /// Maximum amount of CPU ticks to run for, if any
+    pub max_cycles: Option<u64>,
+    /// Whether to run at the real game speed (60 frames per second)
+    /// instead of as fast as possible
+    pub realtime: bool,
+    /// Amount of CPU ticks to run per frame
+    pub ticks_per_frame: usize,
+}
+
+/// Statistics gathered while running the emulator in headless mode
+pub struct Report {
+    /// Amount of frames run
+    pub frames: u64,
+    /// Amount of CPU ticks run
+    pub cycles: u64,
+    /// Wall-clock time taken by the run
+    pub elapsed: Duration,
+    /// Amount of pixels toggled on or off during the run
+    pub pixels_toggled: u64,
+    /// Error that stopped the run early, if any
+    pub error: Option<Chip8Error>,
+}
+
+impl Report {
+    /// Returns the average amount of pixels toggled per frame. A high score
+    /// means the ROM flickers a lot.
+    pub fn flicker_score(&self) -> f64 {
+        if self.frames == 0 {
+            return 0.0;
+        }
+        self.pixels_toggled as f64 / self.frames as f64
+    }
+
+    /// Prints the report to the standard output
+    pub fn print(&self) {
+        let secs = self.elapsed.as_secs_f64();
+
+        println!("Frames:          {}", self.frames);
+        println!("Cycles:          {}", self.cycles);
+        println!("Elapsed:         {:.3}s", secs);
+        println!("Frames / second: {:.1}", self.frames as f64 / secs);
+        println!("Cycles / second: {:.1}", self.cycles as f64 / secs);
+        println!("Flicker score:   {:.2}", self.flicker_score());
+    }
+}
+
+/// Formats the registers, I, PC, SP and timers of a machine as a single line
+/// of space-separated `NAME=0xVALUE` pairs, in a stable order (e.g.
+/// `V0=0x00 ... VF=0x01 I=0x2A0 PC=0x21C SP=0x00 DT=0x00 ST=0x00`), so that
+/// it can be compared against the state of a reference interpreter.
+///
+/// # Arguments
+///
+/// * `snapshot` - State of the machine to format
+pub fn format_registers(snapshot: &Chip8Snapshot) -> String {
+    let mut fields: Vec<String> = snapshot
+        .v_reg
+        .iter()
+        .enumerate()
+        .map(|(idx, value)| format!("V{:X}={:#04X}", idx, value))
+        .collect();
+
+    fields.push(format!("I={:#05X}", snapshot.i_reg));
+    fields.push(format!("PC={:#05X}", snapshot.pc));
+    fields.push(format!("SP={:#04X}", snapshot.sp));
+    fields.push(format!("DT={:#04X}", snapshot.dt));
+    fields.push(format!("ST={:#04X}", snapshot.st));
+
+    fields.join(" ")
+}
+
+/// Runs a Chip-8 emulator instance with a loaded program as fast as possible,
+/// without drawing the screen or reading any input, and returns statistics
+/// about the run.
+///
+/// The run stops after the given amount of frames, or as soon as the maximum
+/// amount of cycles is reached, whichever comes first. The run also stops
+/// early when the machine halts, and an error executing an instruction is
+/// included in the report. When running in real time, each frame lasts
+/// 1/60th of a second, so a run capped by cycles lasts as long as the frames
+/// needed to run those cycles.
+///
+/// # Arguments
+///
+/// * `chip8` - Chip-8 emulator instance
+/// * `options` - Options to customize how the emulator runs
+/// * `recorder` - Recorder to capture the display into a GIF, if any
+pub fn run_headless(
+    chip8: &mut Chip8,
+    options: &Options,
+    mut recorder: Option<GifRecorder>,
+) -> Report {
+    let start = Instant::now();
+    let toggled_before = chip8.pixels_toggled();
+    let mut cycles = 0;
+    let mut frames = 0;
+    let mut error = None;
+
+    'frames: while frames < options.frames {
+        for _ in 0..options.ticks_per_frame {
+            if options.max_cycles.is_some_and(|max| cycles >= max) || chip8.is_halted() {
+                break 'frames;
+            }
+            if let Err(err) = chip8.tick() {
+                error = Some(err);
+                break 'frames;
+            }
+            cycles += 1;
+
+            // With the display wait quirk, a sprite draw ends the frame
+            if chip8.is_waiting_for_vblank() {
+                break;
+            }
+        }
+        chip8.tick_timers();
+        record_frame(chip8, &mut recorder);
+        frames += 1;
+
+        if options.realtime {
+            // Sleep until the frame's scheduled end, so that delays don't accumulate
+            let frame_end = start + FRAME_DURATION * frames as u32;
+            if let Some(remaining) = frame_end.checked_duration_since(Instant::now()) {
+                thread::sleep(remaining);
+            }
+        }
+    }
+
+    Report {
+        frames,
+        cycles,
+        elapsed: start.elapsed(),
+        pixels_toggled: chip8.pixels_toggled() - toggled_before,
+        error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Options;
+    use crate::frontend::TICKS_PER_FRAME;
+    use dorustos::backend::{Chip8, Chip8Error, Quirks};
+
+    #[test]
+    fn run_headless_ends_frames_on_display_wait() {
+        // Draws the "0" font sprite, forever
+        let quirks = Quirks {
+            display_wait: true,
+            ..Default::default()
+        };
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.load(&[0xD0, 0x05, 0x12, 0x00]).unwrap();
+
+        let options = Options {
+            frames: 3,
+            max_cycles: None,
+            realtime: false,
+            ticks_per_frame: TICKS_PER_FRAME,
+        };
+        let report = super::run_headless(&mut chip8, &options, None);
+
+        // The first frame runs the draw, and the others the jump and the draw
+        assert_eq!(report.frames, 3);
+        assert_eq!(report.cycles, 5);
+    }
+
+    #[test]
+    fn run_headless_reports_flicker_score() {
+        // Draws the "0" font sprite (14 lit pixels), then clears the screen, forever
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0xD0, 0x05, 0x00, 0xE0, 0x12, 0x00]).unwrap();
+
+        let options = Options {
+            frames: 3,
+            max_cycles: None,
+            realtime: false,
+            ticks_per_frame: TICKS_PER_FRAME,
+        };
+        let report = super::run_headless(&mut chip8, &options, None);
+
+        // Three frames run 30 instructions: 10 draws and 10 clears
+        assert_eq!(report.frames, 3);
+        assert_eq!(report.cycles, 30);
+        assert_eq!(report.pixels_toggled, 10 * 14 * 2);
+        assert_eq!(report.flicker_score(), 280.0 / 3.0);
+    }
+
+    #[test]
+    fn run_headless_stops_at_max_cycles() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x12, 0x00]).unwrap();
+
+        let options = Options {
+            frames: 10,
+            max_cycles: Some(25),
+            realtime: false,
+            ticks_per_frame: TICKS_PER_FRAME,
+        };
+        let report = super::run_headless(&mut chip8, &options, None);
+
+        assert_eq!(report.cycles, 25);
+        assert_eq!(report.frames, 2);
+    }
+
+    #[test]
+    fn format_registers_lists_every_register() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x61, 0x1F, 0xA2, 0xA0, 0xFF, 0x15]).unwrap();
+        for _ in 0..3 {
+            chip8.tick().unwrap();
+        }
+
+        let regs = super::format_registers(&chip8.snapshot());
+        assert_eq!(
+            regs,
+            "V0=0x00 V1=0x1F V2=0x00 V3=0x00 V4=0x00 V5=0x00 V6=0x00 V7=0x00 \
+             V8=0x00 V9=0x00 VA=0x00 VB=0x00 VC=0x00 VD=0x00 VE=0x00 VF=0x00 \
+             I=0x2A0 PC=0x206 SP=0x00 DT=0x00 ST=0x00"
+        );
+    }
+
+    #[test]
+    fn run_headless_stops_on_error() {
+        // Calls itself forever, until the stack overflows
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x22, 0x00]).unwrap();
+
+        let options = Options {
+            frames: 10,
+            max_cycles: None,
+            realtime: false,
+            ticks_per_frame: TICKS_PER_FRAME,
+        };
+        let report = super::run_headless(&mut chip8, &options, None);
+
+        assert_eq!(report.cycles, 16);
+        assert_eq!(report.frames, 1);
+        assert_eq!(report.error, Some(Chip8Error::StackOverflow(16)));
+    }
+
+    #[test]
+    fn run_headless_in_realtime_lasts_one_sixtieth_of_a_second_per_frame() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x12, 0x00]).unwrap();
+
+        let options = Options {
+            frames: 6,
+            max_cycles: None,
+            realtime: true,
+            ticks_per_frame: TICKS_PER_FRAME,
+        };
+        let report = super::run_headless(&mut chip8, &options, None);
+
+        assert_eq!(report.frames, 6);
+        assert!(report.elapsed >= Duration::from_millis(99));
+    }
+}
@@ -0,0 +1,24 @@
+//! A simple CHIP-8 emulator written in Rust.
+//!
+//! The [`backend`] module contains the Chip-8 virtual machine, which can be
+//! embedded and driven by any frontend, the [`builtin`] module holds the ROMs
+//! embedded in the emulator, and the [`disasm`] module turns operation codes
+//! into human-readable assembly. The [`palette`] module holds
+//! the colors used to draw the screen, the [`octo`] module reads the options
+//! of Octo cartridges, the [`romdb`] module recognizes well-known ROMs, the
+//! [`settings`] module remembers the settings of each game, and the
+//! [`testroms`] module checks the results of test ROMs.
+//! Every error the library returns is collected in the [`error`] module, and
+//! any of them converts into [`Error`].
+
+pub mod backend;
+pub mod builtin;
+pub mod disasm;
+pub mod error;
+pub mod octo;
+pub mod palette;
+pub mod romdb;
+pub mod settings;
+pub mod testroms;
+
+pub use error::Error;
@@ -1,35 +1,1714 @@
 use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::time::Duration;
 
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use dorustos::backend;
+use dorustos::builtin::{self, BuiltinRom};
+use dorustos::disasm;
+use dorustos::octo;
+use dorustos::palette::Palette;
+use dorustos::romdb;
+use dorustos::settings::GameSettings;
+use dorustos::testroms::{self, Outcome};
+use flate2::read::GzDecoder;
+use log::{debug, error, info, log_enabled, warn, Level, LevelFilter};
 
-mod backend;
+mod audio;
+mod browser;
+mod crash;
+mod debounce;
+mod debugger;
 mod frontend;
+mod headless;
+#[cfg(feature = "network")]
+mod network;
+mod output;
+mod recorder;
+mod replay;
+mod script;
+#[cfg(feature = "terminal")]
+mod terminal;
+mod text;
+
+/// Magic bytes at the start of every gzip file
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
 
 #[derive(Parser)]
-#[command(author, about, version)]
+#[command(author, about, version, args_conflicts_with_subcommands = true)]
 struct Args {
-    /// Chip-8 source file to read
-    file: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Chip-8 source file to read, or its URL (requires the `network` feature)
+    #[arg(required_unless_present_any = ["roms_dir", "builtin"])]
+    file: Option<String>,
+
+    /// Run a ROM embedded in the emulator instead of a file (`bounce`)
+    #[arg(long, value_name = "NAME", conflicts_with = "file", value_parser = parse_builtin)]
+    builtin: Option<&'static BuiltinRom>,
+
+    /// Swap each pair of bytes of the ROM before loading it, to run
+    /// little-endian word dumps
+    #[arg(long)]
+    byte_swap: bool,
+
+    /// Directory to pick a ROM from with an on-screen menu, when no file is given
+    #[arg(long)]
+    roms_dir: Option<String>,
+
+    /// Position of the window's top-left corner on the desktop, as `x,y`
+    /// (invalid positions center the window)
+    #[arg(long, value_name = "X,Y", allow_hyphen_values = true)]
+    window_pos: Option<String>,
+
+    /// Center the window on the screen (the default)
+    #[arg(long, conflicts_with = "window_pos")]
+    center: bool,
+
+    /// Maximum amount of pixels a single sprite draw can flip
+    #[arg(long, default_value_t = backend::DEFAULT_SPRITE_PIXEL_LIMIT)]
+    sprite_pixel_limit: usize,
+
+    #[command(flatten)]
+    quirks: QuirkArgs,
+
+    /// Palette file (GIMP .gpl or a list of #RRGGBB colors) to read the
+    /// background and foreground colors from, in that order
+    #[arg(long, value_name = "FILE")]
+    palette: Option<PathBuf>,
+
+    /// Save the quirks, tick rate and colors given for this ROM, to be used
+    /// every time it's loaded again (instead of the flags above)
+    #[arg(long)]
+    save_settings: bool,
+
+    /// WAV file to play while the sound timer is active, instead of a square
+    /// wave (looped if needed)
+    #[arg(long, value_name = "FILE.wav")]
+    beep_wav: Option<PathBuf>,
+
+    /// Shortest time a beep plays for, in milliseconds, so that games turning
+    /// the sound on and off quickly produce audible blips (0 to disable it)
+    #[arg(long, value_name = "MS", default_value_t = 30)]
+    min_beep_ms: u64,
+
+    /// Only draw one out of every N frames, to save rendering time on slow
+    /// systems (the game and its timers still run at full speed)
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    frame_skip: u64,
+
+    /// How drawn frames are shown: in sync with the monitor (no tearing),
+    /// immediately (lowest latency) or held until the end of their frame
+    /// (steadiest pacing without vsync)
+    #[arg(long, value_enum, default_value_t = PresentModeArg::Vsync)]
+    present_mode: PresentModeArg,
+
+    /// Ratio between the width and height of each pixel, to stretch the
+    /// display like some TVs did (1 for square pixels, below 1 for taller ones)
+    #[arg(long, value_name = "RATIO", default_value_t = 1.0, value_parser = parse_aspect)]
+    aspect: f32,
+
+    /// Size of each pixel of the screen when the window opens
+    #[arg(long, value_name = "N", default_value_t = frontend::SCALE, value_parser = parse_scale)]
+    scale: u32,
+
+    /// Draw the waveform of the beep in a corner of the window
+    #[arg(long)]
+    audio_meter: bool,
+
+    /// Show the program counter and the instruction about to execute in the
+    /// window title
+    #[arg(long)]
+    title_debug: bool,
+
+    /// Print the hash of the ROM (as used by --auto-quirks) and exit
+    #[arg(long)]
+    print_hash: bool,
+
+    /// Count how many times each opcode runs, and print a histogram on exit
+    #[arg(long)]
+    profile_opcodes: bool,
+
+    /// Amount of stack levels, i.e. how deeply subroutine calls can be nested
+    #[arg(
+        long,
+        default_value_t = backend::DEFAULT_STACK_DEPTH as u16,
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    stack_depth: u16,
+
+    /// Stop the program with an error when it writes to the font region of
+    /// the RAM (0x000 to 0x050), instead of letting it overwrite the fontset
+    #[arg(long)]
+    protect_font: bool,
+
+    /// What FX33 / FX55 / FX65 do when they store or load bytes past the end
+    /// of the RAM
+    #[arg(long, value_enum, default_value_t = RamOverflowArg::Fail)]
+    ram_overflow: RamOverflowArg,
+
+    /// When the beep starts: as the sound timer is set (lasting until it runs
+    /// out), or as the sound timer runs out
+    #[arg(long, value_enum, default_value_t = BeepTriggerArg::Set)]
+    beep_trigger: BeepTriggerArg,
+
+    /// Write a byte into the RAM after loading the ROM, e.g. 0x300=0xAB
+    /// (hexadecimal). Can be given several times
+    #[arg(long, value_name = "ADDR=VALUE", value_parser = parse_poke)]
+    poke: Vec<(usize, u8)>,
+
+    /// Run as fast as possible without opening a window, then print benchmark
+    /// statistics
+    #[arg(long)]
+    headless: bool,
+
+    /// Amount of frames to run for in headless mode, and to record with --record-gif
+    #[arg(long, default_value_t = 600)]
+    frames: u64,
+
+    /// Seed the random number generator (used by CXNN), so that every run
+    /// gets the same random numbers (picked at random and printed if omitted)
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Record the display into an animated GIF
+    #[arg(long, value_name = "OUT.gif")]
+    record_gif: Option<PathBuf>,
+
+    /// Keep one out of every N frames in the recorded GIF, to control its size
+    #[arg(long, value_name = "N", default_value_t = 2, requires = "record_gif")]
+    gif_every: u64,
+
+    /// Stop after running this amount of CPU cycles in headless mode
+    #[arg(long, requires = "headless")]
+    max_cycles: Option<u64>,
+
+    /// Print the registers, I, PC, SP and timers after running in headless mode,
+    /// as a single line of NAME=0xVALUE pairs
+    #[arg(long, requires = "headless")]
+    dump_regs: bool,
+
+    /// Don't write a crash dump (as text, which can't be loaded back) of the
+    /// running ROM if the emulator itself panics
+    #[arg(long)]
+    no_panic_dump: bool,
+
+    /// Run at the real game speed (60 frames per second) in headless mode,
+    /// instead of as fast as possible
+    #[arg(long, requires = "headless")]
+    realtime: bool,
+
+    /// Run in the terminal instead of opening a window
+    #[cfg(feature = "terminal")]
+    #[arg(long)]
+    tui: bool,
+
+    /// Ignore key releases followed by a new press of the same key within this
+    /// many milliseconds, to filter out key chatter (delays every release)
+    #[arg(long, value_name = "MS")]
+    debounce: Option<u64>,
+
+    /// Toggle keypad keys with each press instead of holding them down, for
+    /// players who can't hold keys (changes how games see input)
+    #[arg(long, conflicts_with = "debounce")]
+    sticky_keys: bool,
+
+    /// How the keypad follows the keyboard: by applying each key event as it
+    /// arrives, or by reading the whole keyboard once per frame
+    #[arg(long, value_enum, default_value_t = InputModeArg::Event)]
+    input_mode: InputModeArg,
+
+    /// Second set of keys mapped to the keypad, for two players sharing a
+    /// keyboard: "right" for the right side of the keyboard, or 16 keys in
+    /// the order of the keypad's rows (123C 456D 789E A0BF)
+    #[arg(long, value_name = "KEYS", value_parser = frontend::Keymap2::parse)]
+    keymap2: Option<frontend::Keymap2>,
+
+    /// Log every keypad event, with its time and frame number (enables the
+    /// debug messages)
+    #[arg(long)]
+    log_input: bool,
+
+    /// How sprites are drawn: XORed (standard) or always set, to see sprite
+    /// shapes clearly (requires --debug or --step-on-start)
+    #[arg(long, value_enum, default_value_t = DrawModeArg::Xor)]
+    draw_mode: DrawModeArg,
+
+    /// Highlight the pixels where the most recent sprite draw collided
+    #[arg(long)]
+    show_collisions: bool,
+
+    /// Draw "off" pixels as a subtle checkerboard, to help align sprites to
+    /// the pixel grid
+    #[arg(long)]
+    grid: bool,
+
+    /// Color the pixels by how many times sprites collided on them over the
+    /// whole session, from cold to hot
+    #[arg(long)]
+    collision_heatmap: bool,
+
+    /// Enable the debugging keys: F5 pauses / resumes, F6 steps a single instruction
+    #[arg(long)]
+    debug: bool,
+
+    /// Start paused before executing the first instruction (implies --debug)
+    #[arg(long)]
+    step_on_start: bool,
+
+    /// Start in slow motion, waiting this many milliseconds between frames
+    /// (F7 toggles slow motion at any time)
+    #[arg(long, value_name = "MS")]
+    slowmo: Option<u64>,
+
+    /// While paused, wait up to this many milliseconds for input before
+    /// redrawing, to save CPU (0 to never wait)
+    #[arg(long, value_name = "MS", default_value_t = 50)]
+    paused_poll_ms: u32,
+
+    /// Longest time, in milliseconds, a single frame can advance the timers
+    /// by, so that long stalls (e.g. breakpoints) aren't caught up on at once
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = frontend::MAX_FRAME_TIME.as_millis() as u64,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    max_frame_ms: u64,
+
+    /// While paused, repeat the step key every this many milliseconds when
+    /// it's held down (0 to never repeat it)
+    #[arg(long, value_name = "MS", default_value_t = 50)]
+    step_repeat_ms: u64,
+
+    /// Don't print anything but errors (which go to the standard error)
+    #[arg(long, short, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print extra diagnostics, like the settings in use
+    #[arg(long, short)]
+    verbose: bool,
+
+    /// Most detailed level of messages to print, replacing --quiet and --verbose
+    #[arg(long, value_enum, conflicts_with_all = ["quiet", "verbose"])]
+    log_level: Option<LogLevelArg>,
+}
+
+/// Options choosing the quirks and tick rate a ROM runs with, shared by the
+/// commands that run ROMs
+#[derive(clap::Args)]
+struct QuirkArgs {
+    /// Make the SCHIP scroll opcodes move 4 pixels in lores mode, instead of 2
+    #[arg(long)]
+    lores_scroll_full: bool,
+
+    /// Make FX1E set VF when I overflows past 0x0FFF (the "Amiga" behavior)
+    #[arg(long)]
+    fx1e_sets_vf: bool,
+
+    /// Wrap the program counter around to the start of the RAM when it runs off
+    /// the end, instead of stopping with an error
+    #[arg(long)]
+    wrap_pc: bool,
+
+    /// Make 8XY6 / 8XYE shift VY into VX, instead of shifting VX in place
+    #[arg(long)]
+    shift_vy: bool,
+
+    /// Make FX55 / FX65 leave I pointing past the last register stored or loaded
+    #[arg(long)]
+    load_store_increments_i: bool,
+
+    /// Make BNNN jump to NNN + VX instead of NNN + V0
+    #[arg(long)]
+    jump_vx: bool,
+
+    /// Make 8XY1, 8XY2 and 8XY3 reset VF to 0
+    #[arg(long)]
+    vf_reset: bool,
+
+    /// Clip sprites at the edges of the screen, instead of wrapping them around
+    #[arg(long)]
+    clip_sprites: bool,
+
+    /// Make pixels clipped by --clip-sprites still set VF when the pixel they
+    /// would have wrapped onto is lit
+    #[arg(long, requires = "clip_sprites")]
+    clip_counts_collision: bool,
+
+    /// Wait for the next frame after drawing a sprite
+    #[arg(long)]
+    display_wait: bool,
+
+    /// Make arithmetic opcodes store their result after setting VF, so the
+    /// result wins when VF is the destination
+    #[arg(long)]
+    vf_result_last: bool,
+
+    /// Keep pixels lit at the end of the previous frame on the display, to
+    /// reduce the flicker of sprites that are erased and redrawn
+    #[arg(long)]
+    anti_flicker: bool,
+
+    /// Keep the display when 00FE / 00FF switch the resolution mode, instead
+    /// of clearing it
+    #[arg(long)]
+    res_switch_keeps_screen: bool,
+
+    /// Octo cartridge options (JSON) to read the tick rate, colors and quirks
+    /// from, replacing the quirk flags above
+    #[arg(long, value_name = "OPTIONS.json")]
+    octo_options: Option<PathBuf>,
+
+    /// Use the quirks of the ROM from the built-in table of well-known ROMs,
+    /// if it's recognized, except for the quirk flags above that are given
+    #[arg(long)]
+    auto_quirks: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the emulator's version, defaults and supported opcodes
+    Info,
+    /// Print which keyboard key is mapped to each button of the keypad
+    Keys {
+        /// Second set of keys mapped to the keypad, to print as well (same
+        /// format as the main command's option)
+        #[arg(long, value_name = "KEYS", value_parser = frontend::Keymap2::parse)]
+        keymap2: Option<frontend::Keymap2>,
+    },
+    /// Run a known test ROM without a display and report whether it passed
+    Test {
+        /// Test ROM file to run
+        rom: PathBuf,
+    },
+    /// List the opcodes of a ROM that the emulator doesn't support, without
+    /// running it
+    Validate {
+        /// ROM file to check
+        rom: PathBuf,
+    },
+    /// Replay two input logs (written with --log-input) against the same
+    /// ROM, and report the first frame where the runs diverge
+    DiffReplay {
+        /// First input log
+        a: PathBuf,
+        /// Second input log
+        b: PathBuf,
+        /// ROM file to replay the logs against
+        rom: PathBuf,
+        /// Amount of frames to replay
+        #[arg(long, default_value_t = 600)]
+        frames: u64,
+        /// Seed of the random number generator, shared by both replays
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        #[command(flatten)]
+        quirks: QuirkArgs,
+    },
+    /// Run a ROM without a display, driven by the commands of a script
+    /// file, and report whether its assertions held
+    Script {
+        /// Script file to run
+        script: PathBuf,
+        /// ROM file to run the script against
+        rom: PathBuf,
+        /// Seed of the random number generator
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DrawModeArg {
+    /// XOR sprites onto the screen, as per the Chip-8 spec
+    Xor,
+    /// Always turn sprite pixels on
+    Set,
+}
+
+impl From<DrawModeArg> for backend::DrawMode {
+    fn from(mode: DrawModeArg) -> Self {
+        match mode {
+            DrawModeArg::Xor => backend::DrawMode::Xor,
+            DrawModeArg::Set => backend::DrawMode::Set,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PresentModeArg {
+    /// Wait for the monitor's vertical sync
+    Vsync,
+    /// Show frames as soon as they're drawn
+    Immediate,
+    /// Show frames at the end of their time slot
+    Paced,
+}
+
+impl From<PresentModeArg> for frontend::PresentMode {
+    fn from(mode: PresentModeArg) -> Self {
+        match mode {
+            PresentModeArg::Vsync => frontend::PresentMode::Vsync,
+            PresentModeArg::Immediate => frontend::PresentMode::Immediate,
+            PresentModeArg::Paced => frontend::PresentMode::Paced,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum InputModeArg {
+    /// Apply each key press and release as it arrives
+    Event,
+    /// Read the state of the keyboard once per frame
+    Poll,
+}
+
+impl From<InputModeArg> for frontend::InputMode {
+    fn from(mode: InputModeArg) -> Self {
+        match mode {
+            InputModeArg::Event => frontend::InputMode::Event,
+            InputModeArg::Poll => frontend::InputMode::Poll,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum RamOverflowArg {
+    /// Stop the program with an error
+    Fail,
+    /// Wrap around to the start of the RAM
+    Wrap,
+    /// Use the last address of the RAM for every address past the end
+    Clamp,
+}
+
+impl From<RamOverflowArg> for backend::RamOverflowAction {
+    fn from(action: RamOverflowArg) -> Self {
+        match action {
+            RamOverflowArg::Fail => backend::RamOverflowAction::Fail,
+            RamOverflowArg::Wrap => backend::RamOverflowAction::Wrap,
+            RamOverflowArg::Clamp => backend::RamOverflowAction::Clamp,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BeepTriggerArg {
+    /// Beep when FX18 sets the sound timer, until it runs out
+    Set,
+    /// Beep for a single tick when the sound timer runs out
+    RunOut,
+}
+
+impl From<BeepTriggerArg> for backend::BeepTrigger {
+    fn from(trigger: BeepTriggerArg) -> Self {
+        match trigger {
+            BeepTriggerArg::Set => backend::BeepTrigger::Set,
+            BeepTriggerArg::RunOut => backend::BeepTrigger::RunOut,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LogLevelArg {
+    /// Print nothing at all
+    Off,
+    /// Print errors only
+    Error,
+    /// Print errors and warnings
+    Warn,
+    /// Print errors, warnings and regular messages
+    Info,
+    /// Also print extra diagnostics, like the settings in use
+    Debug,
+    /// Print everything
+    Trace,
+}
+
+impl From<LogLevelArg> for LevelFilter {
+    fn from(level: LogLevelArg) -> Self {
+        match level {
+            LogLevelArg::Off => LevelFilter::Off,
+            LogLevelArg::Error => LevelFilter::Error,
+            LogLevelArg::Warn => LevelFilter::Warn,
+            LogLevelArg::Info => LevelFilter::Info,
+            LogLevelArg::Debug => LevelFilter::Debug,
+            LogLevelArg::Trace => LevelFilter::Trace,
+        }
+    }
 }
 
 fn main() {
-    let args = Args::parse();
-    let filename = args.file;
-    let file_exists = Path::new(&filename).exists();
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+    let mut level = output::level_filter(
+        args.log_level.map(LevelFilter::from),
+        args.quiet,
+        args.verbose,
+    );
+    if args.log_input {
+        // Keypad events are logged at the debug level
+        level = level.max(LevelFilter::Debug);
+    }
+    output::init(level);
+    if !args.no_panic_dump {
+        crash::install_panic_hook();
+    }
+
+    match &args.command {
+        Some(Command::Info) => {
+            print_info();
+            return;
+        }
+        Some(Command::Keys { keymap2 }) => {
+            println!("{}", frontend::format_keymap(keymap2.as_ref()));
+            return;
+        }
+        Some(Command::Test { rom }) => run_test_rom(rom),
+        Some(Command::Validate { rom }) => validate_rom(rom),
+        Some(Command::DiffReplay {
+            a,
+            b,
+            rom,
+            frames,
+            seed,
+            quirks,
+        }) => {
+            let matches = matches.subcommand_matches("diff-replay").unwrap();
+            diff_replay(a, b, rom, *frames, *seed, quirks, matches)
+        }
+        Some(Command::Script { script, rom, seed }) => run_script(script, rom, *seed),
+        None => (),
+    }
 
-    if !file_exists {
-        println!("ERROR: Couldn't find file: {}", filename);
+    // --step-on-start turns the debugger on by itself
+    if given_on_command_line(&matches, "draw_mode") && !(args.debug || args.step_on_start) {
+        error!("--draw-mode requires --debug or --step-on-start");
         exit(exitcode::USAGE);
     }
 
-    let mut chip8 = backend::Chip8::new();
-    let mut rom = File::open(filename).expect("ERROR: Couldn't open file");
-    let mut buffer = Vec::new();
-    rom.read_to_end(&mut buffer).unwrap();
-    chip8.load(&buffer);
+    // Polling only sees which keys are held, so it can't toggle or debounce them
+    if args.input_mode == InputModeArg::Poll && (args.sticky_keys || args.debounce.is_some()) {
+        error!("--input-mode poll can't be used with --sticky-keys or --debounce");
+        exit(exitcode::USAGE);
+    }
+
+    let mut window_pos = args.window_pos.as_deref().and_then(|text| {
+        let position = parse_window_pos(text);
+        if position.is_none() {
+            warn!("Invalid window position {}, centering the window", text);
+        }
+        position
+    });
+
+    let filename = match (args.builtin, args.file, args.roms_dir) {
+        (Some(rom), _, _) => rom.name.to_string(),
+        (None, Some(file), _) => file,
+        (None, None, Some(dir)) => match pick_rom(&dir, window_pos) {
+            Some((file, position)) => {
+                window_pos = Some(position);
+                file
+            }
+            None => return,
+        },
+        (None, None, None) => {
+            unreachable!("clap requires a file, a ROMs directory or a built-in ROM")
+        }
+    };
+    let buffer = if let Some(rom) = args.builtin {
+        rom.data.to_vec()
+    } else if is_url(&filename) {
+        download_rom(&filename)
+    } else {
+        let file_exists = Path::new(&filename).exists();
+
+        if !file_exists {
+            error!("Couldn't find file: {}", filename);
+            exit(exitcode::USAGE);
+        }
+
+        let mut rom = File::open(&filename).expect("ERROR: Couldn't open file");
+        let mut buffer = Vec::new();
+        rom.read_to_end(&mut buffer).unwrap();
+        buffer
+    };
+
+    let mut buffer = match decompress_rom(buffer) {
+        Ok(buffer) => buffer,
+        Err(err) => {
+            error!("{}", err);
+            exit(exitcode::DATAERR);
+        }
+    };
+
+    if args.byte_swap {
+        if let Err(err) = swap_bytes(&mut buffer) {
+            error!("{}", err);
+            exit(exitcode::DATAERR);
+        }
+    }
+
+    let (mut quirks, mut ticks_per_frame, mut palette) =
+        rom_settings(&args.quirks, &matches, &buffer);
+    let mut scale = args.scale;
+
+    if let Some(path) = &args.palette {
+        let parsed = match std::fs::read_to_string(path) {
+            Ok(text) => Palette::parse(&text),
+            Err(err) => Err(format!("Couldn't read {}: {}", path.display(), err)),
+        };
+        match parsed {
+            Ok(parsed) => palette = parsed,
+            Err(err) => {
+                error!("{}", err);
+                exit(exitcode::DATAERR);
+            }
+        }
+    }
+
+    // Settings in the ROM's settings file, if it has one, to update with the
+    // settings changed while running
+    let mut saved_settings = None;
+    match dirs::config_dir()
+        .map(|dir| GameSettings::path(&dir.join("dorustos"), romdb::rom_hash(&buffer)))
+    {
+        Some(path) if args.save_settings => {
+            let settings = GameSettings {
+                tickrate: ticks_per_frame,
+                quirks,
+                palette,
+                scale: Some(scale),
+            };
+            match settings.save(&path) {
+                Ok(()) => {
+                    info!("Saved the settings of this ROM to {}", path.display());
+                    saved_settings = Some((path, settings));
+                }
+                Err(err) => warn!("{}", err),
+            }
+        }
+        Some(path) => match GameSettings::load(&path) {
+            Ok(Some(saved)) => {
+                info!(
+                    "Using the saved settings of this ROM from {}",
+                    path.display()
+                );
+                let given = GameSettings {
+                    tickrate: ticks_per_frame,
+                    quirks,
+                    palette,
+                    scale: Some(scale),
+                };
+                saved_settings = Some((path, saved.clone()));
+                let (settings, overridden) = merge_settings(saved, given, &matches);
+                if !overridden.is_empty() {
+                    warn!(
+                        "Overriding the saved {} with the command line",
+                        overridden.join(", ")
+                    );
+                }
+                quirks = settings.quirks;
+                ticks_per_frame = settings.tickrate;
+                palette = settings.palette;
+                scale = settings.scale.unwrap_or(scale);
+            }
+            Ok(None) => (),
+            Err(err) => warn!("{}, ignoring the saved settings", err),
+        },
+        None if args.save_settings => {
+            warn!("No configuration directory, the settings won't be saved")
+        }
+        None => (),
+    }
+
+    let mut chip8 = backend::Chip8::builder()
+        .quirks(quirks)
+        .stack_depth(args.stack_depth as usize)
+        .protect_font(args.protect_font)
+        .ram_overflow_action(args.ram_overflow.into())
+        .beep_trigger(args.beep_trigger.into())
+        .seed(session_seed(args.seed))
+        .build();
+    chip8.set_sprite_pixel_limit(args.sprite_pixel_limit);
+    chip8.set_draw_mode(args.draw_mode.into());
+    chip8.set_opcode_profiling(args.profile_opcodes);
+    if let Err(err) = chip8.load(&buffer) {
+        error!("{}", err);
+        exit(exitcode::DATAERR);
+    }
+    for &(addr, value) in &args.poke {
+        if let Err(err) = chip8.poke_ram(addr, value) {
+            error!("Couldn't poke {:#05X}: {}", addr, err);
+            exit(exitcode::USAGE);
+        }
+    }
 
-    frontend::run_game(chip8);
+    if args.print_hash {
+        println!("{:016x}  {}", chip8.rom_hash(), filename);
+        return;
+    }
+
+    debug!(
+        "Loaded {} ({} bytes, hash {:016x})",
+        filename,
+        buffer.len(),
+        chip8.rom_hash()
+    );
+    debug!("CPU ticks per frame: {}", ticks_per_frame);
+    for (name, value) in quirks.flags() {
+        debug!("Quirk {}: {}", name, value);
+    }
+
+    let recorder = args.record_gif.map(|path| {
+        match recorder::GifRecorder::create(&path, args.frames, args.gif_every, &palette) {
+            Ok(recorder) => recorder,
+            Err(err) => {
+                error!("Couldn't create GIF {}: {}", path.display(), err);
+                exit(exitcode::CANTCREAT);
+            }
+        }
+    });
+
+    #[cfg(feature = "terminal")]
+    let tui = args.tui;
+    #[cfg(not(feature = "terminal"))]
+    let tui = false;
+
+    let mode = if args.headless {
+        Mode::Headless
+    } else if tui {
+        Mode::Terminal
+    } else {
+        match frontend::open_display(window_pos, args.aspect, scale, args.present_mode.into()) {
+            Ok(display) => Mode::Window(display),
+            Err(err) => fallback_mode(&err),
+        }
+    };
+
+    let display = match mode {
+        Mode::Headless => {
+            let options = headless::Options {
+                frames: args.frames,
+                max_cycles: args.max_cycles,
+                realtime: args.realtime,
+                ticks_per_frame,
+            };
+            let report = headless::run_headless(&mut chip8, &options, recorder);
+            if log_enabled!(Level::Info) {
+                report.print();
+            }
+            if args.dump_regs {
+                println!("{}", headless::format_registers(&chip8.snapshot()));
+            }
+            print_opcode_histogram(&chip8);
+            if let Some(err) = report.error {
+                error!("{}", err);
+                crash::report(&chip8, &err);
+                exit(exitcode::SOFTWARE);
+            }
+            return;
+        }
+        #[cfg(feature = "terminal")]
+        Mode::Terminal => {
+            match terminal::run_game(
+                chip8,
+                ticks_per_frame,
+                Duration::from_millis(args.max_frame_ms),
+            ) {
+                Ok(chip8) => print_opcode_histogram(&chip8),
+                Err(err) => {
+                    error!("Terminal frontend failed: {}", err);
+                    exit(exitcode::IOERR);
+                }
+            }
+            return;
+        }
+        #[cfg(not(feature = "terminal"))]
+        Mode::Terminal => unreachable!("the terminal frontend isn't compiled in"),
+        Mode::Window(display) => display,
+    };
+
+    let options = frontend::Options {
+        debug: args.debug || args.step_on_start,
+        step_on_start: args.step_on_start,
+        log_input: args.log_input,
+        show_collisions: args.show_collisions,
+        grid: args.grid,
+        collision_heatmap: args.collision_heatmap,
+        sticky_keys: args.sticky_keys,
+        ticks_per_frame,
+        palette,
+        paused_poll_ms: args.paused_poll_ms,
+        slowmo: args.slowmo.map(Duration::from_millis),
+        debounce: args.debounce.map(Duration::from_millis),
+        beep_wav: args.beep_wav,
+        min_beep: Duration::from_millis(args.min_beep_ms),
+        frame_skip: args.frame_skip,
+        audio_meter: args.audio_meter,
+        title_debug: args.title_debug,
+        max_frame_time: Duration::from_millis(args.max_frame_ms),
+        present_mode: args.present_mode.into(),
+        input_mode: args.input_mode.into(),
+        keymap2: args.keymap2,
+        step_repeat: (args.step_repeat_ms > 0).then(|| Duration::from_millis(args.step_repeat_ms)),
+    };
+    let (chip8, final_scale) = frontend::run_game(chip8, display, &options, recorder);
+    print_opcode_histogram(&chip8);
+
+    // Resizing the window changes the scale the ROM opens with next time
+    if let Some((path, mut settings)) = saved_settings.filter(|_| final_scale != scale) {
+        settings.scale = Some(final_scale);
+        match settings.save(&path) {
+            Ok(()) => info!(
+                "Saved the new scale ({}) of this ROM to {}",
+                final_scale,
+                path.display()
+            ),
+            Err(err) => warn!("{}", err),
+        }
+    }
+}
+
+/// Frontend the emulator runs in
+enum Mode {
+    /// A window, already open
+    Window(frontend::Display),
+    /// The terminal (requires the `terminal` feature)
+    Terminal,
+    /// No display at all
+    Headless,
+}
+
+/// Returns the frontend to run in when no window can be opened: the
+/// terminal if it's compiled in and the output is a terminal, or no
+/// display at all otherwise
+///
+/// # Arguments
+///
+/// * `err` - Why the window couldn't be opened
+fn fallback_mode(err: &str) -> Mode {
+    let terminal = cfg!(feature = "terminal") && std::io::stdout().is_terminal();
+
+    if terminal {
+        warn!("Couldn't open a window ({}), running in the terminal", err);
+        Mode::Terminal
+    } else {
+        warn!(
+            "Couldn't open a window ({}), running without a display",
+            err
+        );
+        Mode::Headless
+    }
+}
+
+/// Prints how many times each opcode pattern ran, if profiling was enabled
+///
+/// # Arguments
+///
+/// * `chip8` - Chip-8 emulator instance, after running the program
+fn print_opcode_histogram(chip8: &backend::Chip8) {
+    let histogram = chip8.opcode_histogram();
+    if histogram.is_empty() {
+        return;
+    }
+
+    let total: u64 = histogram.iter().map(|(_, count)| count).sum();
+    println!("Opcode histogram ({} instructions):", total);
+    for (pattern, count) in histogram {
+        println!(
+            "  {:<6} {:>12} {:>6.2}%",
+            pattern,
+            count,
+            count as f64 * 100.0 / total as f64
+        );
+    }
+}
+
+/// Runs a known test ROM without a display, prints whether it passed and
+/// exits accordingly
+///
+/// # Arguments
+///
+/// * `path` - Path of the test ROM file
+fn run_test_rom(path: &Path) -> ! {
+    let data = read_rom_file(path);
+
+    let Some(test) = testroms::lookup(&data) else {
+        error!(
+            "{} isn't a known test ROM (hash {:016x})",
+            path.display(),
+            romdb::rom_hash(&data)
+        );
+        exit(exitcode::DATAERR);
+    };
+
+    match testroms::run(test, &data) {
+        Ok(Outcome::Passed) => {
+            println!("PASS {}", test.name);
+            exit(exitcode::OK);
+        }
+        Ok(Outcome::Failed(hash)) => {
+            println!(
+                "FAIL {} (display hash {:016x}, expected {:016x})",
+                test.name, hash, test.display_hash
+            );
+            exit(exitcode::SOFTWARE);
+        }
+        Err(err) => {
+            error!("{}", err);
+            exit(exitcode::DATAERR);
+        }
+    }
+}
+
+/// Statically decodes every word of a ROM, prints the ones using opcodes the
+/// emulator doesn't support and exits accordingly
+///
+/// # Arguments
+///
+/// * `path` - Path of the ROM file
+fn validate_rom(path: &Path) -> ! {
+    let data = read_rom_file(path);
+    let unsupported = disasm::unsupported_ops(&data);
+
+    if unsupported.is_empty() {
+        println!("OK {}: every opcode is supported", path.display());
+        exit(exitcode::OK);
+    }
+
+    println!(
+        "{} unsupported opcode(s) in {}:",
+        unsupported.len(),
+        path.display()
+    );
+    for (addr, op) in unsupported {
+        println!("{:#05X}: {:04X}", addr, op);
+    }
+    println!("Note: every word is decoded as an instruction, so data stored in the ROM (e.g. sprites) may be listed too");
+    exit(exitcode::DATAERR);
+}
+
+/// Replays two input logs against the same ROM, prints the first frame
+/// where the runs diverge (if any) and exits accordingly
+///
+/// # Arguments
+///
+/// * `a` - Path of the first input log
+/// * `b` - Path of the second input log
+/// * `rom` - Path of the ROM file
+/// * `frames` - Amount of frames to replay
+/// * `seed` - Seed of the random number generator
+/// * `quirks` - Options choosing the quirks and tick rate
+/// * `matches` - Parsed arguments of the command, to tell which were given
+fn diff_replay(
+    a: &Path,
+    b: &Path,
+    rom: &Path,
+    frames: u64,
+    seed: u64,
+    quirks: &QuirkArgs,
+    matches: &ArgMatches,
+) -> ! {
+    let read_log = |path: &Path| {
+        let events = std::fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| replay::parse_log(&text));
+        events.unwrap_or_else(|err| {
+            error!("Couldn't read {}: {}", path.display(), err);
+            exit(exitcode::DATAERR);
+        })
+    };
+    let (left, right) = (read_log(a), read_log(b));
+
+    let buffer = read_rom_file(rom);
+    let (quirks, ticks_per_frame, _) = rom_settings(quirks, matches, &buffer);
+    let mut chip8 = backend::Chip8::builder().quirks(quirks).seed(seed).build();
+    if let Err(err) = chip8.load(&buffer) {
+        error!("{}", err);
+        exit(exitcode::DATAERR);
+    }
+
+    match replay::find_divergence(&chip8, &left, &right, frames, ticks_per_frame) {
+        Some(divergence) => {
+            divergence.print();
+            exit(exitcode::DATAERR);
+        }
+        None => {
+            println!("Replays match for {} frames", frames);
+            exit(exitcode::OK);
+        }
+    }
+}
+
+/// Returns the quirks, tick rate and colors to run a ROM with, from the quirk
+/// flags, `--auto-quirks` and `--octo-options`. Quirk flags given explicitly
+/// take precedence over the quirks of a recognized ROM. Exits if the Octo
+/// options can't be read.
+///
+/// # Arguments
+///
+/// * `args` - Options choosing the quirks and tick rate
+/// * `matches` - Parsed command line arguments, to tell which flags were given
+/// * `rom` - Contents of the ROM, to recognize it with `--auto-quirks`
+fn rom_settings(
+    args: &QuirkArgs,
+    matches: &ArgMatches,
+    rom: &[u8],
+) -> (backend::Quirks, usize, Palette) {
+    let mut quirks = backend::Quirks {
+        lores_scroll_full: args.lores_scroll_full,
+        fx1e_sets_vf: args.fx1e_sets_vf,
+        wrap_pc: args.wrap_pc,
+        shift_vy: args.shift_vy,
+        load_store_increments_i: args.load_store_increments_i,
+        jump_vx: args.jump_vx,
+        vf_reset: args.vf_reset,
+        clip_sprites: args.clip_sprites,
+        clip_counts_collision: args.clip_counts_collision,
+        display_wait: args.display_wait,
+        vf_result_last: args.vf_result_last,
+        anti_flicker: args.anti_flicker,
+        res_switch_clears: !args.res_switch_keeps_screen,
+    };
+    let mut ticks_per_frame = frontend::TICKS_PER_FRAME;
+    let mut palette = Palette::default();
+
+    if args.auto_quirks {
+        match romdb::lookup(rom) {
+            Some(known) => {
+                info!("Recognized {}, using its quirks", known.name);
+                let given = quirks;
+                quirks = known.quirks;
+                for (name, value) in given.flags() {
+                    let flag = quirk_flag(name);
+                    if given_on_command_line(matches, flag) {
+                        info!(
+                            "--{} overrides the quirks of {}",
+                            flag.replace('_', "-"),
+                            known.name
+                        );
+                        quirks.set(name, value);
+                    }
+                }
+            }
+            None => debug!("ROM not recognized, using the given quirks"),
+        }
+    }
+
+    if let Some(path) = &args.octo_options {
+        let options = match std::fs::read_to_string(path) {
+            Ok(json) => octo::parse_options(&json),
+            Err(err) => Err(format!("Couldn't read {}: {}", path.display(), err)),
+        };
+        match options {
+            Ok(options) => {
+                quirks = options.quirks;
+                ticks_per_frame = options.tickrate.unwrap_or(ticks_per_frame);
+                palette = options.palette;
+            }
+            Err(err) => {
+                error!("{}", err);
+                exit(exitcode::DATAERR);
+            }
+        }
+    }
+
+    (quirks, ticks_per_frame, palette)
+}
+
+/// Runs a script against a ROM, prints whether it passed and exits
+/// accordingly
+///
+/// # Arguments
+///
+/// * `path` - Path of the script file
+/// * `rom` - Path of the ROM file
+/// * `seed` - Seed of the random number generator
+fn run_script(path: &Path, rom: &Path, seed: u64) -> ! {
+    let steps = std::fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| script::parse_script(&text));
+    let steps = steps.unwrap_or_else(|err| {
+        error!("Couldn't read {}: {}", path.display(), err);
+        exit(exitcode::DATAERR);
+    });
+
+    let mut chip8 = backend::Chip8::builder().seed(seed).build();
+    if let Err(err) = chip8.load(&read_rom_file(rom)) {
+        error!("{}", err);
+        exit(exitcode::DATAERR);
+    }
+
+    match script::run_script(&mut chip8, &steps, frontend::TICKS_PER_FRAME) {
+        Ok(()) => {
+            println!("PASS {}", path.display());
+            exit(exitcode::OK);
+        }
+        Err(err) => {
+            println!("FAIL {}: {}", path.display(), err);
+            exit(exitcode::SOFTWARE);
+        }
+    }
+}
+
+/// Reads a ROM file, decompressing it if needed, or exits with an error
+/// if it can't be read
+///
+/// # Arguments
+///
+/// * `path` - Path of the ROM file
+fn read_rom_file(path: &Path) -> Vec<u8> {
+    let data = match std::fs::read(path).map_err(|err| err.to_string()) {
+        Ok(data) => decompress_rom(data),
+        Err(err) => Err(err),
+    };
+    match data {
+        Ok(data) => data,
+        Err(err) => {
+            error!("Couldn't read {}: {}", path.display(), err);
+            exit(exitcode::NOINPUT);
+        }
+    }
+}
+
+/// Returns the seed of the random number generator for this session: the
+/// one given on the command line, or a random one otherwise. Either way, the
+/// seed is printed so that the session can be reproduced with `--seed`.
+///
+/// # Arguments
+///
+/// * `seed` - Seed given on the command line, if any
+fn session_seed(seed: Option<u64>) -> u64 {
+    let seed = seed.unwrap_or_else(rand::random);
+    info!(
+        "Random seed: {} (pass --seed {} to reproduce this session)",
+        seed, seed
+    );
+    seed
+}
+
+/// Returns whether a ROM should be downloaded instead of read from a file,
+/// i.e. whether it's given as an HTTP or HTTPS URL
+///
+/// # Arguments
+///
+/// * `filename` - The ROM given on the command line
+fn is_url(filename: &str) -> bool {
+    filename.starts_with("http://") || filename.starts_with("https://")
+}
+
+/// Downloads a ROM, or exits with an error if it can't be downloaded
+///
+/// # Arguments
+///
+/// * `url` - Address of the ROM file
+#[cfg(feature = "network")]
+fn download_rom(url: &str) -> Vec<u8> {
+    match network::download(url) {
+        Ok(data) => data,
+        Err(err) => {
+            error!("Couldn't download {}: {}", url, err);
+            exit(exitcode::UNAVAILABLE);
+        }
+    }
+}
+
+/// Exits with an error, as ROMs can only be downloaded with the `network`
+/// feature
+///
+/// # Arguments
+///
+/// * `url` - Address of the ROM file
+#[cfg(not(feature = "network"))]
+fn download_rom(url: &str) -> Vec<u8> {
+    error!(
+        "Couldn't download {}: dorustos was built without the `network` feature",
+        url
+    );
+    exit(exitcode::UNAVAILABLE);
+}
+
+/// Decompresses a ROM if it's gzipped (i.e. starts with the gzip magic bytes),
+/// or returns it untouched otherwise. Decompression stops as soon as the ROM
+/// turns out to be larger than the RAM, so that a small file can't inflate
+/// into a huge one.
+///
+/// # Arguments
+///
+/// * `data` - The contents of the ROM file
+fn decompress_rom(data: Vec<u8>) -> Result<Vec<u8>, String> {
+    if !data.starts_with(&GZIP_MAGIC) {
+        return Ok(data);
+    }
+
+    let mut decompressed = Vec::new();
+    GzDecoder::new(data.as_slice())
+        .take(backend::RAM_SIZE as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|err| format!("Couldn't decompress gzipped ROM: {}", err))?;
+
+    if decompressed.len() > backend::RAM_SIZE {
+        return Err(format!(
+            "Gzipped ROM is too large: it decompresses to more than {} bytes",
+            backend::RAM_SIZE
+        ));
+    }
+
+    Ok(decompressed)
+}
+
+/// Swaps each pair of bytes of a ROM in place, turning a little-endian word
+/// dump into a regular (big-endian) Chip-8 program
+///
+/// # Arguments
+///
+/// * `data` - The ROM to swap
+fn swap_bytes(data: &mut [u8]) -> Result<(), String> {
+    if !data.len().is_multiple_of(2) {
+        return Err(format!(
+            "Can't swap bytes of a ROM with an odd length ({} bytes)",
+            data.len()
+        ));
+    }
+
+    for pair in data.chunks_exact_mut(2) {
+        pair.swap(0, 1);
+    }
+
+    Ok(())
+}
+
+/// Scans a directory for ROMs and lets the user pick one from an on-screen
+/// menu. Returns the path of the picked ROM, or `None` if the menu was closed.
+///
+/// # Arguments
+///
+/// * `dir` - Directory to scan for ROMs
+/// * `position` - Position of the menu window, or `None` to center it
+fn pick_rom(dir: &str, position: Option<(i32, i32)>) -> Option<(String, (i32, i32))> {
+    let roms = match browser::find_roms(Path::new(dir)) {
+        Ok(roms) => roms,
+        Err(err) => {
+            error!("Couldn't read directory {}: {}", dir, err);
+            exit(exitcode::NOINPUT);
+        }
+    };
+
+    if roms.is_empty() {
+        error!("No ROMs (.ch8 or .rom files) found in {}", dir);
+        exit(exitcode::NOINPUT);
+    }
+
+    browser::select_rom(&roms, position)
+        .map(|(rom, position)| (rom.to_string_lossy().into_owned(), position))
+}
+
+/// Parses a RAM write given as `ADDR=VALUE`, both in hexadecimal with or
+/// without a `0x` prefix
+///
+/// # Arguments
+///
+/// * `text` - The write to parse
+fn parse_poke(text: &str) -> Result<(usize, u8), String> {
+    let hex = |digits: &str| {
+        digits
+            .trim()
+            .trim_start_matches("0x")
+            .trim_start_matches("0X")
+            .to_string()
+    };
+    let (addr, value) = text
+        .split_once('=')
+        .ok_or_else(|| format!("expected ADDR=VALUE, got {}", text))?;
+
+    let addr =
+        usize::from_str_radix(&hex(addr), 16).map_err(|_| format!("invalid address: {}", addr))?;
+    if addr >= backend::RAM_SIZE {
+        return Err(format!("address {:#05X} is past the end of the RAM", addr));
+    }
+    let value =
+        u8::from_str_radix(&hex(value), 16).map_err(|_| format!("invalid byte: {}", value))?;
+
+    Ok((addr, value))
+}
+
+/// Parses the name of a ROM embedded in the emulator
+///
+/// # Arguments
+///
+/// * `name` - The name to parse
+fn parse_builtin(name: &str) -> Result<&'static BuiltinRom, String> {
+    builtin::find(name).ok_or_else(|| {
+        let names: Vec<&str> = builtin::BUILTIN_ROMS.iter().map(|rom| rom.name).collect();
+        format!("unknown ROM {} (available: {})", name, names.join(", "))
+    })
+}
+
+/// Returns whether an argument was given explicitly on the command line,
+/// rather than left to its default value
+///
+/// # Arguments
+///
+/// * `matches` - Parsed command line arguments
+/// * `id` - Name of the argument
+fn given_on_command_line(matches: &ArgMatches, id: &str) -> bool {
+    matches.value_source(id) == Some(ValueSource::CommandLine)
+}
+
+/// Returns the id of the command line flag setting a quirk
+///
+/// # Arguments
+///
+/// * `name` - Name of the quirk, as returned by `Quirks::flags`
+fn quirk_flag(name: &str) -> &str {
+    match name {
+        "res_switch_clears" => "res_switch_keeps_screen",
+        name => name,
+    }
+}
+
+/// Combines the settings saved for a ROM with the ones from the command
+/// line: the saved settings are used, except for those given explicitly on
+/// the command line. Returns the combined settings, along with the names of
+/// the saved settings that were overridden.
+///
+/// # Arguments
+///
+/// * `saved` - Settings saved for the ROM
+/// * `given` - Settings from the command line (including default values)
+/// * `matches` - Parsed command line arguments, to tell which were given
+fn merge_settings(
+    saved: GameSettings,
+    given: GameSettings,
+    matches: &ArgMatches,
+) -> (GameSettings, Vec<&'static str>) {
+    let mut settings = saved;
+    let mut overridden = Vec::new();
+
+    if given_on_command_line(matches, "octo_options") {
+        settings.tickrate = given.tickrate;
+        overridden.push("tick rate");
+    }
+
+    if given_on_command_line(matches, "octo_options")
+        || given_on_command_line(matches, "auto_quirks")
+    {
+        settings.quirks = given.quirks;
+        overridden.push("quirks");
+    } else {
+        for (name, value) in given.quirks.flags() {
+            if given_on_command_line(matches, quirk_flag(name)) {
+                settings.quirks.set(name, value);
+                overridden.push(name);
+            }
+        }
+    }
+
+    if given_on_command_line(matches, "octo_options") || given_on_command_line(matches, "palette") {
+        settings.palette = given.palette;
+        overridden.push("colors");
+    }
+
+    if given_on_command_line(matches, "scale") || settings.scale.is_none() {
+        if settings.scale.is_some() {
+            overridden.push("scale");
+        }
+        settings.scale = given.scale;
+    }
+
+    (settings, overridden)
+}
+
+/// Parses the size of each pixel of the screen, between `frontend::MIN_SCALE`
+/// and `frontend::MAX_SCALE`
+///
+/// # Arguments
+///
+/// * `text` - The scale to parse
+fn parse_scale(text: &str) -> Result<u32, String> {
+    let (min, max) = (frontend::MIN_SCALE, frontend::MAX_SCALE);
+    text.parse()
+        .ok()
+        .filter(|scale| (min..=max).contains(scale))
+        .ok_or(format!(
+            "Invalid scale: {} (expected {} to {})",
+            text, min, max
+        ))
+}
+
+/// Parses a pixel aspect ratio, either as a number (e.g. `0.75`) or as a
+/// fraction (e.g. `3/4`), which has to be within `frontend::ASPECT_RANGE`
+///
+/// # Arguments
+///
+/// * `text` - The ratio to parse
+fn parse_aspect(text: &str) -> Result<f32, String> {
+    let number = |digits: &str| {
+        digits
+            .trim()
+            .parse::<f32>()
+            .map_err(|_| format!("invalid ratio: {}", text))
+    };
+    let aspect = match text.split_once('/') {
+        Some((width, height)) => number(width)? / number(height)?,
+        None => number(text)?,
+    };
+
+    let (min, max) = frontend::ASPECT_RANGE;
+    if !(min..=max).contains(&aspect) {
+        return Err(format!("ratio must be between {} and {}", min, max));
+    }
+    Ok(aspect)
+}
+
+/// Parses a window position given as `x,y`, returning `None` if it's invalid
+///
+/// # Arguments
+///
+/// * `text` - The position to parse
+fn parse_window_pos(text: &str) -> Option<(i32, i32)> {
+    let (x, y) = text.split_once(',')?;
+    Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+}
+
+/// Prints the emulator's version, active defaults and supported opcodes
+fn print_info() {
+    println!("dorustos {}", env!("CARGO_PKG_VERSION"));
+    println!();
+
+    println!("Defaults:");
+    println!("  {:<20} {:#05X}", "Program start", backend::START_ADDR);
+    println!("  {:<20} {} bytes", "RAM size", backend::RAM_SIZE);
+    println!(
+        "  {:<20} {} levels",
+        "Stack depth",
+        backend::DEFAULT_STACK_DEPTH
+    );
+    println!(
+        "  {:<20} {}x{} ({}x{} in hires)",
+        "Screen size",
+        backend::SCREEN_WIDTH,
+        backend::SCREEN_HEIGHT,
+        backend::HIRES_WIDTH,
+        backend::HIRES_HEIGHT
+    );
+    println!(
+        "  {:<20} {} pixels",
+        "Sprite pixel limit",
+        backend::DEFAULT_SPRITE_PIXEL_LIMIT
+    );
+    println!(
+        "  {:<20} {} per frame",
+        "CPU ticks",
+        frontend::TICKS_PER_FRAME
+    );
+    println!();
+
+    println!("Quirks (default values):");
+    for (name, value) in backend::Quirks::default().flags() {
+        println!("  {:<20} {}", name, value);
+    }
+    println!();
+
+    println!("Supported opcodes ({}):", backend::OPCODE_COUNT);
+    for (pattern, description) in backend::OPCODES {
+        println!("  {:<6} {}", pattern, description);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use clap::{CommandFactory, FromArgMatches, Parser};
+    use dorustos::backend;
+    use dorustos::palette::Palette;
+    use dorustos::settings::GameSettings;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    #[test]
+    fn is_url_detects_http_addresses() {
+        assert!(super::is_url("https://example.com/roms/PONG.ch8"));
+        assert!(super::is_url("http://localhost:8000/PONG"));
+        assert!(!super::is_url("roms/PONG.ch8"));
+        assert!(!super::is_url("ftp://example.com/PONG"));
+    }
+
+    #[test]
+    fn parse_poke_reads_address_and_value() {
+        assert_eq!(super::parse_poke("0x300=0xAB"), Ok((0x300, 0xAB)));
+        assert_eq!(super::parse_poke("2a0=7"), Ok((0x2A0, 0x07)));
+        assert!(super::parse_poke("0x300").is_err());
+        assert!(super::parse_poke("0x1000=0x00").is_err());
+        assert!(super::parse_poke("0x300=0x100").is_err());
+    }
+
+    #[test]
+    fn parse_builtin_finds_embedded_roms() {
+        assert_eq!(super::parse_builtin("bounce").unwrap().name, "bounce");
+        assert!(super::parse_builtin("missing")
+            .unwrap_err()
+            .contains("available: bounce"));
+    }
+
+    #[test]
+    fn parse_aspect_reads_numbers_and_fractions() {
+        assert_eq!(super::parse_aspect("1"), Ok(1.0));
+        assert_eq!(super::parse_aspect("0.75"), Ok(0.75));
+        assert_eq!(super::parse_aspect("3/4"), Ok(0.75));
+        assert!(super::parse_aspect("wide").is_err());
+        assert!(super::parse_aspect("0").is_err());
+        assert!(super::parse_aspect("1/0").is_err());
+        assert!(super::parse_aspect("5").is_err());
+    }
+
+    #[test]
+    fn parse_scale_checks_range() {
+        assert_eq!(super::parse_scale("10"), Ok(10));
+        assert!(super::parse_scale("1").is_err());
+        assert!(super::parse_scale("big").is_err());
+    }
+
+    #[test]
+    fn explicit_flags_override_saved_settings() {
+        let saved = GameSettings {
+            tickrate: 20,
+            quirks: backend::Quirks {
+                shift_vy: true,
+                ..Default::default()
+            },
+            palette: Palette::default(),
+            scale: Some(8),
+        };
+        let given = GameSettings {
+            tickrate: 10,
+            quirks: backend::Quirks {
+                jump_vx: true,
+                ..Default::default()
+            },
+            palette: Palette::default(),
+            scale: Some(12),
+        };
+
+        let matches = super::Args::command().get_matches_from(["dorustos", "--jump-vx", "rom.ch8"]);
+        let (settings, overridden) = super::merge_settings(saved.clone(), given.clone(), &matches);
+        assert_eq!(settings.tickrate, 20);
+        assert!(settings.quirks.shift_vy);
+        assert!(settings.quirks.jump_vx);
+        assert_eq!(settings.scale, Some(8));
+        assert_eq!(overridden, ["jump_vx"]);
+
+        let matches =
+            super::Args::command().get_matches_from(["dorustos", "--scale", "12", "rom.ch8"]);
+        let (settings, overridden) = super::merge_settings(saved.clone(), given.clone(), &matches);
+        assert!(!settings.quirks.jump_vx);
+        assert_eq!(settings.scale, Some(12));
+        assert_eq!(overridden, ["scale"]);
+
+        let matches = super::Args::command().get_matches_from(["dorustos", "rom.ch8"]);
+        let unscaled = GameSettings {
+            scale: None,
+            ..saved.clone()
+        };
+        let (settings, overridden) = super::merge_settings(unscaled, given, &matches);
+        assert_eq!(settings.scale, Some(12));
+        assert!(overridden.is_empty());
+    }
+
+    #[test]
+    fn explicit_quirk_flags_override_known_rom() {
+        let rom = include_bytes!("../roms/bounce.ch8");
+        let argv = ["dorustos", "--auto-quirks", "--jump-vx", "rom.ch8"];
+        let matches = super::Args::command().get_matches_from(argv);
+        let args = super::Args::from_arg_matches(&matches).unwrap();
+
+        let (quirks, _, _) = super::rom_settings(&args.quirks, &matches, rom);
+        assert_eq!(
+            quirks,
+            backend::Quirks {
+                jump_vx: true,
+                ..backend::Quirks::DEFAULT
+            }
+        );
+    }
+
+    #[test]
+    fn same_seed_gives_same_random_numbers() {
+        let first_random = |argv: &[&str]| {
+            let args = super::Args::try_parse_from(argv).unwrap();
+            let mut chip8 = backend::Chip8::builder()
+                .seed(super::session_seed(args.seed))
+                .build();
+            // Sets V0 to a random byte
+            chip8.load(&[0xC0, 0xFF]).unwrap();
+            chip8.tick().unwrap();
+            chip8.register(0)
+        };
+
+        let argv = ["dorustos", "--seed", "1234", "rom.ch8"];
+        assert_eq!(first_random(&argv), first_random(&argv));
+        assert_eq!(super::session_seed(Some(1234)), 1234);
+    }
+
+    #[test]
+    fn parse_window_pos_reads_coordinates() {
+        assert_eq!(super::parse_window_pos("100,200"), Some((100, 200)));
+        assert_eq!(super::parse_window_pos("-1920, 0"), Some((-1920, 0)));
+        assert_eq!(super::parse_window_pos("100"), None);
+        assert_eq!(super::parse_window_pos("100,abc"), None);
+    }
+
+    #[test]
+    fn decompress_rom_inflates_gzipped_roms() {
+        let rom = vec![0x00, 0xE0, 0x12, 0x00];
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&rom).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        assert_eq!(super::decompress_rom(gzipped), Ok(rom.clone()));
+        assert_eq!(super::decompress_rom(rom.clone()), Ok(rom));
+    }
+
+    #[test]
+    fn decompress_rom_rejects_corrupt_gzip_data() {
+        let corrupt = vec![0x1F, 0x8B, 0x08, 0x00, 0xFF, 0xFF];
+        assert!(super::decompress_rom(corrupt).is_err());
+    }
+
+    #[test]
+    fn decompress_rom_rejects_roms_larger_than_ram() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&vec![0; 1 << 20]).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        assert!(super::decompress_rom(gzipped)
+            .unwrap_err()
+            .contains("too large"));
+    }
+
+    #[test]
+    fn swap_bytes_swaps_each_pair() {
+        let mut rom = [0xE0, 0x00, 0x00, 0x12];
+        assert_eq!(super::swap_bytes(&mut rom), Ok(()));
+        assert_eq!(rom, [0x00, 0xE0, 0x12, 0x00]);
+    }
+
+    #[test]
+    fn swap_bytes_rejects_odd_lengths() {
+        let mut rom = [0xE0, 0x00, 0x00];
+        assert!(super::swap_bytes(&mut rom).is_err());
+        assert_eq!(rom, [0xE0, 0x00, 0x00]);
+    }
 }
@@ -0,0 +1,17 @@
+/// Downloads a ROM over HTTP or HTTPS into memory
+///
+/// # Arguments
+///
+/// * `url` - Address of the ROM file
+pub fn download(url: &str) -> Result<Vec<u8>, String> {
+    let response = reqwest::blocking::get(url)
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| err.to_string())?;
+    let data = response.bytes().map_err(|err| err.to_string())?;
+
+    if data.is_empty() {
+        return Err("the server sent an empty file".to_string());
+    }
+
+    Ok(data.to_vec())
+}
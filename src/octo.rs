@@ -0,0 +1,162 @@
+use serde_json::{Map, Value};
+
+use crate::backend::Quirks;
+use crate::palette::{Palette, Rgb};
+
+/// Settings read from the options of an Octo cartridge
+#[derive(Clone, Debug, PartialEq)]
+pub struct OctoOptions {
+    /// Amount of instructions to run per frame, if given
+    pub tickrate: Option<usize>,
+    /// Compatibility settings, mapped from Octo's quirk flags
+    pub quirks: Quirks,
+    /// Colors of the screen, mapped from Octo's fill and background colors
+    pub palette: Palette,
+}
+
+/// Parses the options of an Octo cartridge, as exported by Octo in JSON
+/// (e.g. `{"tickrate": 20, "fillColor": "#FFCC00", "clipQuirks": true}`).
+///
+/// Quirk flags that are missing take Octo's default value (`false`), missing
+/// colors take the emulator's default palette, and any options without an
+/// equivalent in the emulator (like `screenRotation`) are ignored.
+///
+/// # Arguments
+///
+/// * `json` - The options to parse
+pub fn parse_options(json: &str) -> Result<OctoOptions, String> {
+    let value: Value =
+        serde_json::from_str(json).map_err(|err| format!("Invalid Octo options: {}", err))?;
+    let options = value
+        .as_object()
+        .ok_or("Invalid Octo options: expected a JSON object")?;
+
+    let tickrate = match options.get("tickrate") {
+        None => None,
+        Some(tickrate) => Some(
+            tickrate
+                .as_u64()
+                .filter(|tickrate| *tickrate > 0)
+                .ok_or(format!("Invalid tickrate: {}", tickrate))? as usize,
+        ),
+    };
+
+    // Octo's quirks are named after the behavior that differs from the
+    // original COSMAC VIP, which is the opposite of some of the emulator's flags
+    let quirks = Quirks {
+        shift_vy: !flag(options, "shiftQuirks")?,
+        load_store_increments_i: !flag(options, "loadStoreQuirks")?,
+        jump_vx: flag(options, "jumpQuirks")?,
+        vf_reset: flag(options, "logicQuirks")?,
+        clip_sprites: flag(options, "clipQuirks")?,
+        display_wait: flag(options, "vBlankQuirks")?,
+        vf_result_last: flag(options, "vfOrderQuirks")?,
+        ..Default::default()
+    };
+
+    let default_palette = Palette::default();
+    let palette = Palette {
+        foreground: color(options, "fillColor")?.unwrap_or(default_palette.foreground),
+        background: color(options, "backgroundColor")?.unwrap_or(default_palette.background),
+    };
+
+    Ok(OctoOptions {
+        tickrate,
+        quirks,
+        palette,
+    })
+}
+
+/// Returns the value of a boolean option, or `false` if it's missing
+///
+/// # Arguments
+///
+/// * `options` - The parsed options
+/// * `name` - Name of the option
+fn flag(options: &Map<String, Value>, name: &str) -> Result<bool, String> {
+    match options.get(name) {
+        None => Ok(false),
+        Some(value) => value
+            .as_bool()
+            .ok_or(format!("Invalid value for {}: {}", name, value)),
+    }
+}
+
+/// Returns the value of a color option, or `None` if it's missing
+///
+/// # Arguments
+///
+/// * `options` - The parsed options
+/// * `name` - Name of the option
+fn color(options: &Map<String, Value>, name: &str) -> Result<Option<Rgb>, String> {
+    match options.get(name) {
+        None => Ok(None),
+        Some(value) => value
+            .as_str()
+            .ok_or(format!("Invalid value for {}: {}", name, value))
+            .and_then(Rgb::from_hex)
+            .map(Some),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_options_maps_octo_cartridge_options() {
+        // Options of an Octo cartridge, as exported by Octo
+        let json = r##"{
+            "tickrate": 20,
+            "fillColor": "#FFCC00",
+            "fillColor2": "#FF6600",
+            "blendColor": "#662200",
+            "backgroundColor": "#996600",
+            "buzzColor": "#FFAA00",
+            "quietColor": "#000000",
+            "shiftQuirks": true,
+            "loadStoreQuirks": false,
+            "vfOrderQuirks": false,
+            "clipQuirks": true,
+            "vBlankQuirks": false,
+            "jumpQuirks": true,
+            "logicQuirks": false,
+            "screenRotation": 0,
+            "maxSize": 3584,
+            "touchInputMode": "none",
+            "fontStyle": "octo"
+        }"##;
+
+        let options = parse_options(json).unwrap();
+        assert_eq!(options.tickrate, Some(20));
+        assert_eq!(options.palette.foreground, Rgb(0xFF, 0xCC, 0x00));
+        assert_eq!(options.palette.background, Rgb(0x99, 0x66, 0x00));
+        assert_eq!(
+            options.quirks,
+            Quirks {
+                shift_vy: false,
+                load_store_increments_i: true,
+                jump_vx: true,
+                clip_sprites: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_options_uses_defaults_for_missing_options() {
+        let options = parse_options("{}").unwrap();
+        assert_eq!(options.tickrate, None);
+        assert_eq!(options.palette, Palette::default());
+        assert!(options.quirks.shift_vy);
+        assert!(options.quirks.load_store_increments_i);
+    }
+
+    #[test]
+    fn parse_options_rejects_invalid_options() {
+        assert!(parse_options("[]").is_err());
+        assert!(parse_options(r#"{"tickrate": 0}"#).is_err());
+        assert!(parse_options(r#"{"clipQuirks": "yes"}"#).is_err());
+        assert!(parse_options(r#"{"fillColor": "yellow"}"#).is_err());
+    }
+}
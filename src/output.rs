@@ -0,0 +1,92 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Logger used by the whole program. Errors and warnings are printed to the
+/// standard error with a prefix, and every other message is printed as is
+/// to the standard output.
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        match prefix(record.level()) {
+            Some(prefix) => eprintln!("{}: {}", prefix, record.args()),
+            None => println!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Returns the prefix printed before messages of the given level, if any.
+/// Only messages with a prefix go to the standard error.
+///
+/// # Arguments
+///
+/// * `level` - The level of the message
+fn prefix(level: Level) -> Option<&'static str> {
+    match level {
+        Level::Error => Some("ERROR"),
+        Level::Warn => Some("WARNING"),
+        Level::Info | Level::Debug | Level::Trace => None,
+    }
+}
+
+/// Returns the log level to use, given the command line options
+///
+/// # Arguments
+///
+/// * `log_level` - The log level given explicitly, if any
+/// * `quiet` - Whether to print nothing but errors
+/// * `verbose` - Whether to print extra diagnostics
+pub fn level_filter(log_level: Option<LevelFilter>, quiet: bool, verbose: bool) -> LevelFilter {
+    match log_level {
+        Some(level) => level,
+        None if quiet => LevelFilter::Error,
+        None if verbose => LevelFilter::Debug,
+        None => LevelFilter::Info,
+    }
+}
+
+/// Installs the program's logger, printing messages up to the given level
+///
+/// # Arguments
+///
+/// * `level` - The most detailed level of messages to print
+pub fn init(level: LevelFilter) {
+    if log::set_logger(&Logger).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, LevelFilter};
+
+    use super::{level_filter, prefix};
+
+    #[test]
+    fn level_filter_prefers_explicit_level() {
+        assert_eq!(level_filter(None, false, false), LevelFilter::Info);
+        assert_eq!(level_filter(None, true, false), LevelFilter::Error);
+        assert_eq!(level_filter(None, false, true), LevelFilter::Debug);
+        assert_eq!(
+            level_filter(Some(LevelFilter::Trace), true, false),
+            LevelFilter::Trace
+        );
+    }
+
+    #[test]
+    fn only_errors_and_warnings_are_prefixed() {
+        assert_eq!(prefix(Level::Error), Some("ERROR"));
+        assert_eq!(prefix(Level::Warn), Some("WARNING"));
+        assert_eq!(prefix(Level::Info), None);
+        assert_eq!(prefix(Level::Debug), None);
+    }
+}
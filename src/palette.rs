@@ -0,0 +1,212 @@
+use std::fmt;
+
+/// A color, given by its red, green and blue components
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Parses a color in the `#RRGGBB` hexadecimal notation (the `#` is optional)
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The color to parse
+    pub fn from_hex(text: &str) -> Result<Self, String> {
+        let digits = text.strip_prefix('#').unwrap_or(text);
+        if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid color: {}", text));
+        }
+
+        let value = u32::from_str_radix(digits, 16).unwrap();
+
+        Ok(Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8))
+    }
+
+    /// Returns a color between this one and another
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The color to mix with
+    /// * `amount` - How much of `other` to use, between 0 and 1
+    pub fn mix(self, other: Rgb, amount: f32) -> Self {
+        let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+
+        Rgb(
+            channel(self.0, other.0),
+            channel(self.1, other.1),
+            channel(self.2, other.2),
+        )
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02X}{:02X}{:02X}", self.0, self.1, self.2)
+    }
+}
+
+/// Colors used to draw the screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    /// Color of "on" pixels
+    pub foreground: Rgb,
+    /// Color of "off" pixels
+    pub background: Rgb,
+}
+
+impl Palette {
+    /// Parses a palette file, either in the GIMP palette format (`.gpl`) or
+    /// as a list of `#RRGGBB` colors, one per line. The first color is the
+    /// background and the second one the foreground. Any further colors
+    /// (e.g. for XO-CHIP's extra planes) are ignored, as only two are used.
+    ///
+    /// In both formats, empty lines and lines starting with `#` (other than
+    /// colors) are ignored. As a comment can look like a color (e.g.
+    /// `#Facade`), a line starting with `#` is only a color if it's exactly
+    /// `#RRGGBB`, with its letters either all uppercase or all lowercase.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The contents of the palette file
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().map(str::trim).peekable();
+        let gimp = lines.peek() == Some(&"GIMP Palette");
+        if gimp {
+            lines.next();
+        }
+
+        let mut colors = Vec::new();
+        for line in lines {
+            if line.is_empty() {
+                continue;
+            }
+
+            if gimp {
+                if line.starts_with('#') || line.contains(':') {
+                    continue;
+                }
+                colors.push(parse_gimp_color(line)?);
+            } else {
+                if line.starts_with('#') && !is_hex_color(line) {
+                    continue;
+                }
+                colors.push(Rgb::from_hex(line)?);
+            }
+        }
+
+        match colors[..] {
+            [background, foreground, ..] => Ok(Self {
+                foreground,
+                background,
+            }),
+            _ => Err(format!(
+                "The palette needs at least 2 colors (background and foreground), found {}",
+                colors.len()
+            )),
+        }
+    }
+}
+
+/// Returns whether a line of a palette is a `#RRGGBB` color rather than a
+/// comment: exactly 6 hexadecimal digits after the `#`, with the letters
+/// among them in a single case
+///
+/// # Arguments
+///
+/// * `line` - The line to check
+fn is_hex_color(line: &str) -> bool {
+    let digits = match line.strip_prefix('#') {
+        Some(digits) if digits.len() == 6 => digits,
+        _ => return false,
+    };
+
+    digits.chars().all(|c| c.is_ascii_hexdigit())
+        && (digits == digits.to_ascii_uppercase() || digits == digits.to_ascii_lowercase())
+}
+
+/// Parses a color from a line of a GIMP palette, given by its red, green and
+/// blue components in decimal followed by an optional name
+///
+/// # Arguments
+///
+/// * `line` - The line to parse
+fn parse_gimp_color(line: &str) -> Result<Rgb, String> {
+    let mut components = line
+        .split_whitespace()
+        .map(|component| component.parse::<u8>());
+
+    match (components.next(), components.next(), components.next()) {
+        (Some(Ok(r)), Some(Ok(g)), Some(Ok(b))) => Ok(Rgb(r, g, b)),
+        _ => Err(format!("Invalid color: {}", line)),
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            foreground: Rgb(255, 255, 255),
+            background: Rgb(0, 0, 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Palette, Rgb};
+
+    #[test]
+    fn palette_parses_gimp_palettes() {
+        let text = "GIMP Palette\nName: Amber\nColumns: 2\n#\n 20  10   0\tBackground\n255 176   0\tForeground\n 0 0 0\n";
+        let palette = Palette::parse(text).unwrap();
+
+        assert_eq!(palette.background, Rgb(20, 10, 0));
+        assert_eq!(palette.foreground, Rgb(255, 176, 0));
+        assert!(Palette::parse("GIMP Palette\n0 0 0\n256 0 0\n").is_err());
+    }
+
+    #[test]
+    fn palette_parses_hex_lists() {
+        let palette = Palette::parse("# My palette\n\n#000000\n996600\n").unwrap();
+
+        assert_eq!(palette.background, Rgb(0, 0, 0));
+        assert_eq!(palette.foreground, Rgb(0x99, 0x66, 0x00));
+
+        // Comments that look like colors are skipped
+        let palette = Palette::parse("#Facade\n#Bead\n#000000\n#ffcc00\n").unwrap();
+        assert_eq!(palette.background, Rgb(0, 0, 0));
+        assert_eq!(palette.foreground, Rgb(0xFF, 0xCC, 0x00));
+        assert!(Palette::parse("#000000\nnot a color\n").is_err());
+    }
+
+    #[test]
+    fn palette_needs_two_colors() {
+        assert!(Palette::parse("#000000\n").is_err());
+        assert!(Palette::parse("GIMP Palette\nName: Empty\n").is_err());
+    }
+
+    #[test]
+    fn rgb_parses_hex_colors() {
+        assert_eq!(Rgb::from_hex("#FFCC00"), Ok(Rgb(0xFF, 0xCC, 0x00)));
+        assert_eq!(Rgb::from_hex("996600"), Ok(Rgb(0x99, 0x66, 0x00)));
+        assert!(Rgb::from_hex("#FFF").is_err());
+        assert!(Rgb::from_hex("#GGGGGG").is_err());
+        assert!(Rgb::from_hex("#+FFFFF").is_err());
+    }
+
+    #[test]
+    fn rgb_mixes_colors() {
+        let black = Rgb(0, 0, 0);
+        let white = Rgb(255, 255, 255);
+
+        assert_eq!(black.mix(white, 0.0), black);
+        assert_eq!(black.mix(white, 1.0), white);
+        assert_eq!(
+            Rgb(0, 100, 200).mix(Rgb(100, 0, 200), 0.5),
+            Rgb(50, 50, 200)
+        );
+    }
+
+    #[test]
+    fn rgb_displays_as_hex() {
+        assert_eq!(Rgb(0xFF, 0xCC, 0x00).to_string(), "#FFCC00");
+    }
+}
@@ -0,0 +1,174 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use gif::{Encoder, EncodingError, Frame, Repeat};
+
+use dorustos::backend::{Chip8, SCREEN_HEIGHT, SCREEN_WIDTH};
+use dorustos::palette::Palette;
+
+/// Scaling factor applied to the display in recorded GIFs
+const GIF_SCALE: usize = 4;
+
+/// Records the display of a Chip-8 emulator instance into an animated GIF
+pub struct GifRecorder {
+    path: PathBuf,
+    encoder: Encoder<File>,
+    frames_left: u64,
+    every: u64,
+    frame: u64,
+}
+
+impl GifRecorder {
+    /// Creates the GIF file and returns a recorder that will write to it
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the GIF file to create
+    /// * `frames` - Amount of emulator frames to record for
+    /// * `every` - Keep one out of every `every` frames (at least 1), to control
+    ///   the file size
+    /// * `palette` - Colors of the display, used for "off" pixels (index 0)
+    ///   and "on" pixels (index 1)
+    pub fn create(
+        path: &Path,
+        frames: u64,
+        every: u64,
+        palette: &Palette,
+    ) -> Result<Self, EncodingError> {
+        let file = File::create(path)?;
+        let (off, on) = (palette.background, palette.foreground);
+        let mut encoder = Encoder::new(
+            file,
+            (SCREEN_WIDTH * GIF_SCALE) as u16,
+            (SCREEN_HEIGHT * GIF_SCALE) as u16,
+            &[off.0, off.1, off.2, on.0, on.1, on.2],
+        )?;
+        encoder.set_repeat(Repeat::Infinite)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            encoder,
+            frames_left: frames,
+            every: every.max(1),
+            frame: 0,
+        })
+    }
+
+    /// Returns the path of the GIF file being recorded
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns whether the recorder still has frames left to record
+    pub fn is_recording(&self) -> bool {
+        self.frames_left > 0
+    }
+
+    /// Records the current display of the emulator, if this frame is sampled
+    /// and there are frames left to record. Should be called once per frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `chip8` - Chip-8 emulator instance
+    pub fn capture(&mut self, chip8: &Chip8) -> Result<(), EncodingError> {
+        if !self.is_recording() {
+            return Ok(());
+        }
+
+        if self.frame.is_multiple_of(self.every) {
+            // Hires displays are scaled less, so that every frame has the
+            // same size
+            let (width, _) = chip8.display_dimensions();
+            let scale = SCREEN_WIDTH * GIF_SCALE / width;
+            let pixels = to_indexed(chip8.get_display(), width, scale);
+            let frame = Frame {
+                width: (SCREEN_WIDTH * GIF_SCALE) as u16,
+                height: (SCREEN_HEIGHT * GIF_SCALE) as u16,
+                delay: (elapsed(self.frame + self.every) - elapsed(self.frame)) as u16,
+                buffer: Cow::Owned(pixels),
+                ..Default::default()
+            };
+            self.encoder.write_frame(&frame)?;
+        }
+
+        self.frame += 1;
+        self.frames_left -= 1;
+        Ok(())
+    }
+}
+
+/// Returns the time elapsed before the given frame, in the 1/100ths of a
+/// second GIF delays are given in. Frames last 1/60th of a second, so the
+/// delays of consecutive GIF frames are rounded differently (e.g. 1, 2 and
+/// 2 hundredths for every frame) to keep the recording at the right speed.
+///
+/// # Arguments
+///
+/// * `frame` - Number of the emulator frame
+fn elapsed(frame: u64) -> u64 {
+    frame * 100 / 60
+}
+
+/// Converts a screen buffer into a scaled-up buffer of palette indices,
+/// one byte per pixel (0 for "off" pixels, 1 for "on" pixels)
+///
+/// # Arguments
+///
+/// * `display` - Screen buffer to convert
+/// * `display_width` - Width of the screen buffer in pixels
+/// * `scale` - Scaling factor to apply
+fn to_indexed(display: &[bool], display_width: usize, scale: usize) -> Vec<u8> {
+    let width = display_width * scale;
+    let mut pixels = vec![0; display.len() * scale * scale];
+
+    for (i, pixel) in display.iter().enumerate() {
+        if *pixel {
+            let x = (i % display_width) * scale;
+            let y = (i / display_width) * scale;
+
+            for row in y..y + scale {
+                pixels[row * width + x..row * width + x + scale].fill(1);
+            }
+        }
+    }
+
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use dorustos::backend::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    #[test]
+    fn to_indexed_scales_display() {
+        let mut display = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        display[1] = true;
+        display[SCREEN_WIDTH * SCREEN_HEIGHT - 1] = true;
+
+        let pixels = super::to_indexed(&display, SCREEN_WIDTH, 2);
+        let width = SCREEN_WIDTH * 2;
+
+        assert_eq!(pixels.len(), width * SCREEN_HEIGHT * 2);
+        assert_eq!(&pixels[..4], &[0, 0, 1, 1]);
+        assert_eq!(&pixels[width..width + 4], &[0, 0, 1, 1]);
+        assert_eq!(&pixels[2 * width..2 * width + 4], &[0, 0, 0, 0]);
+        assert_eq!(pixels[pixels.len() - 1], 1);
+        assert_eq!(pixels.iter().filter(|pixel| **pixel == 1).count(), 8);
+    }
+
+    #[test]
+    fn frame_delays_add_up_to_real_time() {
+        let delays: Vec<u64> = (0..6)
+            .map(|frame| super::elapsed(frame + 1) - super::elapsed(frame))
+            .collect();
+        assert_eq!(delays, [1, 2, 2, 1, 2, 2]);
+
+        let delays: Vec<u64> = (0..60)
+            .step_by(2)
+            .map(|frame| super::elapsed(frame + 2) - super::elapsed(frame))
+            .collect();
+        assert!(delays.iter().all(|delay| *delay == 3 || *delay == 4));
+        assert_eq!(delays.iter().sum::<u64>(), 100);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::headless::{self, format_registers};
+use dorustos::backend::{Chip8, Chip8Error};
+
+/// A keypad event read from an input log, as written by `--log-input`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputEvent {
+    /// Frame the event happened on
+    pub frame: u64,
+    /// Index of the key in the keypad
+    pub key: usize,
+    /// Whether the key was pressed or released
+    pub pressed: bool,
+}
+
+/// The first frame where two replays of the same program stopped matching
+pub struct Divergence {
+    /// Frame the replays diverged on
+    pub frame: u64,
+    /// State of the machine replaying the first log
+    pub left: Chip8,
+    /// State of the machine replaying the second log
+    pub right: Chip8,
+    /// Error that stopped the machine replaying the first log, if any
+    pub left_error: Option<Chip8Error>,
+    /// Error that stopped the machine replaying the second log, if any
+    pub right_error: Option<Chip8Error>,
+}
+
+impl Divergence {
+    /// Prints the frame the replays diverged on, followed by the registers
+    /// and displays of both machines, side by side
+    pub fn print(&self) {
+        println!("Replays diverge at frame {}", self.frame);
+        println!("A: {}", format_registers(&self.left.snapshot()));
+        println!("B: {}", format_registers(&self.right.snapshot()));
+        if let Some(err) = &self.left_error {
+            println!("A stopped: {}", err);
+        }
+        if let Some(err) = &self.right_error {
+            println!("B stopped: {}", err);
+        }
+
+        let (left_width, _) = self.left.display_dimensions();
+        let (right_width, _) = self.right.display_dimensions();
+        let left = self.left.get_display().chunks(left_width);
+        let right = self.right.get_display().chunks(right_width);
+        for (left_row, right_row) in left.zip(right) {
+            println!("{} | {}", draw_row(left_row), draw_row(right_row));
+        }
+    }
+}
+
+/// Returns a row of the display as text, with `#` for lit pixels
+///
+/// # Arguments
+///
+/// * `row` - Pixels of the row
+fn draw_row(row: &[bool]) -> String {
+    row.iter().map(|lit| if *lit { '#' } else { '.' }).collect()
+}
+
+/// Parses an input log, as written by `--log-input` (e.g.
+/// `[1.250s] frame 75: key A pressed`). Lines that aren't keypad events,
+/// like the other messages logged along with them, are ignored, and so is
+/// any prefix the logger puts before an event's timestamp.
+///
+/// # Arguments
+///
+/// * `text` - Contents of the log
+pub fn parse_log(text: &str) -> Result<Vec<InputEvent>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| line.contains("] frame "))
+        .map(|(idx, line)| {
+            line.find('[')
+                .and_then(|start| parse_event(&line[start..]))
+                .ok_or(format!("Invalid event on line {}: {}", idx + 1, line))
+        })
+        .collect()
+}
+
+/// Parses a single event of an input log
+///
+/// # Arguments
+///
+/// * `line` - The line of the log to parse
+fn parse_event(line: &str) -> Option<InputEvent> {
+    let (_, event) = line.split_once("] frame ")?;
+    let (frame, event) = event.split_once(": key ")?;
+    let (key, state) = event.split_once(' ')?;
+
+    let key = usize::from_str_radix(key, 16)
+        .ok()
+        .filter(|key| *key < 16)?;
+    let pressed = match state.trim() {
+        "pressed" => true,
+        "released" => false,
+        _ => return None,
+    };
+
+    Some(InputEvent {
+        frame: frame.parse().ok()?,
+        key,
+        pressed,
+    })
+}
+
+/// Replays two input logs against copies of the same machine, frame by
+/// frame, and returns the first frame after which their displays or
+/// registers differ, or only one of the machines stopped with an error (or
+/// both with different ones), if any. Runs stopping with the same error on
+/// the same frame haven't diverged, and aren't replayed any further. For
+/// the replays to be reproducible, the machine's random number generator
+/// should be seeded.
+///
+/// # Arguments
+///
+/// * `chip8` - Chip-8 emulator instance, with the program loaded
+/// * `left` - Events of the first log
+/// * `right` - Events of the second log
+/// * `frames` - Amount of frames to run for
+/// * `ticks_per_frame` - Amount of CPU ticks to run per frame
+pub fn find_divergence(
+    chip8: &Chip8,
+    left: &[InputEvent],
+    right: &[InputEvent],
+    frames: u64,
+    ticks_per_frame: usize,
+) -> Option<Divergence> {
+    let options = headless::Options {
+        frames: 1,
+        max_cycles: None,
+        realtime: false,
+        ticks_per_frame,
+    };
+    let mut machines = [chip8.clone(), chip8.clone()];
+
+    for frame in 0..frames {
+        let mut errors = [None, None];
+        for ((machine, events), error) in machines.iter_mut().zip([left, right]).zip(&mut errors) {
+            for event in events.iter().filter(|event| event.frame == frame) {
+                machine.keypress(event.key, event.pressed);
+            }
+            *error = headless::run_headless(machine, &options, None).error;
+        }
+
+        let [left, right] = &machines;
+        if errors[0] != errors[1]
+            || left.get_display() != right.get_display()
+            || format_registers(&left.snapshot()) != format_registers(&right.snapshot())
+        {
+            let [left, right] = machines;
+            let [left_error, right_error] = errors;
+            return Some(Divergence {
+                frame,
+                left,
+                right,
+                left_error,
+                right_error,
+            });
+        }
+        if errors[0].is_some() {
+            break;
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InputEvent;
+    use crate::frontend::TICKS_PER_FRAME;
+    use dorustos::backend::{Chip8, Chip8Error};
+
+    #[test]
+    fn parse_log_reads_events() {
+        let log = "[0.050s] frame 3: key A pressed\n\
+                   WARNING: something else\n\
+                   Loaded 132 bytes [0x200 to 0x284]\n\
+                   DEBUG: [0.100s] frame 6: key 1 released\n";
+
+        let events = super::parse_log(log).unwrap();
+        assert_eq!(
+            events,
+            vec![
+                InputEvent {
+                    frame: 3,
+                    key: 0xA,
+                    pressed: true
+                },
+                InputEvent {
+                    frame: 6,
+                    key: 0x1,
+                    pressed: false
+                },
+            ]
+        );
+
+        assert!(super::parse_log("[0.050s] frame 3: key G pressed").is_err());
+    }
+
+    #[test]
+    fn find_divergence_reports_first_differing_frame() {
+        // Waits for a key, then draws its font sprite, forever
+        let mut chip8 = Chip8::builder().seed(0).build();
+        chip8
+            .load(&[0xF0, 0x0A, 0xF0, 0x29, 0x00, 0xE0, 0xD1, 0x15, 0x12, 0x00])
+            .unwrap();
+
+        let press = |frame, key, pressed| InputEvent {
+            frame,
+            key,
+            pressed,
+        };
+        let left = [press(3, 1, true)];
+        let right = [press(3, 1, true), press(5, 1, false), press(5, 2, true)];
+
+        let divergence = super::find_divergence(&chip8, &left, &right, 10, TICKS_PER_FRAME);
+        let divergence = divergence.unwrap();
+        assert_eq!(divergence.frame, 5);
+        assert_eq!(divergence.left.register(0), 1);
+        assert_eq!(divergence.right.register(0), 2);
+
+        assert!(super::find_divergence(&chip8, &left, &left, 10, TICKS_PER_FRAME).is_none());
+    }
+
+    #[test]
+    fn find_divergence_reports_execution_errors() {
+        // Waits for a key, then runs an invalid opcode
+        let mut chip8 = Chip8::builder().seed(0).build();
+        chip8.load(&[0xF0, 0x0A, 0xFF, 0xFF]).unwrap();
+
+        let press = |frame, key, pressed| InputEvent {
+            frame,
+            key,
+            pressed,
+        };
+        let left = [press(2, 1, true)];
+
+        let divergence = super::find_divergence(&chip8, &left, &[], 10, TICKS_PER_FRAME);
+        let divergence = divergence.unwrap();
+        assert_eq!(divergence.frame, 2);
+        assert_eq!(
+            divergence.left_error,
+            Some(Chip8Error::UnsupportedOpcode(0xFFFF))
+        );
+        assert_eq!(divergence.right_error, None);
+
+        // Both runs stop the same way, which isn't a divergence
+        assert!(super::find_divergence(&chip8, &left, &left, 10, TICKS_PER_FRAME).is_none());
+    }
+}
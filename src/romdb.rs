@@ -0,0 +1,124 @@
+use crate::backend::Quirks;
+
+/// A well-known ROM, along with the compatibility settings it needs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KnownRom {
+    /// Name of the ROM
+    pub name: &'static str,
+    /// Hash of the ROM's contents, as returned by [`rom_hash`]
+    pub hash: u64,
+    /// Compatibility settings the ROM needs to run correctly
+    pub quirks: Quirks,
+}
+
+/// Built-in table of well-known ROMs.
+///
+/// Entries are keyed by the hash of the exact ROM file, which can be printed
+/// by running the emulator with `--print-hash`.
+pub const KNOWN_ROMS: &[KnownRom] = &[
+    // roms/bounce.ch8, also embedded as the `bounce` built-in ROM
+    KnownRom {
+        name: "BOUNCE",
+        hash: 0x9451_9414_53AA_07A7,
+        quirks: Quirks::DEFAULT,
+    },
+    // roms/opcodes.ch8, the test ROM run by `dorustos test`
+    KnownRom {
+        name: "OPCODES",
+        hash: 0x56D5_20D2_5EAE_586C,
+        quirks: Quirks::DEFAULT,
+    },
+];
+
+/// FNV-1a offset basis (64 bits)
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+
+/// FNV-1a prime (64 bits)
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+/// Returns the 64-bit FNV-1a hash of a ROM's contents
+///
+/// # Arguments
+///
+/// * `data` - The contents of the ROM
+pub fn rom_hash(data: &[u8]) -> u64 {
+    data.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Returns the entry of the built-in table matching a ROM, if it's a
+/// well-known one
+///
+/// # Arguments
+///
+/// * `data` - The contents of the ROM
+pub fn lookup(data: &[u8]) -> Option<&'static KnownRom> {
+    find(KNOWN_ROMS, rom_hash(data))
+}
+
+/// Returns the entry of a table with the given hash, if any
+///
+/// # Arguments
+///
+/// * `roms` - Table of known ROMs to search
+/// * `hash` - Hash of the ROM to find
+fn find(roms: &[KnownRom], hash: u64) -> Option<&KnownRom> {
+    roms.iter().find(|rom| rom.hash == hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rom_hash_matches_fnv1a() {
+        assert_eq!(rom_hash(&[]), 0xCBF2_9CE4_8422_2325);
+        assert_eq!(rom_hash(b"a"), 0xAF63_DC4C_8601_EC8C);
+    }
+
+    #[test]
+    fn lookup_recognizes_bundled_rom() {
+        let data = include_bytes!("../roms/bounce.ch8");
+        let known = lookup(data).unwrap();
+        assert_eq!(known.name, "BOUNCE");
+        assert_eq!(known.hash, rom_hash(data));
+        assert_eq!(known.quirks, Quirks::default());
+
+        assert!(lookup(&data[1..]).is_none());
+    }
+
+    #[test]
+    fn known_rom_hashes_are_unique() {
+        for (i, rom) in KNOWN_ROMS.iter().enumerate() {
+            assert!(find(&KNOWN_ROMS[..i], rom.hash).is_none(), "{}", rom.name);
+        }
+    }
+
+    #[test]
+    fn lookup_recognizes_bundled_test_rom() {
+        let data = include_bytes!("../roms/opcodes.ch8");
+        assert_eq!(lookup(data).unwrap().name, "OPCODES");
+    }
+
+    #[test]
+    fn find_resolves_known_hash_to_quirks() {
+        let rom = [0x00, 0xE0, 0x12, 0x00];
+        let quirks = Quirks {
+            shift_vy: true,
+            vf_reset: true,
+            ..Default::default()
+        };
+        let roms = [KnownRom {
+            name: "TEST",
+            hash: rom_hash(&rom),
+            quirks,
+        }];
+
+        let found = find(&roms, rom_hash(&rom)).unwrap();
+        assert_eq!(found.name, "TEST");
+        assert_eq!(found.quirks, quirks);
+
+        assert!(find(&roms, rom_hash(&[0x12, 0x00])).is_none());
+    }
+}
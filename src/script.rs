@@ -0,0 +1,280 @@
+use std::fmt;
+
+use dorustos::backend::{Chip8, NUM_KEYS, NUM_REGS};
+
+/// A value of the machine's state that a script can check
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    /// One of the V0 to VF registers
+    Register(usize),
+    /// The I register
+    I,
+    /// The program counter
+    Pc,
+    /// The delay timer
+    Delay,
+    /// The sound timer
+    Sound,
+}
+
+impl Target {
+    /// Returns the current value of the target in the given machine
+    ///
+    /// # Arguments
+    ///
+    /// * `chip8` - Chip-8 emulator instance
+    fn read(&self, chip8: &Chip8) -> u16 {
+        match *self {
+            Target::Register(idx) => chip8.register(idx) as u16,
+            Target::I => chip8.snapshot().i_reg,
+            Target::Pc => chip8.pc(),
+            Target::Delay => chip8.delay_timer() as u16,
+            Target::Sound => chip8.sound_timer() as u16,
+        }
+    }
+}
+
+/// Formats the target with the name scripts use for it (e.g. `v1`)
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Register(idx) => write!(f, "v{:x}", idx),
+            Target::I => write!(f, "i"),
+            Target::Pc => write!(f, "pc"),
+            Target::Delay => write!(f, "dt"),
+            Target::Sound => write!(f, "st"),
+        }
+    }
+}
+
+/// A command of a script
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    /// Executes the given amount of instructions, without advancing the timers
+    Tick(u64),
+    /// Presses or releases the given key
+    Key(usize, bool),
+    /// Runs the given amount of whole frames, timers included
+    Wait(u64),
+    /// Checks that a value of the machine's state is the given one
+    Assert(Target, u16),
+}
+
+/// A command of a script, along with the line it was read from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Step {
+    /// Line of the script the command is on (starting at 1)
+    pub line: usize,
+    /// The command to run
+    pub command: Command,
+}
+
+/// Parses a script, with one command per line:
+///
+/// * `tick <n>`: execute `n` instructions
+/// * `key <key> down|up`: press or release a key (in hexadecimal)
+/// * `wait <n>frames`: run `n` whole frames, timers included
+/// * `assert <target> <value>`: check that `v0` to `vf`, `i`, `pc`, `dt` or
+///   `st` holds a value (in decimal, or hexadecimal with a `0x` prefix)
+///
+/// Empty lines and lines starting with `#` are ignored.
+///
+/// # Arguments
+///
+/// * `text` - Contents of the script
+pub fn parse_script(text: &str) -> Result<Vec<Step>, String> {
+    text.lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line, text)| {
+            parse_command(text)
+                .map(|command| Step { line, command })
+                .map_err(|err| format!("Line {}: {}", line, err))
+        })
+        .collect()
+}
+
+/// Parses a single command of a script
+///
+/// # Arguments
+///
+/// * `line` - The line to parse
+fn parse_command(line: &str) -> Result<Command, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+
+    match words.as_slice() {
+        ["tick", count] => parse_number(count).map(|count| Command::Tick(count as u64)),
+        ["key", key, state] => {
+            let key = usize::from_str_radix(key, 16)
+                .ok()
+                .filter(|key| *key < NUM_KEYS)
+                .ok_or(format!("Invalid key: {}", key))?;
+            match *state {
+                "down" => Ok(Command::Key(key, true)),
+                "up" => Ok(Command::Key(key, false)),
+                _ => Err(format!(
+                    "Invalid key state: {} (expected down or up)",
+                    state
+                )),
+            }
+        }
+        ["wait", frames] => {
+            let count = frames
+                .strip_suffix("frames")
+                .ok_or(format!("Invalid wait: {} (expected e.g. 10frames)", frames))?;
+            parse_number(count).map(|count| Command::Wait(count as u64))
+        }
+        ["assert", target, value] => {
+            let target = parse_target(target)?;
+            let value = parse_number(value)?;
+            let value = u16::try_from(value).map_err(|_| format!("Invalid value: {}", value))?;
+            Ok(Command::Assert(target, value))
+        }
+        _ => Err(format!("Unknown command: {}", line)),
+    }
+}
+
+/// Parses the name of a value of the machine's state (e.g. `v5` or `pc`)
+///
+/// # Arguments
+///
+/// * `name` - The name to parse
+fn parse_target(name: &str) -> Result<Target, String> {
+    match name.to_lowercase().as_str() {
+        "i" => Ok(Target::I),
+        "pc" => Ok(Target::Pc),
+        "dt" => Ok(Target::Delay),
+        "st" => Ok(Target::Sound),
+        register => register
+            .strip_prefix('v')
+            .and_then(|idx| usize::from_str_radix(idx, 16).ok())
+            .filter(|idx| *idx < NUM_REGS)
+            .map(Target::Register)
+            .ok_or(format!("Invalid target: {}", name)),
+    }
+}
+
+/// Parses a number, in decimal or in hexadecimal with a `0x` prefix
+///
+/// # Arguments
+///
+/// * `text` - The number to parse
+fn parse_number(text: &str) -> Result<u32, String> {
+    let result = match text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        Some(digits) => u32::from_str_radix(digits, 16),
+        None => text.parse(),
+    };
+    result.map_err(|_| format!("Invalid number: {}", text))
+}
+
+/// Runs a script against a machine with a program loaded, stopping at the
+/// first failed assertion or execution error
+///
+/// # Arguments
+///
+/// * `chip8` - Chip-8 emulator instance, with the program loaded
+/// * `steps` - Commands of the script
+/// * `ticks_per_frame` - Amount of CPU ticks to run per frame when waiting
+pub fn run_script(chip8: &mut Chip8, steps: &[Step], ticks_per_frame: usize) -> Result<(), String> {
+    for step in steps {
+        let fail = |err: String| format!("Line {}: {}", step.line, err);
+
+        match step.command {
+            Command::Tick(count) => {
+                for _ in 0..count {
+                    chip8.tick().map_err(|err| fail(err.to_string()))?;
+                }
+            }
+            Command::Key(key, pressed) => chip8.keypress(key, pressed),
+            Command::Wait(frames) => {
+                for _ in 0..frames {
+                    if let Some(err) = chip8.advance_frame(ticks_per_frame).error {
+                        return Err(fail(err.to_string()));
+                    }
+                }
+            }
+            Command::Assert(target, expected) => {
+                let actual = target.read(chip8);
+                if actual != expected {
+                    return Err(fail(format!(
+                        "expected {} to be {:#04X}, got {:#04X}",
+                        target, expected, actual
+                    )));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Target};
+    use crate::frontend::TICKS_PER_FRAME;
+    use dorustos::backend::Chip8;
+
+    #[test]
+    fn parse_script_reads_commands() {
+        let script = "# Press a key\n\
+                      tick 100\n\
+                      key A down\n\
+                      \n\
+                      wait 10frames\n\
+                      assert v0 0x1F\n\
+                      assert pc 512\n";
+
+        let steps = super::parse_script(script).unwrap();
+        let commands: Vec<(usize, Command)> =
+            steps.iter().map(|step| (step.line, step.command)).collect();
+        assert_eq!(
+            commands,
+            vec![
+                (2, Command::Tick(100)),
+                (3, Command::Key(0xA, true)),
+                (5, Command::Wait(10)),
+                (6, Command::Assert(Target::Register(0), 0x1F)),
+                (7, Command::Assert(Target::Pc, 0x200)),
+            ]
+        );
+
+        let err = super::parse_script("tick 1\nkey 10 down").unwrap_err();
+        assert_eq!(err, "Line 2: Invalid key: 10");
+        assert!(super::parse_script("wait 10").is_err());
+        assert!(super::parse_script("assert vg 1").is_err());
+        assert!(super::parse_script("jump").is_err());
+    }
+
+    #[test]
+    fn target_displays_script_name() {
+        for name in ["v0", "va", "i", "pc", "dt", "st"] {
+            assert_eq!(super::parse_target(name).unwrap().to_string(), name);
+        }
+    }
+
+    #[test]
+    fn run_script_drives_machine() {
+        // Waits for a key and stores it in V0, then counts up in V1 forever
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0xF0, 0x0A, 0x71, 0x01, 0x12, 0x02]).unwrap();
+
+        let script = "tick 5\n\
+                      assert pc 0x200\n\
+                      key 7 down\n\
+                      tick 1\n\
+                      key 7 up\n\
+                      assert v0 7\n\
+                      tick 10\n\
+                      assert v1 5\n";
+        let steps = super::parse_script(script).unwrap();
+        assert_eq!(
+            super::run_script(&mut chip8, &steps, TICKS_PER_FRAME),
+            Ok(())
+        );
+
+        let steps = super::parse_script("wait 1frames\nassert v1 0").unwrap();
+        let err = super::run_script(&mut chip8, &steps, TICKS_PER_FRAME).unwrap_err();
+        assert_eq!(err, "Line 2: expected v1 to be 0x00, got 0x0A");
+    }
+}
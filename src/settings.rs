@@ -0,0 +1,224 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use crate::backend::Quirks;
+use crate::palette::{Palette, Rgb};
+
+/// Settings remembered for a single game, so that it runs the same way the
+/// next time it's loaded without passing the same flags again
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameSettings {
+    /// Amount of instructions to run per frame
+    pub tickrate: usize,
+    /// Compatibility settings
+    pub quirks: Quirks,
+    /// Colors of the screen
+    pub palette: Palette,
+    /// Size of each pixel of the screen in the window when it opens, if
+    /// saved (settings saved by older versions don't have it)
+    pub scale: Option<u32>,
+}
+
+impl GameSettings {
+    /// Returns the path of the settings file of a game, named after the
+    /// hash of its ROM (e.g. `<dir>/games/0123456789abcdef.json`)
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Directory where the emulator keeps its settings
+    /// * `rom_hash` - Hash of the ROM, as returned by `romdb::rom_hash`
+    pub fn path(dir: &Path, rom_hash: u64) -> PathBuf {
+        dir.join("games").join(format!("{:016x}.json", rom_hash))
+    }
+
+    /// Parses the settings of a game, as written by `to_json`. Missing quirks
+    /// take their default value, and unknown ones are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - The settings to parse
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let value: Value =
+            serde_json::from_str(json).map_err(|err| format!("Invalid settings: {}", err))?;
+        let settings = value
+            .as_object()
+            .ok_or("Invalid settings: expected a JSON object")?;
+
+        let tickrate = settings
+            .get("tickrate")
+            .and_then(Value::as_u64)
+            .filter(|tickrate| *tickrate > 0)
+            .ok_or("Invalid settings: missing or invalid tickrate")?
+            as usize;
+
+        let mut quirks = Quirks::default();
+        if let Some(flags) = settings.get("quirks") {
+            let flags = flags
+                .as_object()
+                .ok_or("Invalid settings: expected quirks to be a JSON object")?;
+            for (name, value) in flags {
+                let value = value
+                    .as_bool()
+                    .ok_or(format!("Invalid value for quirk {}: {}", name, value))?;
+                quirks.set(name, value);
+            }
+        }
+
+        let palette = Palette {
+            foreground: color(settings, "foreground")?,
+            background: color(settings, "background")?,
+        };
+
+        let scale = match settings.get("scale") {
+            Some(scale) => Some(
+                scale
+                    .as_u64()
+                    .and_then(|scale| u32::try_from(scale).ok())
+                    .filter(|scale| *scale > 0)
+                    .ok_or("Invalid settings: invalid scale")?,
+            ),
+            None => None,
+        };
+
+        Ok(GameSettings {
+            tickrate,
+            quirks,
+            palette,
+            scale,
+        })
+    }
+
+    /// Returns the settings as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        let quirks: Map<String, Value> = self
+            .quirks
+            .flags()
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), Value::Bool(value)))
+            .collect();
+
+        let mut settings = Map::new();
+        settings.insert("tickrate".to_string(), Value::from(self.tickrate));
+        settings.insert("quirks".to_string(), Value::Object(quirks));
+        settings.insert(
+            "foreground".to_string(),
+            Value::String(self.palette.foreground.to_string()),
+        );
+        settings.insert(
+            "background".to_string(),
+            Value::String(self.palette.background.to_string()),
+        );
+        if let Some(scale) = self.scale {
+            settings.insert("scale".to_string(), Value::from(scale));
+        }
+
+        serde_json::to_string_pretty(&Value::Object(settings)).unwrap()
+    }
+
+    /// Reads the settings of a game from a file, returning `None` if the
+    /// game has no settings saved yet
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the settings file
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => Self::parse(&json).map(Some),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(format!("Couldn't read {}: {}", path.display(), err)),
+        }
+    }
+
+    /// Writes the settings of a game to a file, creating its directory if needed
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the settings file
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|err| format!("Couldn't create {}: {}", dir.display(), err))?;
+        }
+        std::fs::write(path, self.to_json())
+            .map_err(|err| format!("Couldn't write {}: {}", path.display(), err))
+    }
+}
+
+/// Returns the value of a color setting
+///
+/// # Arguments
+///
+/// * `settings` - The parsed settings
+/// * `name` - Name of the setting
+fn color(settings: &Map<String, Value>, name: &str) -> Result<Rgb, String> {
+    settings
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or(format!("Invalid settings: missing or invalid {}", name))
+        .and_then(Rgb::from_hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> GameSettings {
+        GameSettings {
+            tickrate: 30,
+            quirks: Quirks {
+                clip_sprites: true,
+                vf_reset: true,
+                ..Default::default()
+            },
+            palette: Palette {
+                foreground: Rgb(0xFF, 0xCC, 0x00),
+                background: Rgb(0x99, 0x66, 0x00),
+            },
+            scale: Some(10),
+        }
+    }
+
+    #[test]
+    fn settings_round_trip_through_json() {
+        let settings = settings();
+        assert_eq!(GameSettings::parse(&settings.to_json()), Ok(settings));
+    }
+
+    #[test]
+    fn settings_round_trip_through_file() {
+        let dir = std::env::temp_dir().join(format!("dorustos-settings-{}", std::process::id()));
+        let path = GameSettings::path(&dir, 0x0123_4567_89AB_CDEF);
+        assert!(path.ends_with("games/0123456789abcdef.json"));
+        assert_eq!(GameSettings::load(&path), Ok(None));
+
+        let settings = settings();
+        settings.save(&path).unwrap();
+        assert_eq!(GameSettings::load(&path), Ok(Some(settings)));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parse_rejects_invalid_settings() {
+        assert!(GameSettings::parse("[]").is_err());
+        assert!(GameSettings::parse(r##"{"foreground": "#FFFFFF"}"##).is_err());
+        assert!(GameSettings::parse(
+            r##"{"tickrate": 10, "quirks": {"wrap_pc": 1}, "foreground": "#FFFFFF", "background": "#000000"}"##
+        )
+        .is_err());
+        assert!(GameSettings::parse(
+            r##"{"tickrate": 10, "foreground": "#FFFFFF", "background": "#000000", "scale": 0}"##
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn parse_accepts_settings_without_scale() {
+        let settings = GameSettings::parse(
+            r##"{"tickrate": 10, "foreground": "#FFFFFF", "background": "#000000"}"##,
+        )
+        .unwrap();
+        assert_eq!(settings.scale, None);
+    }
+}
@@ -0,0 +1,255 @@
+use std::io::{stdout, Stdout, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    poll, read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, size, supports_keyboard_enhancement, Clear, ClearType,
+    EnterAlternateScreen, LeaveAlternateScreen,
+};
+use crossterm::{execute, queue};
+
+use crate::crash;
+use crate::frontend::FRAME_DURATION;
+use dorustos::backend::{Chip8, NUM_KEYS};
+
+/// Amount of frames a key stays pressed for, when the terminal can't report
+/// key releases
+const KEY_HOLD_FRAMES: u32 = 6;
+
+/// Restores the terminal to its original state when dropped, so that
+/// it's restored even if the emulator panics
+struct TerminalGuard {
+    enhanced_keyboard: bool,
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let mut out = stdout();
+        if self.enhanced_keyboard {
+            let _ = execute!(out, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(out, Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
+
+/// Runs a Chip-8 emulator instance with a loaded program in the terminal,
+/// drawing the screen with Unicode half-block characters and capturing
+/// the keypresses from the terminal.
+///
+/// # Arguments
+///
+/// * `chip8` - Chip-8 emulator instance
+/// * `ticks_per_frame` - Amount of CPU ticks to run per frame
+/// * `max_frame_time` - Longest time a single frame can advance the timers by
+pub fn run_game(
+    mut chip8: Chip8,
+    ticks_per_frame: usize,
+    max_frame_time: Duration,
+) -> std::io::Result<Chip8> {
+    let mut out = stdout();
+
+    enable_raw_mode()?;
+    let enhanced_keyboard = supports_keyboard_enhancement().unwrap_or(false);
+    let _guard = TerminalGuard { enhanced_keyboard };
+
+    execute!(out, EnterAlternateScreen, Hide, Clear(ClearType::All))?;
+    if enhanced_keyboard {
+        execute!(
+            out,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
+
+    // Frames left until each key is released, for terminals that can't
+    // report key releases
+    let mut held_frames = [0; NUM_KEYS];
+    let mut last_frame = Instant::now();
+
+    'gameloop: loop {
+        let frame_start = Instant::now();
+
+        while poll(Duration::ZERO)? {
+            match read()? {
+                Event::Key(KeyEvent {
+                    code: KeyCode::Esc, ..
+                })
+                | Event::Key(KeyEvent {
+                    code: KeyCode::Char('c'),
+                    modifiers: KeyModifiers::CONTROL,
+                    ..
+                }) => {
+                    break 'gameloop;
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(c),
+                    kind,
+                    ..
+                }) => {
+                    if let Some(btn) = char2btn(c) {
+                        match kind {
+                            KeyEventKind::Release => chip8.keypress(btn, false),
+                            _ => {
+                                chip8.keypress(btn, true);
+                                if !enhanced_keyboard {
+                                    held_frames[btn] = KEY_HOLD_FRAMES;
+                                }
+                            }
+                        }
+                    }
+                }
+                Event::Resize(..) => {
+                    queue!(out, Clear(ClearType::All))?;
+                }
+                _ => (),
+            }
+        }
+
+        for (btn, frames) in held_frames.iter_mut().enumerate() {
+            if *frames > 0 {
+                *frames -= 1;
+                if *frames == 0 {
+                    chip8.keypress(btn, false);
+                }
+            }
+        }
+
+        for _ in 0..ticks_per_frame {
+            if let Err(err) = chip8.tick() {
+                // Messages logged now would be lost with the alternate
+                // screen, so the dump's path is reported with the error
+                let path = crash::dump_path(chip8.rom_hash());
+                let message = match crash::write_dump(&chip8, &err, &path) {
+                    Ok(()) => format!("{} (crash dump written to {})", err, path.display()),
+                    Err(_) => err.to_string(),
+                };
+                return Err(std::io::Error::other(message));
+            }
+            if chip8.is_waiting_for_vblank() {
+                break;
+            }
+        }
+        let now = Instant::now();
+        chip8.tick_timers_dt((now - last_frame).min(max_frame_time));
+        last_frame = now;
+
+        draw_screen(&chip8, &mut out)?;
+        crash::remember(&chip8);
+
+        if let Some(remaining) = FRAME_DURATION.checked_sub(frame_start.elapsed()) {
+            thread::sleep(remaining);
+        }
+    }
+
+    Ok(chip8)
+}
+
+/// Draws the current screen buffer to the terminal, using one character
+/// for every two vertically adjacent pixels.
+///
+/// # Arguments
+///
+/// * `chip8` - Chip-8 emulator instance
+/// * `out` - Terminal output to draw to
+fn draw_screen(chip8: &Chip8, out: &mut Stdout) -> std::io::Result<()> {
+    let (cols, rows) = size()?;
+    let (width, height) = chip8.display_dimensions();
+    if (cols as usize) < width || (rows as usize) < height / 2 {
+        queue!(out, MoveTo(0, 0))?;
+        write!(
+            out,
+            "Terminal too small: resize to at least {}x{}",
+            width,
+            height / 2
+        )?;
+        return out.flush();
+    }
+
+    let screen_buf = chip8.get_display();
+
+    for row in 0..height / 2 {
+        let line: String = (0..width)
+            .map(|x| {
+                let top = screen_buf[x + width * (2 * row)];
+                let bottom = screen_buf[x + width * (2 * row + 1)];
+                half_block(top, bottom)
+            })
+            .collect();
+
+        queue!(out, MoveTo(0, row as u16))?;
+        write!(out, "{}", line)?;
+    }
+
+    out.flush()
+}
+
+/// Returns the character representing two vertically adjacent pixels.
+///
+/// # Arguments
+///
+/// * `top` - Whether the top pixel is on
+/// * `bottom` - Whether the bottom pixel is on
+fn half_block(top: bool, bottom: bool) -> char {
+    match (top, bottom) {
+        (true, true) => '█',
+        (true, false) => '▀',
+        (false, true) => '▄',
+        (false, false) => ' ',
+    }
+}
+
+/// Maps a typed character to the respective Chip-8 button, using the same
+/// layout as the SDL2 frontend (the left side of a standard QWERTY keyboard).
+///
+/// # Arguments
+///
+/// * `c` - Typed character to map
+fn char2btn(c: char) -> Option<usize> {
+    match c.to_ascii_lowercase() {
+        '1' => Some(0x1),
+        '2' => Some(0x2),
+        '3' => Some(0x3),
+        '4' => Some(0xC),
+        'q' => Some(0x4),
+        'w' => Some(0x5),
+        'e' => Some(0x6),
+        'r' => Some(0xD),
+        'a' => Some(0x7),
+        's' => Some(0x8),
+        'd' => Some(0x9),
+        'f' => Some(0xE),
+        'z' => Some(0xA),
+        'x' => Some(0x0),
+        'c' => Some(0xB),
+        'v' => Some(0xF),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn char2btn_maps_appropriately() {
+        let result_1 = super::char2btn('1');
+        assert_eq!(result_1, Some(0x1));
+
+        let result_2 = super::char2btn('W');
+        assert_eq!(result_2, Some(0x5));
+
+        let result_3 = super::char2btn('k');
+        assert_eq!(result_3, None);
+    }
+
+    #[test]
+    fn half_block_combines_pixels() {
+        assert_eq!(super::half_block(true, true), '█');
+        assert_eq!(super::half_block(true, false), '▀');
+        assert_eq!(super::half_block(false, true), '▄');
+        assert_eq!(super::half_block(false, false), ' ');
+    }
+}
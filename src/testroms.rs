@@ -0,0 +1,163 @@
+use crate::backend::{Chip8, LoadError, Quirks};
+use crate::romdb;
+
+/// A test ROM, along with how to run it and what its display should look
+/// like afterwards when the emulator behaves correctly
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TestRom {
+    /// Name of the test ROM
+    pub name: &'static str,
+    /// Hash of the ROM's contents, as returned by [`romdb::rom_hash`]
+    pub hash: u64,
+    /// Compatibility settings to run the ROM with
+    pub quirks: Quirks,
+    /// Amount of CPU ticks to run per frame
+    pub ticks_per_frame: usize,
+    /// Amount of frames to run before checking the display
+    pub frames: usize,
+    /// Hash of the display of a passing run, as returned by [`display_hash`]
+    pub display_hash: u64,
+}
+
+/// Built-in table of test ROMs.
+///
+/// Entries are keyed by the hash of the exact ROM file, and their reference
+/// display is the one a correct interpreter leaves on the screen.
+pub const TEST_ROMS: &[TestRom] = &[
+    // roms/opcodes.ch8: runs 8 checks of arithmetic, flags, BCD, load /
+    // store, subroutines and collisions, then draws how many passed (8).
+    // On a failure it draws "F" followed by the number of the failing check.
+    TestRom {
+        name: "opcodes",
+        hash: 0x56D5_20D2_5EAE_586C,
+        quirks: Quirks::DEFAULT,
+        ticks_per_frame: 10,
+        frames: 30,
+        display_hash: 0xBFE8_F582_1A33_2455,
+    },
+];
+
+/// Result of running a test ROM
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    /// The display matched the reference
+    Passed,
+    /// The display (with the given hash) didn't match the reference
+    Failed(u64),
+}
+
+/// Returns the 64-bit FNV-1a hash of a display, with one byte per pixel
+///
+/// # Arguments
+///
+/// * `display` - The display buffer, as returned by `Chip8::get_display`
+pub fn display_hash(display: &[bool]) -> u64 {
+    let pixels: Vec<u8> = display.iter().map(|pixel| *pixel as u8).collect();
+    romdb::rom_hash(&pixels)
+}
+
+/// Returns the entry of the built-in table matching a ROM, if it's a known
+/// test ROM
+///
+/// # Arguments
+///
+/// * `data` - The contents of the ROM
+pub fn lookup(data: &[u8]) -> Option<&'static TestRom> {
+    let hash = romdb::rom_hash(data);
+    TEST_ROMS.iter().find(|test| test.hash == hash)
+}
+
+/// Runs a test ROM without a display and checks the resulting display
+/// against its reference. Errors (e.g. a stack overflow) make the run stop
+/// early, and the display is checked as it was at that point.
+///
+/// # Arguments
+///
+/// * `test` - The test ROM's entry
+/// * `data` - The contents of the ROM
+pub fn run(test: &TestRom, data: &[u8]) -> Result<Outcome, LoadError> {
+    let mut chip8 = Chip8::with_quirks(test.quirks);
+    chip8.load(data)?;
+
+    'frames: for _ in 0..test.frames {
+        for _ in 0..test.ticks_per_frame {
+            if chip8.tick().is_err() {
+                break 'frames;
+            }
+        }
+        chip8.tick_timers();
+    }
+
+    let hash = display_hash(chip8.get_display());
+    if hash == test.display_hash {
+        Ok(Outcome::Passed)
+    } else {
+        Ok(Outcome::Failed(hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    /// Draws the "0" font sprite at the top-left corner, then loops forever
+    const DRAW_ZERO: [u8; 4] = [0xD0, 0x05, 0x12, 0x02];
+
+    /// Reference display of `DRAW_ZERO`
+    fn zero_display() -> Vec<bool> {
+        glyph_display(&[0xF0, 0x90, 0x90, 0x90, 0xF0])
+    }
+
+    /// Returns a display with a font glyph at the top-left corner
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Rows of the glyph, one byte each
+    fn glyph_display(rows: &[u8]) -> Vec<bool> {
+        let mut display = vec![false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        for (y, row) in rows.iter().enumerate() {
+            for x in 0..8 {
+                display[y * SCREEN_WIDTH + x] = row & (0x80 >> x) != 0;
+            }
+        }
+        display
+    }
+
+    #[test]
+    fn run_passes_with_matching_display() {
+        let test = TestRom {
+            name: "DRAW ZERO",
+            hash: romdb::rom_hash(&DRAW_ZERO),
+            quirks: Quirks::default(),
+            ticks_per_frame: 10,
+            frames: 5,
+            display_hash: display_hash(&zero_display()),
+        };
+
+        assert_eq!(run(&test, &DRAW_ZERO), Ok(Outcome::Passed));
+
+        // Clearing the screen instead of drawing leaves a blank display
+        let blank = [0x00, 0xE0, 0x12, 0x02];
+        let expected = display_hash(&[false; SCREEN_WIDTH * SCREEN_HEIGHT]);
+        assert_eq!(run(&test, &blank), Ok(Outcome::Failed(expected)));
+    }
+
+    #[test]
+    fn bundled_opcode_test_passes() {
+        let data = include_bytes!("../roms/opcodes.ch8");
+        let test = lookup(data).unwrap();
+        assert_eq!(test.name, "opcodes");
+
+        // The reference is the "8" glyph, drawn once all 8 checks pass
+        let eight = glyph_display(&[0xF0, 0x90, 0xF0, 0x90, 0xF0]);
+        assert_eq!(test.display_hash, display_hash(&eight));
+        assert_eq!(run(test, data), Ok(Outcome::Passed));
+    }
+
+    #[test]
+    fn display_hash_depends_on_pixels() {
+        let blank = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        assert_ne!(display_hash(&blank), display_hash(&zero_display()));
+    }
+}